@@ -7,6 +7,6 @@ pub const MAX_DRIVER_PORT: u32 = 8100;
 pub const MIN_PASSENGER_PORT: u32 = 8000;
 pub const MAX_PASSENGER_PORT: u32 = 8020;
 pub const PAYMENT_PORT: u32 = 3000;
+/// Lado de la grilla del mapa, las coordenadas validas van de 0 a MAP_SIZE - 1
+pub const MAP_SIZE: u32 = 100;
 pub const LOG_LEVEL: LevelFilter = LevelFilter::Debug;
-
-
@@ -1,18 +1,53 @@
 use serde::{Deserialize, Serialize};
 
-use super::position::Position;
+use super::{position::Position, pricing::FareQuote};
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub enum TripStatus {
-    RequestDelivered,
+    /// El pedido fue recibido, con el presupuesto del viaje
+    RequestDelivered {
+        quote: FareQuote,
+    },
     Info,
     Success,
     Error,
+    Cancelled,
+    /// El viaje quedo reservado para mas adelante; la busqueda de un driver empieza en `search_at`
+    /// (en milisegundos desde UNIX_EPOCH)
+    Scheduled {
+        booking_id: u64,
+        search_at: u64,
+    },
+}
+
+/// Viaje reservado por un pasajero para buscarlo mas adelante
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Booking {
+    /// Id de la reserva
+    pub booking_id: u64,
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+    /// Paradas intermedias, en el orden en que se recorren
+    pub stops: Vec<Position>,
+    /// Momento en el que el pasajero quiere que lo busquen, en milisegundos desde UNIX_EPOCH
+    pub pickup_at: u64,
+    /// Presupuesto que acepto el pasajero al reservar
+    pub quote: FareQuote,
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum CommonMessages {
-    Identification { id: u32, type_: char },
+    Identification {
+        id: u32,
+        type_: char,
+        /// Direccion en la que escucha el driver, los pasajeros no la envian
+        #[serde(default)]
+        addr: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -20,22 +55,62 @@ pub enum TripMessages {
     TripRequest {
         source: Position,
         destination: Position,
+        /// Paradas intermedias, en el orden en que se recorren
+        #[serde(default)]
+        stops: Vec<Position>,
+        /// Momento en el que el pasajero quiere que lo busquen, en milisegundos desde UNIX_EPOCH.
+        /// Si no esta, el viaje se busca en el momento
+        #[serde(default)]
+        pickup_at: Option<u64>,
     },
     TripResponse {
         status: TripStatus,
         detail: String,
     },
     Listening {},
+    Cancel {},
+    /// Presupuesto del viaje pedido, que el pasajero tiene que aceptar antes de que se lo busque
+    Quote {
+        quote: FareQuote,
+    },
+    /// Respuesta del pasajero al presupuesto
+    QuoteAnswer {
+        accepted: bool,
+    },
+    /// Pedido de las reservas pendientes del pasajero
+    ListBookings {},
+    /// Pedido de cancelacion de una reserva del pasajero
+    CancelBooking {
+        booking_id: u64,
+    },
+    /// Reservas pendientes del pasajero
+    Bookings {
+        bookings: Vec<Booking>,
+    },
 }
 
 #[derive(Deserialize, Serialize)]
 pub enum PaymentMessages {
-    AuthPayment { passenger_id: u32 },
-    CollectPayment { driver_id: u32, passenger_id: u32 },
+    AuthPayment {
+        passenger_id: u32,
+    },
+    CollectPayment {
+        driver_id: u32,
+        passenger_id: u32,
+        /// Precio del viaje
+        amount: u32,
+    },
+    CollectCancellationFee {
+        driver_id: u32,
+        passenger_id: u32,
+        /// Multa por cancelar el viaje
+        amount: u32,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum PaymentResponses {
     AuthPayment { passenger_id: u32, response: bool },
     CollectPayment { passenger_id: u32, response: bool },
+    CollectCancellationFee { passenger_id: u32, response: bool },
 }
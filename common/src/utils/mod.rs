@@ -1,3 +1,6 @@
+pub mod consts;
 pub mod json_parser;
 pub mod position;
-pub mod consts;
+pub mod pricing;
+pub mod seeds;
+pub mod time;
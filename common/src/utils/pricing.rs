@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::position::Position;
+
+/// Tarifas con las que se calcula el precio de un viaje, en pesos
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pricing {
+    /// Costo fijo de cada viaje, que tambien se cobra como multa al cancelarlo
+    pub base_fee: u32,
+    /// Costo por cuadra desde la posicion del pasajero hasta su destino
+    pub per_block: u32,
+    /// Costo por segundo que el conductor espera al pasajero en su puerta
+    pub per_waiting_second: u32,
+}
+
+/// Presupuesto de un viaje, que se le envia al pasajero al recibir su pedido
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FareQuote {
+    /// Cuadras desde la posicion del pasajero hasta su destino (distancia Manhattan)
+    pub distance: u32,
+    /// Multiplicador por alta demanda en la zona del pasajero, 1 si no hay
+    pub surge: f64,
+    /// Precio del viaje sin contar la espera, con el multiplicador aplicado
+    pub amount: u32,
+}
+
+impl Pricing {
+    /// Crea las tarifas con los costos dados.
+    pub fn new(base_fee: u32, per_block: u32, per_waiting_second: u32) -> Self {
+        Self {
+            base_fee,
+            per_block,
+            per_waiting_second,
+        }
+    }
+
+    /// Presupuesta un viaje con el multiplicador por demanda dado, sin contar la espera que todavia
+    /// no se conoce.
+    pub fn quote(&self, source: &Position, destination: &Position, surge: f64) -> FareQuote {
+        self.route_quote(source, &[], destination, surge)
+    }
+
+    /// Presupuesta un viaje que pasa por las paradas intermedias dadas, en orden, con el multiplicador
+    /// por demanda dado. La distancia es la suma de las de cada tramo.
+    pub fn route_quote(
+        &self,
+        source: &Position,
+        stops: &[Position],
+        destination: &Position,
+        surge: f64,
+    ) -> FareQuote {
+        let mut distance: u32 = 0;
+        let mut from = source;
+
+        for to in stops.iter().chain(std::iter::once(destination)) {
+            distance = distance.saturating_add(from.distance_to(to));
+            from = to;
+        }
+
+        FareQuote {
+            distance,
+            surge,
+            amount: self.amount(distance, surge),
+        }
+    }
+
+    /// Ajusta el presupuesto de un pasajero que compartio el auto: solo paga su parte de las cuadras
+    /// que recorrio, repartidas entre los pasajeros que iban en el auto en cada tramo, y nunca mas de
+    /// lo que se le presupuesto.
+    pub fn shared_quote(&self, quote: &FareQuote, blocks: f64) -> FareQuote {
+        let distance = (blocks.round() as u32).min(quote.distance);
+
+        FareQuote {
+            distance,
+            surge: quote.surge,
+            amount: self.amount(distance, quote.surge).min(quote.amount),
+        }
+    }
+
+    /// Calcula el precio final de un viaje: el presupuesto mas el tiempo que se espero al pasajero,
+    /// al que no se le aplica el multiplicador.
+    pub fn fare(&self, quote: &FareQuote, waiting: Duration) -> u32 {
+        let waiting_fee = (self.per_waiting_second as f64 * waiting.as_secs_f64()).round() as u32;
+
+        quote.amount.saturating_add(waiting_fee)
+    }
+
+    /// Precio de recorrer la distancia dada con el multiplicador por demanda dado
+    fn amount(&self, distance: u32, surge: f64) -> u32 {
+        let amount = self
+            .base_fee
+            .saturating_add(self.per_block.saturating_mul(distance));
+
+        (amount as f64 * surge).round() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        let pricing = Pricing::new(500, 40, 10);
+        let quote = pricing.quote(&Position::new(10, 20), &Position::new(15, 30), 1.0);
+
+        assert_eq!(quote.distance, 15);
+        assert_eq!(quote.amount, 500 + 40 * 15);
+    }
+
+    #[test]
+    fn test_surge_multiplies_the_quote() {
+        let pricing = Pricing::new(500, 40, 10);
+        let quote = pricing.quote(&Position::new(10, 20), &Position::new(15, 30), 1.5);
+
+        assert_eq!(quote.surge, 1.5);
+        assert_eq!(quote.amount, (500 + 40 * 15) * 3 / 2);
+    }
+
+    #[test]
+    fn test_fare_adds_waiting_time() {
+        let pricing = Pricing::new(500, 40, 10);
+        let quote = pricing.quote(&Position::new(0, 0), &Position::new(3, 4), 1.0);
+
+        assert_eq!(pricing.fare(&quote, Duration::ZERO), quote.amount);
+        assert_eq!(
+            pricing.fare(&quote, Duration::from_millis(2500)),
+            500 + 40 * 7 + 25
+        );
+    }
+
+    #[test]
+    fn test_route_quote_adds_every_leg() {
+        let pricing = Pricing::new(500, 40, 10);
+        let quote = pricing.route_quote(
+            &Position::new(0, 0),
+            &[Position::new(10, 0), Position::new(10, 10)],
+            &Position::new(0, 10),
+            1.0,
+        );
+
+        assert_eq!(quote.distance, 30);
+        assert_eq!(quote.amount, 500 + 40 * 30);
+    }
+
+    #[test]
+    fn test_shared_quote_charges_the_share() {
+        let pricing = Pricing::new(500, 40, 10);
+        let quote = pricing.quote(&Position::new(0, 0), &Position::new(10, 10), 1.5);
+
+        let shared = pricing.shared_quote(&quote, 12.4);
+        assert_eq!(shared.distance, 12);
+        assert_eq!(shared.amount, ((500 + 40 * 12) as f64 * 1.5).round() as u32);
+
+        assert_eq!(pricing.shared_quote(&quote, 30.0), quote);
+    }
+
+    #[test]
+    fn test_out_of_bounds_saturates() {
+        let pricing = Pricing::new(500, 40, 10);
+        let quote = pricing.quote(&Position::new(0, 0), &Position::infinity(), 1.0);
+
+        assert_eq!(quote.amount, u32::MAX);
+    }
+}
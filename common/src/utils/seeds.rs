@@ -0,0 +1,40 @@
+use super::consts::{HOST, MAX_DRIVER_PORT, MIN_DRIVER_PORT};
+
+/// Devuelve las direcciones de los drivers a traves de los cuales unirse al cluster.
+/// Se leen de la variable de entorno DRIVER_SEEDS, separadas por comas
+/// (por ejemplo "127.0.0.1:8080,127.0.0.2:8080").
+/// Si la variable no esta definida, son todos los puertos de drivers en HOST.
+pub fn driver_seeds() -> Vec<String> {
+    match std::env::var("DRIVER_SEEDS") {
+        Ok(seeds) => parse_seeds(&seeds),
+        Err(_) => (MIN_DRIVER_PORT..=MAX_DRIVER_PORT)
+            .map(|port| format!("{}:{}", HOST, port))
+            .collect(),
+    }
+}
+
+/// Parsea una lista de direcciones separadas por comas, ignorando las vacias.
+pub fn parse_seeds(seeds: &str) -> Vec<String> {
+    seeds
+        .split(',')
+        .map(|seed| seed.trim())
+        .filter(|seed| !seed.is_empty())
+        .map(|seed| seed.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_seeds() {
+        let seeds = parse_seeds("127.0.0.1:8080, 127.0.0.2:8080,,");
+        assert_eq!(seeds, vec!["127.0.0.1:8080", "127.0.0.2:8080"]);
+    }
+
+    #[test]
+    fn test_parse_empty_seeds() {
+        assert!(parse_seeds("").is_empty());
+    }
+}
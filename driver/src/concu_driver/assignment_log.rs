@@ -0,0 +1,765 @@
+use std::collections::HashMap;
+
+use common::utils::{json_parser::Booking, position::Position};
+use serde::{Deserialize, Serialize};
+
+/// Asignacion de un viaje a un driver
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Assignment {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id del driver que lleva al pasajero
+    pub driver_id: u32,
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+    /// Id de la oferta que acepto el driver
+    pub offer_id: u64,
+}
+
+/// Operacion registrada en el log de asignaciones
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AssignmentOp {
+    /// Entrada vacia que agrega cada lider al asumir, para confirmar las entradas de terminos anteriores
+    Noop,
+    /// Se le asigno un viaje a un driver
+    Assign(Assignment),
+    /// Termino (o se cancelo) el viaje de un pasajero
+    Complete { passenger_id: u32 },
+    /// El driver solto el viaje de un pasajero (o se lo dio por muerto), para que se le asigne otro driver.
+    /// Lleva la oferta de la asignacion liberada, para que el driver que la tenia la abandone
+    Release { passenger_id: u32, offer_id: u64 },
+    /// Un pasajero reservo un viaje para mas adelante
+    Book(Booking),
+    /// Llego la hora de buscarle un driver a un viaje reservado, que deja de estar pendiente
+    StartBooking(Booking),
+    /// El pasajero cancelo un viaje reservado
+    CancelBooking { booking_id: u64 },
+}
+
+/// Entrada del log de asignaciones
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    /// Termino del lider que agrego la entrada
+    pub term: u64,
+    /// Operacion registrada
+    pub op: AssignmentOp,
+}
+
+/// Estado que resulta de aplicar las entradas del log
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LogState {
+    /// Asignaciones activas, segun el id del pasajero
+    pub assignments: HashMap<u32, Assignment>,
+    /// Viajes reservados que todavia no empezaron a buscarse, segun el id de la reserva
+    pub bookings: HashMap<u64, Booking>,
+}
+
+impl LogState {
+    /// Aplica una operacion al estado.
+    fn apply(&mut self, op: &AssignmentOp) {
+        match op {
+            AssignmentOp::Noop => (),
+            AssignmentOp::Assign(assignment) => {
+                self.assignments
+                    .insert(assignment.passenger_id, *assignment);
+            }
+            AssignmentOp::Complete { passenger_id }
+            | AssignmentOp::Release { passenger_id, .. } => {
+                self.assignments.remove(passenger_id);
+            }
+            AssignmentOp::Book(booking) => {
+                self.bookings.insert(booking.booking_id, booking.clone());
+            }
+            AssignmentOp::StartBooking(Booking { booking_id, .. })
+            | AssignmentOp::CancelBooking { booking_id } => {
+                self.bookings.remove(booking_id);
+            }
+        }
+    }
+}
+
+/// Estado del log de asignaciones hasta una entrada confirmada, que reemplaza a las entradas compactadas
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LogSnapshot {
+    /// Indice de la ultima entrada incluida
+    pub last_index: u64,
+    /// Termino de la ultima entrada incluida
+    pub last_term: u64,
+    /// Asignaciones activas y viajes reservados hasta esa entrada
+    pub state: LogState,
+    /// Indice de la ultima entrada que le asigno un viaje a cada driver hasta esa entrada, segun su id
+    pub last_assignments: HashMap<u32, u64>,
+}
+
+/// Pedido de replicacion del lider a un seguidor
+pub struct AppendRequest {
+    /// Snapshot a instalar antes de agregar las entradas, si el seguidor necesita entradas ya compactadas
+    pub snapshot: Option<LogSnapshot>,
+    /// Indice de la entrada anterior a las enviadas
+    pub prev_index: u64,
+    /// Termino de la entrada anterior a las enviadas
+    pub prev_term: u64,
+    /// Entradas a agregar
+    pub entries: Vec<LogEntry>,
+}
+
+/// Log de asignaciones de viajes replicado al estilo Raft.
+///
+/// Las entradas se numeran desde 1. Una entrada esta confirmada cuando el lider la replico en la
+/// mayoria de los drivers, y recien ahi se le avisa al driver y al pasajero de la asignacion.
+/// El log tambien registra los viajes reservados, asi una reserva confirmada no se pierde si cae el lider.
+///
+/// Las asignaciones activas y las reservas se mantienen materializadas a medida que se agregan y confirman entradas,
+/// asi las consultas no recorren el log. Las entradas confirmadas se compactan en un snapshot, que el
+/// lider le envia a los seguidores que necesitan entradas que ya no tiene.
+#[derive(Default)]
+pub struct AssignmentLog {
+    /// Estado de las entradas compactadas
+    snapshot: LogSnapshot,
+    /// Entradas posteriores al snapshot, la entrada i esta en la posicion i - snapshot.last_index - 1
+    entries: Vec<LogEntry>,
+    /// Indice de la ultima entrada confirmada
+    commit_index: u64,
+    /// Indice de la proxima entrada a enviarle a cada seguidor, segun su id (solo en el lider)
+    next_index: HashMap<u32, u64>,
+    /// Indice de la ultima entrada replicada en cada seguidor, segun su id (solo en el lider)
+    match_index: HashMap<u32, u64>,
+    /// Estado segun las entradas confirmadas
+    committed: LogState,
+    /// Estado contando las entradas no confirmadas
+    state: LogState,
+    /// Indice de la ultima entrada que le asigno un viaje a cada driver, segun su id,
+    /// contando las entradas no confirmadas
+    last_assignments: HashMap<u32, u64>,
+}
+
+impl AssignmentLog {
+    /// Crea un log vacio.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indice de la ultima entrada del log, 0 si esta vacio.
+    pub fn last_index(&self) -> u64 {
+        self.snapshot.last_index + self.entries.len() as u64
+    }
+
+    /// Termino de la ultima entrada del log, 0 si esta vacio.
+    pub fn last_term(&self) -> u64 {
+        self.term_at(self.last_index())
+    }
+
+    /// Indice de la ultima entrada confirmada.
+    pub fn commit_index(&self) -> u64 {
+        self.commit_index
+    }
+
+    /// Posicion en `entries` de la entrada con el indice dado, que no debe estar compactada.
+    fn position(&self, index: u64) -> usize {
+        (index - self.snapshot.last_index - 1) as usize
+    }
+
+    /// Termino de la entrada con el indice dado, 0 si no existe o si esta compactada
+    /// (salvo la ultima del snapshot).
+    fn term_at(&self, index: u64) -> u64 {
+        if index <= self.snapshot.last_index {
+            return match index == self.snapshot.last_index {
+                true => self.snapshot.last_term,
+                false => 0,
+            };
+        }
+
+        self.entries
+            .get(self.position(index))
+            .map(|entry| entry.term)
+            .unwrap_or_default()
+    }
+
+    /// Agrega una entrada al final del log (solo en el lider) y devuelve su indice.
+    pub fn append(&mut self, term: u64, op: AssignmentOp) -> u64 {
+        let index = self.last_index() + 1;
+        self.state.apply(&op);
+        Self::track_last_assignment(&mut self.last_assignments, index, &op);
+        self.entries.push(LogEntry { term, op });
+
+        index
+    }
+
+    /// Verifica si el log de un candidato, dado por el termino y el indice de su ultima entrada,
+    /// esta al menos tan actualizado como este.
+    pub fn is_up_to_date(&self, last_term: u64, last_index: u64) -> bool {
+        (last_term, last_index) >= (self.last_term(), self.last_index())
+    }
+
+    /// Arma el pedido de replicacion para un seguidor, con las entradas que todavia no tiene.
+    /// Si alguna ya fue compactada, el pedido incluye el snapshot y todas las entradas posteriores.
+    pub fn append_request(&self, driver_id: u32) -> AppendRequest {
+        let next_index = self
+            .next_index
+            .get(&driver_id)
+            .copied()
+            .unwrap_or(1)
+            .min(self.last_index() + 1);
+
+        if next_index <= self.snapshot.last_index {
+            return AppendRequest {
+                snapshot: Some(self.snapshot.clone()),
+                prev_index: self.snapshot.last_index,
+                prev_term: self.snapshot.last_term,
+                entries: self.entries.clone(),
+            };
+        }
+
+        let prev_index = next_index - 1;
+
+        AppendRequest {
+            snapshot: None,
+            prev_index,
+            prev_term: self.term_at(prev_index),
+            entries: self.entries[self.position(next_index)..].to_vec(),
+        }
+    }
+
+    /// Aplica un pedido de replicacion del lider (en un seguidor).
+    /// Si el pedido trae un snapshot, primero lo instala.
+    /// Si el log no contiene la entrada anterior a las enviadas, devuelve None para que el lider
+    /// envie entradas anteriores. Si no, descarta las entradas que no coinciden con las del lider,
+    /// agrega las nuevas, adopta el indice de confirmacion del lider y devuelve el indice de la
+    /// ultima entrada replicada.
+    pub fn handle_append(
+        &mut self,
+        snapshot: Option<LogSnapshot>,
+        prev_index: u64,
+        prev_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    ) -> Option<u64> {
+        if let Some(snapshot) = snapshot {
+            self.install_snapshot(snapshot);
+        }
+
+        if prev_index > self.last_index()
+            || (prev_index >= self.snapshot.last_index && self.term_at(prev_index) != prev_term)
+        {
+            return None;
+        }
+
+        let mut truncated = false;
+
+        for (offset, entry) in entries.iter().enumerate() {
+            let index = prev_index + offset as u64 + 1;
+
+            if index <= self.snapshot.last_index {
+                continue;
+            }
+
+            if index <= self.last_index() && self.term_at(index) != entry.term {
+                let position = self.position(index);
+                self.entries.truncate(position);
+                truncated = true;
+            }
+
+            if index > self.last_index() {
+                if !truncated {
+                    self.state.apply(&entry.op);
+                    Self::track_last_assignment(&mut self.last_assignments, index, &entry.op);
+                }
+
+                self.entries.push(entry.clone());
+            }
+        }
+
+        if truncated {
+            self.rebuild();
+        }
+
+        let match_index = prev_index + entries.len() as u64;
+
+        if leader_commit > self.commit_index {
+            self.commit_up_to(leader_commit.min(match_index));
+        }
+
+        Some(match_index)
+    }
+
+    /// Reemplaza las entradas hasta la ultima del snapshot por el snapshot (en un seguidor).
+    /// Si el log tiene esa entrada, conserva las posteriores; si no, las descarta.
+    fn install_snapshot(&mut self, snapshot: LogSnapshot) {
+        if snapshot.last_index <= self.snapshot.last_index {
+            return;
+        }
+
+        if snapshot.last_index <= self.last_index()
+            && self.term_at(snapshot.last_index) == snapshot.last_term
+        {
+            let position = self.position(snapshot.last_index);
+            self.entries.drain(..=position);
+        } else {
+            self.entries.clear();
+        }
+
+        self.commit_index = self.commit_index.max(snapshot.last_index);
+        self.snapshot = snapshot;
+        self.rebuild();
+    }
+
+    /// Registra la respuesta de un seguidor a un pedido de replicacion (en el lider).
+    /// - Si la acepto, actualiza hasta donde tiene replicado el log.
+    /// - Si no, retrocede la proxima entrada a enviarle, usando como pista su ultimo indice.
+    pub fn handle_ack(&mut self, driver_id: u32, success: bool, match_index: u64) {
+        if success {
+            self.match_index.insert(driver_id, match_index);
+            self.next_index.insert(driver_id, match_index + 1);
+            return;
+        }
+
+        let next_index = self.next_index.get(&driver_id).copied().unwrap_or(1);
+        let next_index = (next_index - 1).min(match_index + 1).max(1);
+
+        self.next_index.insert(driver_id, next_index);
+    }
+
+    /// Reinicia el progreso de replicacion de los seguidores al asumir como lider, asi las copias que
+    /// registro en un liderazgo anterior no cuentan para la mayoria del termino nuevo: a cada seguidor
+    /// se le empieza enviando desde el final del log y se lo considera sin entradas replicadas.
+    pub fn become_leader(&mut self, followers: impl IntoIterator<Item = u32>) {
+        let next_index = self.last_index() + 1;
+
+        self.next_index.clear();
+        self.match_index.clear();
+
+        for driver_id in followers {
+            self.next_index.insert(driver_id, next_index);
+            self.match_index.insert(driver_id, 0);
+        }
+    }
+
+    /// Olvida el progreso de replicacion de un seguidor, por ejemplo al perder la conexion con el.
+    pub fn forget_follower(&mut self, driver_id: u32) {
+        self.next_index.remove(&driver_id);
+        self.match_index.remove(&driver_id);
+    }
+
+    /// Avanza el indice de confirmacion hasta la ultima entrada del termino actual replicada en la
+    /// mayoria del cluster (contando al lider) y devuelve las entradas recien confirmadas.
+    /// `quorum` es la mayoria del cluster completo, no solo de los seguidores conectados.
+    pub fn advance_commit(&mut self, term: u64, quorum: usize) -> Vec<LogEntry> {
+        let previous_commit = self.commit_index;
+
+        for index in (previous_commit + 1..=self.last_index()).rev() {
+            let replicas = 1 + self
+                .match_index
+                .values()
+                .filter(|match_index| **match_index >= index)
+                .count();
+
+            if self.term_at(index) == term && replicas >= quorum {
+                self.commit_up_to(index);
+                break;
+            }
+        }
+
+        self.committed_since(previous_commit)
+    }
+
+    /// Confirma las entradas hasta el indice dado y las aplica a las asignaciones confirmadas.
+    fn commit_up_to(&mut self, index: u64) {
+        for i in self.commit_index + 1..=index {
+            let position = self.position(i);
+            self.committed.apply(&self.entries[position].op);
+        }
+
+        self.commit_index = self.commit_index.max(index);
+    }
+
+    /// Compacta en el snapshot las entradas confirmadas, si hay al menos `threshold` sin compactar.
+    /// Devuelve si se compacto el log.
+    pub fn compact(&mut self, threshold: usize) -> bool {
+        let count = (self.commit_index - self.snapshot.last_index) as usize;
+
+        if count == 0 || count < threshold {
+            return false;
+        }
+
+        let last_term = self.term_at(self.commit_index);
+
+        for (offset, entry) in self.entries[..count].iter().enumerate() {
+            let index = self.snapshot.last_index + offset as u64 + 1;
+            Self::track_last_assignment(&mut self.snapshot.last_assignments, index, &entry.op);
+        }
+
+        self.entries.drain(..count);
+        self.snapshot.last_index = self.commit_index;
+        self.snapshot.last_term = last_term;
+        self.snapshot.state = self.committed.clone();
+
+        true
+    }
+
+    /// Asignaciones activas segun las entradas confirmadas, segun el id del pasajero.
+    pub fn active_assignments(&self) -> HashMap<u32, Assignment> {
+        self.committed.assignments.clone()
+    }
+
+    /// Asignacion activa del pasajero, contando las entradas no confirmadas.
+    pub fn assignment_of(&self, passenger_id: u32) -> Option<&Assignment> {
+        self.state.assignments.get(&passenger_id)
+    }
+
+    /// Verifica si el pasajero tiene un viaje asignado, contando las entradas no confirmadas.
+    pub fn passenger_assigned(&self, passenger_id: u32) -> bool {
+        self.state.assignments.contains_key(&passenger_id)
+    }
+
+    /// Verifica si el driver tiene un viaje asignado, contando las entradas no confirmadas.
+    pub fn driver_assigned(&self, driver_id: u32) -> bool {
+        self.state
+            .assignments
+            .values()
+            .any(|assignment| assignment.driver_id == driver_id)
+    }
+
+    /// Viajes reservados segun las entradas confirmadas, segun el id de la reserva.
+    pub fn committed_bookings(&self) -> &HashMap<u64, Booking> {
+        &self.committed.bookings
+    }
+
+    /// Viajes reservados contando las entradas no confirmadas, segun el id de la reserva.
+    /// Una reserva cuya busqueda o cancelacion todavia no se confirmo ya no aparece.
+    pub fn bookings(&self) -> &HashMap<u64, Booking> {
+        &self.state.bookings
+    }
+
+    /// Indice de la ultima entrada que le asigno un viaje a cada driver, segun su id,
+    /// contando las entradas no confirmadas.
+    pub fn last_assignments(&self) -> &HashMap<u32, u64> {
+        &self.last_assignments
+    }
+
+    /// Entradas confirmadas despues del indice dado, sin las que ya fueron compactadas.
+    pub fn committed_since(&self, index: u64) -> Vec<LogEntry> {
+        let from = index.max(self.snapshot.last_index).min(self.commit_index);
+
+        self.entries
+            [(from - self.snapshot.last_index) as usize..self.position(self.commit_index + 1)]
+            .to_vec()
+    }
+
+    /// Recalcula el estado materializado a partir del snapshot y las entradas, por ejemplo al
+    /// descartar entradas que no coinciden con las del lider.
+    fn rebuild(&mut self) {
+        self.committed = self.snapshot.state.clone();
+        self.last_assignments = self.snapshot.last_assignments.clone();
+
+        let committed = (self.commit_index - self.snapshot.last_index) as usize;
+
+        for entry in &self.entries[..committed] {
+            self.committed.apply(&entry.op);
+        }
+
+        self.state = self.committed.clone();
+
+        for entry in &self.entries[committed..] {
+            self.state.apply(&entry.op);
+        }
+
+        for (offset, entry) in self.entries.iter().enumerate() {
+            let index = self.snapshot.last_index + offset as u64 + 1;
+            Self::track_last_assignment(&mut self.last_assignments, index, &entry.op);
+        }
+    }
+
+    /// Si la operacion con el indice dado asigna un viaje, la registra como la ultima del driver.
+    fn track_last_assignment(
+        last_assignments: &mut HashMap<u32, u64>,
+        index: u64,
+        op: &AssignmentOp,
+    ) {
+        if let AssignmentOp::Assign(assignment) = op {
+            last_assignments.insert(assignment.driver_id, index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::utils::pricing::FareQuote;
+
+    use super::*;
+
+    fn assign(passenger_id: u32, driver_id: u32) -> AssignmentOp {
+        AssignmentOp::Assign(Assignment {
+            passenger_id,
+            driver_id,
+            source: Position::new(0, 0),
+            destination: Position::new(10, 10),
+            offer_id: passenger_id as u64,
+        })
+    }
+
+    #[test]
+    fn test_commit_needs_majority() {
+        let mut log = AssignmentLog::new();
+        log.append(1, assign(1, 2));
+
+        assert!(log.advance_commit(1, 2).is_empty());
+        assert!(log.active_assignments().is_empty());
+        assert!(log.passenger_assigned(1));
+
+        log.handle_ack(2, true, 1);
+        assert_eq!(log.advance_commit(1, 2).len(), 1);
+        assert_eq!(log.commit_index(), 1);
+        assert_eq!(log.active_assignments().get(&1).unwrap().driver_id, 2);
+    }
+
+    #[test]
+    fn test_previous_term_entries_commit_with_current_term() {
+        let mut log = AssignmentLog::new();
+        log.append(1, assign(1, 2));
+        log.handle_ack(2, true, 1);
+
+        assert!(log.advance_commit(2, 2).is_empty());
+
+        log.append(2, AssignmentOp::Noop);
+        log.handle_ack(2, true, 2);
+        assert_eq!(log.advance_commit(2, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_follower_replaces_conflicting_entries() {
+        let mut leader = AssignmentLog::new();
+        leader.append(1, assign(1, 2));
+        leader.append(2, assign(2, 3));
+
+        let mut follower = AssignmentLog::new();
+        follower.append(1, assign(1, 2));
+        follower.append(1, assign(3, 4));
+        follower.append(1, assign(4, 5));
+
+        let request = leader.append_request(7);
+        assert_eq!(request.prev_index, 0);
+        assert_eq!(
+            follower.handle_append(
+                request.snapshot,
+                request.prev_index,
+                request.prev_term,
+                request.entries,
+                1
+            ),
+            Some(2)
+        );
+        assert_eq!(follower.last_index(), 2);
+        assert_eq!(follower.last_term(), 2);
+        assert_eq!(follower.commit_index(), 1);
+        assert!(!follower.passenger_assigned(3));
+    }
+
+    #[test]
+    fn test_leader_backs_off_when_follower_is_behind() {
+        let mut leader = AssignmentLog::new();
+        leader.append(1, assign(1, 2));
+        leader.append(1, assign(2, 3));
+        leader.handle_ack(7, true, 2);
+        leader.append(1, assign(3, 4));
+
+        let mut follower = AssignmentLog::new();
+        let request = leader.append_request(7);
+        assert_eq!(request.prev_index, 2);
+        assert_eq!(
+            follower.handle_append(
+                request.snapshot,
+                request.prev_index,
+                request.prev_term,
+                request.entries,
+                0
+            ),
+            None
+        );
+
+        leader.handle_ack(7, false, follower.last_index());
+        let request = leader.append_request(7);
+        assert_eq!(request.prev_index, 0);
+        assert_eq!(
+            follower.handle_append(
+                request.snapshot,
+                request.prev_index,
+                request.prev_term,
+                request.entries,
+                0
+            ),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_new_leader_forgets_old_replicas() {
+        let mut log = AssignmentLog::new();
+        log.append(1, assign(1, 2));
+        log.handle_ack(7, true, 1);
+
+        log.become_leader([7, 8]);
+        log.append(2, AssignmentOp::Noop);
+        assert!(log.advance_commit(2, 2).is_empty());
+        assert_eq!(log.append_request(7).prev_index, 1);
+
+        log.handle_ack(7, true, 2);
+        assert_eq!(log.advance_commit(2, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_complete_frees_driver_and_passenger() {
+        let mut log = AssignmentLog::new();
+        log.append(1, assign(1, 2));
+        assert!(log.driver_assigned(2));
+
+        log.append(1, AssignmentOp::Complete { passenger_id: 1 });
+        assert!(!log.driver_assigned(2));
+        assert!(!log.passenger_assigned(1));
+    }
+
+    #[test]
+    fn test_release_frees_driver_and_passenger() {
+        let mut log = AssignmentLog::new();
+        log.append(1, assign(1, 2));
+        log.append(
+            1,
+            AssignmentOp::Release {
+                passenger_id: 1,
+                offer_id: 1,
+            },
+        );
+        assert!(!log.driver_assigned(2));
+        assert!(!log.passenger_assigned(1));
+
+        log.append(1, assign(1, 3));
+        assert!(log.passenger_assigned(1));
+        assert!(log.driver_assigned(3));
+    }
+
+    fn booking(booking_id: u64) -> Booking {
+        Booking {
+            booking_id,
+            passenger_id: 1,
+            source: Position::new(0, 0),
+            destination: Position::new(10, 10),
+            stops: Vec::new(),
+            pickup_at: 0,
+            quote: FareQuote {
+                distance: 20,
+                surge: 1.0,
+                amount: 1300,
+            },
+        }
+    }
+
+    #[test]
+    fn test_bookings_follow_commits() {
+        let mut log = AssignmentLog::new();
+        log.append(1, AssignmentOp::Book(booking(1)));
+        log.append(1, AssignmentOp::Book(booking(2)));
+        assert!(log.committed_bookings().is_empty());
+        assert_eq!(log.bookings().len(), 2);
+
+        log.handle_ack(7, true, 2);
+        assert_eq!(log.advance_commit(1, 2).len(), 2);
+        assert_eq!(log.committed_bookings().len(), 2);
+
+        log.append(1, AssignmentOp::StartBooking(booking(1)));
+        log.append(1, AssignmentOp::CancelBooking { booking_id: 2 });
+        assert!(log.bookings().is_empty());
+        assert_eq!(log.committed_bookings().len(), 2);
+
+        log.handle_ack(7, true, 4);
+        log.advance_commit(1, 2);
+        log.compact(1);
+        assert!(log.committed_bookings().is_empty());
+    }
+
+    #[test]
+    fn test_last_assignments() {
+        let mut log = AssignmentLog::new();
+        log.append(1, assign(1, 2));
+        log.append(1, assign(2, 3));
+        log.append(1, AssignmentOp::Complete { passenger_id: 1 });
+        log.append(1, assign(4, 2));
+
+        let last_assignments = log.last_assignments();
+        assert_eq!(last_assignments.get(&2), Some(&4));
+        assert_eq!(last_assignments.get(&3), Some(&2));
+        assert_eq!(last_assignments.get(&5), None);
+    }
+
+    #[test]
+    fn test_up_to_date_candidate() {
+        let mut log = AssignmentLog::new();
+        log.append(1, AssignmentOp::Noop);
+        log.append(2, AssignmentOp::Noop);
+
+        assert!(log.is_up_to_date(2, 2));
+        assert!(log.is_up_to_date(3, 1));
+        assert!(!log.is_up_to_date(2, 1));
+        assert!(!log.is_up_to_date(1, 5));
+    }
+
+    #[test]
+    fn test_compact_keeps_assignments() {
+        let mut log = AssignmentLog::new();
+        log.append(1, assign(1, 2));
+        log.append(1, assign(2, 3));
+        log.append(1, AssignmentOp::Complete { passenger_id: 1 });
+        log.append(1, assign(4, 5));
+        log.handle_ack(7, true, 3);
+        assert_eq!(log.advance_commit(1, 2).len(), 3);
+
+        assert!(!log.compact(4));
+        assert!(log.compact(3));
+        assert_eq!(log.last_index(), 4);
+        assert_eq!(log.last_term(), 1);
+        assert_eq!(log.active_assignments().len(), 1);
+        assert!(log.passenger_assigned(2));
+        assert!(log.passenger_assigned(4));
+        assert!(!log.driver_assigned(2));
+        assert_eq!(log.last_assignments().get(&2), Some(&1));
+        assert_eq!(log.last_assignments().get(&5), Some(&4));
+        assert_eq!(log.committed_since(0), vec![]);
+
+        log.handle_ack(7, true, 4);
+        assert_eq!(log.advance_commit(1, 2).len(), 1);
+        assert_eq!(log.active_assignments().len(), 2);
+    }
+
+    #[test]
+    fn test_lagging_follower_installs_snapshot() {
+        let mut leader = AssignmentLog::new();
+        leader.append(1, assign(1, 2));
+        leader.append(1, assign(2, 3));
+        leader.handle_ack(7, true, 2);
+        leader.advance_commit(1, 2);
+        leader.compact(1);
+        leader.append(1, AssignmentOp::Complete { passenger_id: 1 });
+
+        let mut follower = AssignmentLog::new();
+        follower.append(1, assign(1, 2));
+
+        let request = leader.append_request(8);
+        assert_eq!(request.snapshot.as_ref().map(|s| s.last_index), Some(2));
+        assert_eq!(request.entries.len(), 1);
+        assert_eq!(
+            follower.handle_append(
+                request.snapshot,
+                request.prev_index,
+                request.prev_term,
+                request.entries,
+                2
+            ),
+            Some(3)
+        );
+        assert_eq!(follower.last_index(), 3);
+        assert_eq!(follower.commit_index(), 2);
+        assert_eq!(follower.active_assignments().len(), 2);
+        assert!(!follower.passenger_assigned(1));
+        assert!(follower.passenger_assigned(2));
+    }
+}
@@ -1,25 +1,64 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, SpawnHandle};
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, MessageResult, SpawnHandle};
 use actix_async_handler::async_handler;
 use common::utils::{
     json_parser::{PaymentMessages, TripMessages, TripStatus},
     position::Position,
+    pricing::{FareQuote, Pricing},
 };
+use rand::seq::IteratorRandom;
+use tokio::sync::oneshot;
 
 use crate::concu_driver::{
     driver_connection::SendAll,
-    handle_trip::{ClearPassenger, ForceNotifyPosition},
-    json_parser::DriverMessages,
+    handle_trip::{ClearPassenger, Drain, ForceNotifyPosition},
+    json_parser::{DriverMessages, PendingTrip, TripTerms},
 };
 
 use super::{
-    consts::ELECTION_TIMEOUT_DURATION,
-    driver_connection::DriverConnection,
-    driver_finder::{DriverACK, DriverFinder},
+    assignment_log::{Assignment, AssignmentLog, AssignmentOp, LogEntry},
+    config::{env_duration_ms, env_or, pricing_from_env},
+    connection_supervisor::{
+        ConnectionSupervisor, PeerConnected, PeerDead, PeerDiscovered, PeerLost,
+    },
+    consts::{
+        CANCELLED_TRIP_MEMORY, DEFAULT_BATCH_WINDOW, DEFAULT_DISPATCH_STRATEGY,
+        DEFAULT_FAIRNESS_FACTOR, DEFAULT_GOSSIP_FANOUT, DEFAULT_GOSSIP_INTERVAL,
+        DEFAULT_HEARTBEAT_INTERVAL, DEFAULT_LEADERLESS_TRIP_DEADLINE,
+        DEFAULT_LOG_COMPACTION_THRESHOLD, DEFAULT_MAX_POOL_DETOUR, DEFAULT_MAX_SEARCH_RADIUS,
+        DEFAULT_MAX_SURGE, DEFAULT_MEMBER_DEAD_TIMEOUT, DEFAULT_SCHEDULE_LEAD_TIME,
+        DEFAULT_SURGE_GRID_COLS, DEFAULT_SURGE_GRID_ROWS, DEFAULT_SURGE_SENSITIVITY,
+        DEFAULT_ZONE_COLS, DEFAULT_ZONE_ROWS, MAX_DISTANCE, SCHEDULE_CHECK_INTERVAL,
+        SURGE_UPDATE_INTERVAL,
+    },
+    dispatch::{strategy_from_name, DispatchStrategy, DriverHistory},
+    driver_connection::{CloseConnection, DriverConnection},
+    driver_finder::{DriverACK, DriverFinder, StopSearch, UpdateDriverPosition},
+    fairness::FairnessTracker,
     handle_trip::TripHandler,
+    matching::min_cost_assignment,
+    membership::{MemberInfo, MemberState, Membership},
     passenger_connection::PassengerConnection,
     payment_connection::PaymentConnection,
+    pool::PoolRoute,
+    surge::SurgeGrid,
+    zones::ZoneGrid,
+};
+
+mod booking;
+mod election;
+mod replication;
+
+pub use booking::{CancelBooking, ListBookings, ScheduleTrip};
+pub use election::{Coordinator, MergeDispatchState, RequestVote, StartElection, TimeoutNow, Vote};
+pub use replication::{
+    AppendEntries, AppendEntriesACK, DispatchSnapshot, ReplicatePoolRoute, ReplicatePosition,
+    ReplicateTripSearch, ReplicateTripSearchDone, ReplicateTripTerms,
 };
 
 pub struct CentralDriver {
@@ -31,33 +70,215 @@ pub struct CentralDriver {
     driver_finders: HashMap<u32, Addr<DriverFinder>>,
     /// Direcciones de los drivers segun su id
     connection_with_drivers: HashMap<u32, Addr<DriverConnection>>, // 0...N
+    /// Id del driver que inicio cada conexion, segun el id del otro driver
+    link_initiators: HashMap<u32, u32>,
+    /// Direccion del actor ConnectionSupervisor
+    connection_supervisor: Addr<ConnectionSupervisor>,
     /// Posiciones de los demas drivers segun su id,
-    /// cobra sentido si este driver es lider
+    /// el lider las mantiene y las replica en los seguidores
     driver_positions: HashMap<u32, Position>,
+    /// Busquedas de conductor abiertas segun la id del pasajero, tanto del lider como de los coordinadores
+    /// de zona. El lider las mantiene y las replica en los seguidores
+    pending_trips: HashMap<u32, PendingTrip>,
+    /// Coordinador de zona que lleva cada busqueda abierta fuera del lider, segun la id del pasajero.
+    /// Solo lo mantiene el lider, para retomar las busquedas de un coordinador que se cae
+    zone_searches: HashMap<u32, u32>,
     /// Id del driver lider
     leader_id: Option<u32>,
     /// Id del driver
     id: u32,
     /// Timeout de la eleccion
     election_timeout: Option<SpawnHandle>,
+    /// Termino actual, aumenta con cada eleccion
+    current_term: u64,
+    /// Driver al que se le dio el voto en el termino actual
+    voted_for: Option<u32>,
+    /// Drivers que votaron a este driver en el termino actual
+    votes_received: HashSet<u32>,
+    /// Drivers conectados que se sospechan caidos por no enviar heartbeats
+    suspected_drivers: HashSet<u32>,
+    /// Pedidos de viaje recibidos sin conocer al lider, segun la id del pasajero,
+    /// junto con el timeout tras el cual se le avisa al pasajero que no pudo ser atendido
+    buffered_trips: HashMap<u32, (PendingTrip, SpawnHandle)>,
+    /// Pedidos de viaje que esperan a que se libere un driver, en orden de llegada (solo en el lider)
+    waiting_trips: VecDeque<PendingTrip>,
+    /// Pasajeros que cancelaron su viaje hace poco (solo en el lider), para descartar las asignaciones
+    /// que se propongan despues de la cancelacion
+    cancelled_trips: HashSet<u32>,
+    /// Drivers que soltaron el viaje de cada pasajero antes de buscarlo, segun el id del pasajero
+    /// (solo en el lider), a los que no se les vuelve a ofrecer ese viaje
+    excluded_drivers: HashMap<u32, HashSet<u32>>,
+    /// Ultima posicion conocida de cada pasajero que va en un auto, segun su id (solo en el lider),
+    /// desde la que se le busca otro driver si se pierde la conexion con el que lo lleva
+    passenger_positions: HashMap<u32, Position>,
+    /// Ventana durante la que se juntan los pedidos nuevos para asignarlos en conjunto (cero si no se usa)
+    batch_window: Duration,
+    /// Pedidos nuevos juntados durante la ventana actual
+    batched_trips: Vec<PendingTrip>,
+    /// Timeout que cierra la ventana actual y asigna los pedidos juntados
+    batch_timeout: Option<SpawnHandle>,
+    /// Estrategia con la que se ordenan los drivers a los que se les ofrece un viaje
+    dispatch_strategy: Arc<dyn DispatchStrategy>,
+    /// Trabajo de cada driver en la sesion, segun las asignaciones confirmadas
+    fairness: FairnessTracker,
+    /// Tiempo maximo que se guarda un pedido de viaje esperando a un lider
+    leaderless_trip_deadline: Duration,
+    /// Si el driver se esta apagando, en cuyo caso no se postula como lider
+    draining: bool,
+    /// Vista de la membresia del cluster, propagada por gossip
+    membership: Membership,
+    /// Cantidad fija de drivers del cluster, con la que se calcula la mayoria
+    cluster_size: usize,
+    /// Cada cuanto se propaga la vista de membresia
+    gossip_interval: Duration,
+    /// Cada cuanto el lider le reenvia el log de asignaciones a los seguidores, junto con los heartbeats
+    heartbeat_interval: Duration,
+    /// A cuantos drivers se propaga la vista de membresia en cada ronda
+    gossip_fanout: usize,
+    /// Tiempo que un driver puede estar sospechado antes de confirmarlo como muerto
+    member_dead_timeout: Duration,
+    /// Grilla de zonas en la que se divide el mapa
+    zones: ZoneGrid,
+    /// Id del driver que coordina cada zona, segun el numero de zona.
+    /// Lo reparte el lider; mientras este vacio, el lider coordina todas las zonas
+    zone_coordinators: Vec<u32>,
+    /// Zona a la que este driver notifico su posicion por ultima vez
+    position_zone: Option<u32>,
+    /// Radio de busqueda maximo, para elegir las zonas vecinas a las que pasarles una busqueda
+    max_search_radius: u32,
+    /// Log de asignaciones de viajes, el lider lo replica en los seguidores
+    assignment_log: AssignmentLog,
+    /// Cantidad de entradas confirmadas a partir de la cual se compacta el log de asignaciones
+    log_compaction_threshold: usize,
+    /// Tarifas con las que se presupuestan los viajes
+    pricing: Pricing,
+    /// Grilla en la que se calcula el multiplicador por demanda
+    surge_grid: SurgeGrid,
+    /// Multiplicador por demanda de cada celda de la grilla, segun su numero. Lo calcula el lider;
+    /// las celdas que no estan no tienen multiplicador
+    surge_multipliers: HashMap<u32, f64>,
+    /// Condiciones que acepto cada pasajero con un viaje en curso, segun su id. Las replica el lider,
+    /// para que el driver que lleve al pasajero pase por sus paradas y le cobre el precio presupuestado
+    trip_terms: HashMap<u32, TripTerms>,
+    /// Ruta de los drivers que llevan pasajeros y tienen asientos libres, segun su id.
+    /// El lider las mantiene y las replica en los seguidores
+    pool_routes: HashMap<u32, PoolRoute>,
+    /// Cuadras que puede alargarse la ruta de un driver para sumar a un pasajero
+    max_pool_detour: u32,
+    /// Respuestas pendientes a los pasajeros que reservaron o cancelaron un viaje en este driver, segun el
+    /// id de la reserva. Se responden cuando la operacion se confirma en el log de asignaciones
+    booking_replies: HashMap<u64, oneshot::Sender<bool>>,
+    /// Anticipacion con la que se empieza a buscar un driver para un viaje reservado
+    schedule_lead_time: Duration,
 }
 
 impl Actor for CentralDriver {
     type Context = Context<Self>;
+
+    /// Al iniciar el actor, comienza a propagar periodicamente la vista de membresia por gossip, a
+    /// reenviar el log de asignaciones, a actualizar los multiplicadores por demanda y a lanzar las
+    /// busquedas de los viajes reservados (estas tres ultimas solo en el lider).
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.gossip_interval, |this, _ctx| this.gossip());
+        ctx.run_interval(self.heartbeat_interval, |this, _ctx| this.replicate_log());
+        ctx.run_interval(SURGE_UPDATE_INTERVAL, |this, _ctx| this.update_surge());
+        ctx.run_interval(SCHEDULE_CHECK_INTERVAL, |this, ctx| {
+            this.start_due_bookings(ctx)
+        });
+    }
 }
 
 impl CentralDriver {
-    /// Crea un nuevo actor `CentralDriver` con un id dado.
-    pub fn create_new(id: u32) -> Addr<Self> {
+    /// Crea un nuevo actor `CentralDriver` con un id dado, la direccion en la que escucha y la cantidad fija
+    /// de drivers del cluster, sobre la que se calcula la mayoria.
+    ///
+    /// El gossip de membresia se configura con las variables de entorno GOSSIP_INTERVAL_MS,
+    /// GOSSIP_FANOUT y MEMBER_DEAD_TIMEOUT_MS, cada cuanto se reenvia el log de asignaciones con
+    /// HEARTBEAT_INTERVAL_MS, la grilla de zonas con ZONE_ROWS y ZONE_COLS, y el radio
+    /// de busqueda maximo con MAX_SEARCH_RADIUS. La asignacion en conjunto de los pedidos se activa con
+    /// BATCH_WINDOW_MS, y la estrategia de despacho con DISPATCH_STRATEGY (y FAIRNESS_FACTOR para fair).
+    /// La grilla del multiplicador por demanda se configura con SURGE_GRID_ROWS, SURGE_GRID_COLS,
+    /// SURGE_SENSITIVITY y MAX_SURGE, y el desvio maximo para sumar pasajeros a un auto con MAX_POOL_DETOUR.
+    /// La anticipacion con la que se buscan los viajes reservados se configura con SCHEDULE_LEAD_TIME_MS
+    /// y cada cuantas entradas confirmadas se compacta el log de asignaciones con LOG_COMPACTION_THRESHOLD.
+    pub fn create_new(id: u32, self_addr: String, cluster_size: usize) -> Addr<Self> {
         CentralDriver::create(|ctx| Self {
             id,
+            zones: ZoneGrid::new(
+                env_or("ZONE_ROWS", DEFAULT_ZONE_ROWS),
+                env_or("ZONE_COLS", DEFAULT_ZONE_COLS),
+            ),
+            zone_coordinators: Vec::new(),
+            position_zone: None,
+            max_search_radius: env_or("MAX_SEARCH_RADIUS", DEFAULT_MAX_SEARCH_RADIUS)
+                .max(MAX_DISTANCE),
+            assignment_log: AssignmentLog::new(),
+            log_compaction_threshold: env_or(
+                "LOG_COMPACTION_THRESHOLD",
+                DEFAULT_LOG_COMPACTION_THRESHOLD,
+            )
+            .max(1),
+            membership: Membership::new(id, self_addr.clone()),
+            cluster_size,
+            gossip_interval: env_duration_ms("GOSSIP_INTERVAL_MS", DEFAULT_GOSSIP_INTERVAL),
+            heartbeat_interval: env_duration_ms(
+                "HEARTBEAT_INTERVAL_MS",
+                DEFAULT_HEARTBEAT_INTERVAL,
+            ),
+            gossip_fanout: env_or("GOSSIP_FANOUT", DEFAULT_GOSSIP_FANOUT),
+            member_dead_timeout: env_duration_ms(
+                "MEMBER_DEAD_TIMEOUT_MS",
+                DEFAULT_MEMBER_DEAD_TIMEOUT,
+            ),
             leader_id: None,
             driver_positions: HashMap::new(),
+            pending_trips: HashMap::new(),
+            zone_searches: HashMap::new(),
             connection_with_drivers: HashMap::new(),
+            link_initiators: HashMap::new(),
+            connection_supervisor: ConnectionSupervisor::new(ctx.address(), id, self_addr).start(),
             trip_handler: TripHandler::new(ctx.address(), id).start(),
             passengers: HashMap::new(),
             election_timeout: None,
             driver_finders: HashMap::new(),
+            current_term: 0,
+            voted_for: None,
+            votes_received: HashSet::new(),
+            suspected_drivers: HashSet::new(),
+            buffered_trips: HashMap::new(),
+            waiting_trips: VecDeque::new(),
+            cancelled_trips: HashSet::new(),
+            excluded_drivers: HashMap::new(),
+            passenger_positions: HashMap::new(),
+            batch_window: env_duration_ms("BATCH_WINDOW_MS", DEFAULT_BATCH_WINDOW),
+            batched_trips: Vec::new(),
+            batch_timeout: None,
+            dispatch_strategy: strategy_from_name(
+                &env_or("DISPATCH_STRATEGY", DEFAULT_DISPATCH_STRATEGY.to_string()),
+                env_or("FAIRNESS_FACTOR", DEFAULT_FAIRNESS_FACTOR),
+            ),
+            fairness: FairnessTracker::new(Instant::now()),
+            leaderless_trip_deadline: env_duration_ms(
+                "LEADERLESS_TRIP_DEADLINE_MS",
+                DEFAULT_LEADERLESS_TRIP_DEADLINE,
+            ),
+            draining: false,
+            pricing: pricing_from_env(),
+            surge_grid: SurgeGrid::new(
+                env_or("SURGE_GRID_ROWS", DEFAULT_SURGE_GRID_ROWS),
+                env_or("SURGE_GRID_COLS", DEFAULT_SURGE_GRID_COLS),
+                env_or("SURGE_SENSITIVITY", DEFAULT_SURGE_SENSITIVITY),
+                env_or("MAX_SURGE", DEFAULT_MAX_SURGE),
+            ),
+            surge_multipliers: HashMap::new(),
+            trip_terms: HashMap::new(),
+            pool_routes: HashMap::new(),
+            max_pool_detour: env_or("MAX_POOL_DETOUR", DEFAULT_MAX_POOL_DETOUR),
+            booking_replies: HashMap::new(),
+            schedule_lead_time: env_duration_ms(
+                "SCHEDULE_LEAD_TIME_MS",
+                DEFAULT_SCHEDULE_LEAD_TIME,
+            ),
         })
     }
 
@@ -69,6 +290,650 @@ impl CentralDriver {
 
         false
     }
+
+    /// Cantidad de votos (o de copias de una entrada del log) que forman una mayoria del cluster.
+    /// El tamaño del cluster es fijo y se conoce al arrancar (ver `cluster_size_from_env`), asi la mayoria
+    /// no depende de la vista parcial de la membresia ni se achica cuando se pierde la conexion con otros
+    /// drivers.
+    fn quorum(&self) -> usize {
+        self.cluster_size / 2 + 1
+    }
+
+    /// Id del driver que coordina una zona. Mientras el lider no reparta las zonas, las coordina todas.
+    fn coordinator_of(&self, zone: u32) -> Option<u32> {
+        self.zone_coordinators
+            .get(zone as usize)
+            .copied()
+            .or(self.leader_id)
+    }
+
+    /// Verifica si este driver coordina una zona.
+    fn coordinates(&self, zone: u32) -> bool {
+        self.coordinator_of(zone) == Some(self.id)
+    }
+
+    /// Verifica si se le pueden enviar mensajes a un driver: es este o hay conexion con el.
+    fn is_reachable(&self, driver_id: u32) -> bool {
+        driver_id == self.id || self.connection_with_drivers.contains_key(&driver_id)
+    }
+
+    /// Reparte las zonas entre los drivers conectados y no sospechados, si este driver es el lider.
+    /// La zona 0 queda para el lider y las demas se asignan en orden a los drivers que le siguen por id.
+    /// Si el reparto cambio, se lo envia a todos los drivers.
+    /// Devuelve si el reparto cambio.
+    fn assign_zones(&mut self) -> bool {
+        if !self.im_leader() {
+            return false;
+        }
+
+        let mut alive_drivers = self.alive_drivers();
+
+        alive_drivers.sort();
+
+        let leader_idx = alive_drivers
+            .iter()
+            .position(|id| *id == self.id)
+            .unwrap_or_default();
+
+        let coordinators = (0..self.zones.zone_count() as usize)
+            .map(|zone| alive_drivers[(leader_idx + zone) % alive_drivers.len()])
+            .collect::<Vec<u32>>();
+
+        if coordinators == self.zone_coordinators {
+            return false;
+        }
+
+        log::info!(
+            "[ZONES] Zone coordinators for term {}: {:?}",
+            self.current_term,
+            coordinators
+        );
+
+        self.zone_coordinators = coordinators;
+        self.broadcast(&DriverMessages::ZoneAssignment {
+            term: self.current_term,
+            coordinators: self.zone_coordinators.clone(),
+        });
+
+        let _ = self
+            .trip_handler
+            .try_send(ForceNotifyPosition {})
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        true
+    }
+
+    /// Envia la posicion de este driver al coordinador de una zona.
+    fn send_position_to_coordinator(&self, zone: u32, position: Position, ctx: &mut Context<Self>) {
+        match self.coordinator_of(zone) {
+            Some(cid) if cid == self.id => ctx.notify(SetDriverPosition {
+                driver_id: self.id,
+                driver_position: position,
+            }),
+            Some(cid) => self.send_to_driver(
+                cid,
+                &DriverMessages::NotifyPosition {
+                    driver_id: self.id,
+                    driver_position: position,
+                },
+            ),
+            None => (),
+        }
+    }
+
+    /// Serializa un mensaje y lo envia al driver con el id dado, si hay conexion con el.
+    fn send_to_driver(&self, driver_id: u32, message: &DriverMessages) {
+        let parsed_data = serde_json::to_string(message)
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        if let (Ok(data), Some(driver)) =
+            (parsed_data, self.connection_with_drivers.get(&driver_id))
+        {
+            driver.do_send(SendAll { data });
+        }
+    }
+
+    /// Serializa un mensaje y lo envia a todos los drivers conectados.
+    fn broadcast(&self, message: &DriverMessages) {
+        let parsed_data = serde_json::to_string(message)
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        if let Ok(data) = parsed_data {
+            for driver in self.connection_with_drivers.values() {
+                driver.do_send(SendAll { data: data.clone() });
+            }
+        }
+    }
+
+    /// Envia la vista de membresia a GOSSIP_FANOUT drivers conectados elegidos al azar.
+    fn gossip(&self) {
+        let message = DriverMessages::Gossip {
+            members: self.membership.digest(),
+        };
+
+        let peers = self
+            .connection_with_drivers
+            .keys()
+            .copied()
+            .choose_multiple(&mut rand::thread_rng(), self.gossip_fanout);
+
+        for driver_id in peers {
+            self.send_to_driver(driver_id, &message);
+        }
+    }
+
+    /// Reacciona a un cambio en la vista de membresia:
+    /// - Si se descubrio un driver vivo sin conexion, le pide al `ConnectionSupervisor` que se conecte.
+    /// - Si un driver quedo sospechado, programa su confirmacion como muerto.
+    /// - Si un driver murio, le pide al `ConnectionSupervisor` que deje de reconectarse.
+    fn on_member_update(&mut self, member: MemberInfo, ctx: &mut Context<Self>) {
+        if member.id == self.id {
+            log::info!(
+                "[MEMBERSHIP] Refuting that i am down, incarnation {}",
+                member.incarnation
+            );
+            return;
+        }
+
+        log::debug!(
+            "[MEMBERSHIP] Driver {} at {} is {:?} (incarnation {})",
+            member.id,
+            member.addr,
+            member.state,
+            member.incarnation
+        );
+
+        match member.state {
+            MemberState::Alive => {
+                if !self.connection_with_drivers.contains_key(&member.id) {
+                    self.connection_supervisor.do_send(PeerDiscovered {
+                        driver_id: member.id,
+                        addr: member.addr,
+                    });
+                }
+            }
+            MemberState::Suspect => {
+                ctx.notify_later(
+                    ConfirmMemberDead {
+                        id: member.id,
+                        incarnation: member.incarnation,
+                    },
+                    self.member_dead_timeout,
+                );
+            }
+            MemberState::Dead => {
+                log::info!("[MEMBERSHIP] Driver {} is dead", member.id);
+                self.connection_supervisor.do_send(PeerDead {
+                    driver_id: member.id,
+                });
+                self.resume_zone_searches(member.id, ctx);
+                self.recover_trips_of(member.id, ctx);
+            }
+        }
+    }
+
+    /// Si este driver es el lider, recalcula el multiplicador por demanda de cada celda a partir de los
+    /// drivers libres y los pedidos abiertos. Si cambio, lo loggea y se lo envia a los demas drivers.
+    fn update_surge(&mut self) {
+        if !self.im_leader() {
+            return;
+        }
+
+        let free_drivers = self
+            .driver_positions
+            .values()
+            .filter(|position| **position != Position::infinity())
+            .copied()
+            .collect::<Vec<Position>>();
+
+        let open_requests = self
+            .pending_trips
+            .values()
+            .chain(self.waiting_trips.iter())
+            .chain(self.batched_trips.iter())
+            .map(|trip| (trip.passenger_id, trip.source))
+            .collect::<HashMap<u32, Position>>()
+            .into_values()
+            .collect::<Vec<Position>>();
+
+        let multipliers = self.surge_grid.multipliers(&free_drivers, &open_requests);
+
+        if multipliers == self.surge_multipliers {
+            return;
+        }
+
+        let mut cells = multipliers.iter().collect::<Vec<(&u32, &f64)>>();
+        cells.sort_by_key(|(cell, _)| **cell);
+        log::info!("[SURGE] Multipliers by cell: {:?}", cells);
+
+        self.surge_multipliers = multipliers;
+        self.broadcast(&DriverMessages::SurgeUpdate {
+            term: self.current_term,
+            multipliers: self.surge_multipliers.clone(),
+        });
+    }
+
+    /// Retoma las busquedas abiertas que fueron replicadas por el lider anterior y que
+    /// todavia no tienen un `DriverFinder` en este driver.
+    fn resume_pending_trips(&self, ctx: &mut Context<Self>) {
+        for trip in self.pending_trips.values() {
+            if self.driver_finders.contains_key(&trip.passenger_id)
+                || self.assignment_log.passenger_assigned(trip.passenger_id)
+            {
+                continue;
+            }
+
+            log::info!(
+                "[REPLICATION] Resuming the search for passenger {}",
+                trip.passenger_id
+            );
+
+            ctx.notify(FindDriver {
+                passenger_id: trip.passenger_id,
+                source: trip.source,
+                destination: trip.destination,
+                handoff_zones: None,
+            });
+        }
+    }
+
+    /// Registra una busqueda abierta por este driver. El lider la replica en los seguidores; un
+    /// coordinador de zona se la informa al lider, que la replica como las suyas.
+    fn open_search(&mut self, trip: PendingTrip) {
+        if self.im_leader() {
+            self.zone_searches.remove(&trip.passenger_id);
+            self.pending_trips.insert(trip.passenger_id, trip);
+            self.broadcast(&DriverMessages::ReplicateTripSearch {
+                term: self.current_term,
+                trip,
+            });
+        } else if let Some(lid) = self.leader_id.filter(|lid| self.is_reachable(*lid)) {
+            self.send_to_driver(lid, &DriverMessages::ZoneTripSearch { trip });
+        }
+    }
+
+    /// Da por terminada una busqueda abierta. Si este driver es el lider, se lo notifica a los seguidores.
+    fn close_search(&mut self, passenger_id: u32) {
+        self.zone_searches.remove(&passenger_id);
+
+        if self.pending_trips.remove(&passenger_id).is_some() && self.im_leader() {
+            self.broadcast(&DriverMessages::ReplicateTripSearchDone {
+                term: self.current_term,
+                passenger_id,
+            });
+        }
+    }
+
+    /// Si este driver es el lider, retoma las busquedas abiertas que llevaba un coordinador de zona
+    /// que se cayo.
+    fn resume_zone_searches(&mut self, coordinator_id: u32, ctx: &mut Context<Self>) {
+        if !self.im_leader() {
+            return;
+        }
+
+        let orphaned = self
+            .zone_searches
+            .iter()
+            .filter(|(_, cid)| **cid == coordinator_id)
+            .map(|(passenger_id, _)| *passenger_id)
+            .collect::<Vec<u32>>();
+
+        for passenger_id in orphaned {
+            self.zone_searches.remove(&passenger_id);
+
+            let trip = match self.pending_trips.get(&passenger_id) {
+                Some(trip) if !self.assignment_log.passenger_assigned(passenger_id) => *trip,
+                _ => continue,
+            };
+
+            log::info!(
+                "[ZONES] Coordinator {} is down, resuming the search for passenger {}",
+                coordinator_id,
+                passenger_id
+            );
+
+            ctx.notify(FindDriver {
+                passenger_id,
+                source: trip.source,
+                destination: trip.destination,
+                handoff_zones: None,
+            });
+        }
+    }
+
+    /// Registra en el log que el driver de una asignacion solto el viaje y vuelve a buscarle un driver
+    /// al pasajero desde la posicion dada, sin ofrecerselo a los drivers que ya lo soltaron.
+    fn reassign_trip(&mut self, assignment: Assignment, source: Position, ctx: &mut Context<Self>) {
+        self.excluded_drivers
+            .entry(assignment.passenger_id)
+            .or_default()
+            .insert(assignment.driver_id);
+
+        self.append_assignment_op(
+            AssignmentOp::Release {
+                passenger_id: assignment.passenger_id,
+                offer_id: assignment.offer_id,
+            },
+            ctx,
+        );
+
+        ctx.notify(FindDriver {
+            passenger_id: assignment.passenger_id,
+            source,
+            destination: assignment.destination,
+            handoff_zones: None,
+        });
+    }
+
+    /// Si este driver es el lider, les vuelve a buscar un driver a los pasajeros que llevaba un driver
+    /// que la membresia confirmo como muerto. La busqueda empieza desde la ultima posicion conocida del
+    /// pasajero, o desde donde pidio el viaje si todavia no lo habian buscado, y se le avisa al pasajero.
+    /// La entrada que libera cada viaje lleva la oferta del driver muerto, asi si en realidad seguia vivo
+    /// abandona el viaje al recibirla, y sus avisos de fin de viaje se descartan.
+    fn recover_trips_of(&mut self, driver_id: u32, ctx: &mut Context<Self>) {
+        if !self.im_leader() {
+            return;
+        }
+
+        let trips = self
+            .assignment_log
+            .active_assignments()
+            .into_values()
+            .filter(|assignment| assignment.driver_id == driver_id)
+            .collect::<Vec<Assignment>>();
+
+        for assignment in trips {
+            let source = self
+                .passenger_positions
+                .remove(&assignment.passenger_id)
+                .unwrap_or(assignment.source);
+
+            log::warn!(
+                "[ASSIGNMENT] Driver {} is dead with the trip of passenger {}, looking for another driver from {:?}",
+                driver_id,
+                assignment.passenger_id,
+                source
+            );
+
+            ctx.notify(InformPassenger {
+                passenger_id: assignment.passenger_id,
+                detail: "Your driver is not responding, looking for another driver".to_string(),
+            });
+
+            self.reassign_trip(assignment, source, ctx);
+        }
+    }
+
+    /// Genera un actor DriverFinder y lo inicia para buscar un driver a un pasajero.
+    /// Si no se indican las zonas a las que pasarle la busqueda, se usan las zonas cercanas al pasajero.
+    /// Si el pedido se asigno en conjunto con otros, la primera oferta es solo para el driver asignado.
+    /// No se le ofrece el viaje a los drivers que ya lo soltaron, y si se le ofrece a los que llevan
+    /// pasajeros y les queda de camino (salvo que el viaje tenga paradas intermedias).
+    fn start_driver_finder(
+        &mut self,
+        trip: PendingTrip,
+        handoff_zones: Option<Vec<u32>>,
+        matched_driver: Option<u32>,
+        ctx: &mut Context<Self>,
+    ) {
+        let handoff_zones = handoff_zones
+            .unwrap_or_else(|| self.zones.zones_near(&trip.source, self.max_search_radius));
+
+        let mut finder = DriverFinder::new(
+            ctx.address().clone(),
+            self.id,
+            trip,
+            self.driver_positions.clone(),
+            self.driver_histories(),
+            handoff_zones,
+            self.dispatch_strategy.clone(),
+        );
+
+        if let Some(did) = matched_driver {
+            finder = finder.with_matched_driver(did);
+        }
+
+        if let Some(excluded) = self.excluded_drivers.get(&trip.passenger_id) {
+            finder = finder.with_excluded_drivers(excluded.clone());
+        }
+
+        let has_stops = self
+            .trip_terms
+            .get(&trip.passenger_id)
+            .is_some_and(|terms| !terms.stops.is_empty());
+
+        if !self.pool_routes.is_empty() && !has_stops {
+            finder = finder.with_pool_routes(&self.pool_routes, self.max_pool_detour);
+        }
+
+        self.driver_finders
+            .insert(trip.passenger_id, finder.start());
+    }
+
+    /// Historial de los drivers con posicion conocida, segun su id: su ultima asignacion en el log
+    /// y su trabajo en la sesion.
+    fn driver_histories(&self) -> HashMap<u32, DriverHistory> {
+        let last_assignments = self.assignment_log.last_assignments();
+        let now = Instant::now();
+
+        self.driver_positions
+            .keys()
+            .map(|id| {
+                let stats = self.fairness.stats(*id, now);
+
+                let history = DriverHistory {
+                    last_served: last_assignments.get(id).copied().unwrap_or_default(),
+                    completed_trips: stats.completed_trips,
+                    idle_time: stats.idle_time,
+                };
+
+                (*id, history)
+            })
+            .collect()
+    }
+
+    /// Registra en el trabajo de cada driver las entradas recien confirmadas del log de asignaciones.
+    /// Si este driver es el lider, al terminar un viaje loggea el trabajo de los drivers vivos.
+    fn track_fairness(&mut self, entries: &[LogEntry]) {
+        let now = Instant::now();
+
+        for entry in entries {
+            match entry.op {
+                AssignmentOp::Assign(assignment) => self
+                    .fairness
+                    .record_assignment(assignment.passenger_id, assignment.driver_id),
+                AssignmentOp::Complete { passenger_id } => {
+                    if self.fairness.record_completion(passenger_id, now).is_none()
+                        || !self.im_leader()
+                    {
+                        continue;
+                    }
+
+                    let mut alive_drivers = self.alive_drivers();
+                    alive_drivers.sort();
+
+                    for id in alive_drivers {
+                        let stats = self.fairness.stats(id, now);
+
+                        log::info!(
+                            "[FAIRNESS] Driver {}: {} trips completed, idle for {}s",
+                            id,
+                            stats.completed_trips,
+                            stats.idle_time.as_secs()
+                        );
+                    }
+                }
+                AssignmentOp::Release { passenger_id, .. } => {
+                    self.fairness.record_release(passenger_id)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Ids de los drivers vivos, incluyendo a este.
+    fn alive_drivers(&self) -> Vec<u32> {
+        self.connection_with_drivers
+            .keys()
+            .filter(|id| !self.suspected_drivers.contains(id))
+            .copied()
+            .chain(std::iter::once(self.id))
+            .collect()
+    }
+
+    /// Verifica si todos los drivers vivos tienen un viaje asignado y ningun asiento libre.
+    fn all_drivers_busy(&self) -> bool {
+        self.alive_drivers()
+            .into_iter()
+            .all(|id| self.driver_full(id))
+    }
+
+    /// Verifica si un driver tiene un viaje asignado y no puede sumar a otro pasajero.
+    fn driver_full(&self, driver_id: u32) -> bool {
+        self.assignment_log.driver_assigned(driver_id)
+            && !self
+                .pool_routes
+                .get(&driver_id)
+                .is_some_and(|route| route.has_free_seat())
+    }
+
+    /// Guarda la ruta de un driver que puede sumar pasajeros, o la borra si es None.
+    fn set_pool_route(&mut self, driver_id: u32, route: Option<PoolRoute>) {
+        match route {
+            Some(route) => {
+                self.pool_routes.insert(driver_id, route);
+            }
+            None => {
+                self.pool_routes.remove(&driver_id);
+            }
+        }
+    }
+
+    /// Encola un pedido de viaje hasta que se libere un driver (solo en el lider) y le avisa al pasajero
+    /// su lugar en la cola. La busqueda queda abierta, para que un nuevo lider la retome si este se cae.
+    fn enqueue_trip(&mut self, trip: PendingTrip, ctx: &mut Context<Self>) {
+        if !self
+            .waiting_trips
+            .iter()
+            .any(|waiting| waiting.passenger_id == trip.passenger_id)
+        {
+            log::info!(
+                "[QUEUE] All drivers are busy, queueing the trip for passenger {}",
+                trip.passenger_id
+            );
+
+            self.waiting_trips.push_back(trip);
+        }
+
+        self.zone_searches.remove(&trip.passenger_id);
+
+        if self.pending_trips.insert(trip.passenger_id, trip).is_none() {
+            self.broadcast(&DriverMessages::ReplicateTripSearch {
+                term: self.current_term,
+                trip,
+            });
+        }
+
+        self.inform_queue_positions(ctx);
+    }
+
+    /// Le avisa a cada pasajero en espera su lugar en la cola.
+    fn inform_queue_positions(&self, ctx: &mut Context<Self>) {
+        for (idx, trip) in self.waiting_trips.iter().enumerate() {
+            ctx.notify(InformPassenger {
+                passenger_id: trip.passenger_id,
+                detail: format!(
+                    "All drivers are busy, you are number {} in the queue",
+                    idx + 1
+                ),
+            });
+        }
+    }
+
+    /// Despacha los pedidos en espera, en orden de llegada, mientras haya drivers libres (solo en el lider).
+    fn dispatch_waiting_trips(&mut self, ctx: &mut Context<Self>) {
+        if !self.im_leader() || self.waiting_trips.is_empty() {
+            return;
+        }
+
+        let free_drivers = self
+            .alive_drivers()
+            .into_iter()
+            .filter(|id| !self.driver_full(*id))
+            .count();
+
+        let count = free_drivers.min(self.waiting_trips.len());
+
+        if count == 0 {
+            return;
+        }
+
+        for trip in self.waiting_trips.drain(..count) {
+            log::info!(
+                "[QUEUE] Dispatching the queued trip for passenger {}",
+                trip.passenger_id
+            );
+
+            ctx.notify(RedirectNewTrip {
+                passenger_id: trip.passenger_id,
+                source: trip.source,
+                destination: trip.destination,
+            });
+        }
+
+        self.inform_queue_positions(ctx);
+    }
+
+    /// Guarda un pedido de viaje hasta que se conozca al lider. Si no aparece un lider antes de
+    /// LEADERLESS_TRIP_DEADLINE_MS, se le avisa al pasajero que su pedido no pudo ser atendido.
+    fn buffer_trip(&mut self, trip: PendingTrip, ctx: &mut Context<Self>) {
+        log::warn!(
+            "[TRIP] There is no leader, holding the trip for passenger {}",
+            trip.passenger_id
+        );
+
+        if let Some((buffered, _)) = self.buffered_trips.get_mut(&trip.passenger_id) {
+            *buffered = trip;
+            return;
+        }
+
+        let deadline = ctx.notify_later(
+            ExpireBufferedTrip {
+                passenger_id: trip.passenger_id,
+            },
+            self.leaderless_trip_deadline,
+        );
+
+        self.buffered_trips
+            .insert(trip.passenger_id, (trip, deadline));
+    }
+
+    /// Si este driver es el lider o tiene conexion con el, le reenvia los pedidos de viaje guardados.
+    fn replay_buffered_trips(&mut self, ctx: &mut Context<Self>) {
+        let leader_reachable = match self.leader_id {
+            Some(lid) => self.im_leader() || self.connection_with_drivers.contains_key(&lid),
+            None => false,
+        };
+
+        if !leader_reachable {
+            return;
+        }
+
+        for (_, (trip, deadline)) in self.buffered_trips.drain() {
+            ctx.cancel_future(deadline);
+
+            log::info!(
+                "[TRIP] Replaying the trip for passenger {} to the new leader",
+                trip.passenger_id
+            );
+
+            ctx.notify(RedirectNewTrip {
+                passenger_id: trip.passenger_id,
+                source: trip.source,
+                destination: trip.destination,
+            });
+        }
+    }
 }
 
 #[derive(Message)]
@@ -81,37 +946,31 @@ impl Handler<NotifyPositionToLeader> for CentralDriver {
     type Result = ();
 
     /// Maneja los mensajes de notificacion de posicion de un driver.
-    /// - Si no tengo el lider, no hago nada.
-    /// - Si soy el lider, envio un mensaje al actor `CentralDriver` para que actualice la posicion del driver.
-    /// - Si no soy el lider, envio un mensaje al lider con la posicion del driver.
+    /// La posicion se le envia al coordinador de la zona en la que esta el driver (que puede ser este driver).
+    /// - Si no tengo el lider ni el reparto de zonas, no hago nada.
+    /// - Si el driver esta en el 'infinito', se le avisa al coordinador de la ultima zona notificada.
+    /// - Si el driver cambio de zona, se le avisa al coordinador de la zona anterior que ya no esta
+    ///   disponible en ella.
     fn handle(&mut self, msg: NotifyPositionToLeader, ctx: &mut Context<Self>) -> Self::Result {
         if self.leader_id.is_none() {
             return;
         }
 
-        if let Some(lid) = self.leader_id {
-            if self.im_leader() {
-                ctx.address().do_send(SetDriverPosition {
-                    driver_id: self.id,
-                    driver_position: msg.driver_location,
-                });
-
-                return;
+        let zone = if msg.driver_location == Position::infinity() {
+            match self.position_zone {
+                Some(zone) => zone,
+                None => return,
             }
+        } else {
+            self.zones.zone_of(&msg.driver_location)
+        };
 
-            let parsed_data = serde_json::to_string(&DriverMessages::NotifyPosition {
-                driver_id: self.id,
-                driver_position: msg.driver_location,
-            })
-            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
-
-            if let Ok(data) = parsed_data {
-                match self.connection_with_drivers.get(&lid) {
-                    Some(leader) => leader.do_send(SendAll { data }),
-                    None => (),
-                };
-            }
+        if let Some(old_zone) = self.position_zone.filter(|old_zone| *old_zone != zone) {
+            self.send_position_to_coordinator(old_zone, Position::infinity(), ctx);
         }
+
+        self.position_zone = Some(zone);
+        self.send_position_to_coordinator(zone, msg.driver_location, ctx);
     }
 }
 
@@ -130,10 +989,28 @@ impl Handler<SetDriverPosition> for CentralDriver {
     /// Maneja los mensajes de actualizacion de posicion de un driver.
     /// Actualiza la posicion del driver en el hashmap de posiciones de drivers.
     /// Loggea la posicion del driver.
+    /// Si este driver es el lider, replica la posicion en los seguidores.
+    /// Le avisa la nueva posicion a los DriverFinder en curso, para que reintenten la busqueda si el driver
+    /// se acerco al pasajero.
     fn handle(&mut self, msg: SetDriverPosition, _ctx: &mut Context<Self>) -> Self::Result {
         log::debug!("Driver {} in {:?}", msg.driver_id, msg.driver_position);
         self.driver_positions
             .insert(msg.driver_id, msg.driver_position);
+
+        for finder in self.driver_finders.values() {
+            finder.do_send(UpdateDriverPosition {
+                driver_id: msg.driver_id,
+                driver_position: msg.driver_position,
+            });
+        }
+
+        if self.im_leader() {
+            self.broadcast(&DriverMessages::ReplicatePosition {
+                term: self.current_term,
+                driver_id: msg.driver_id,
+                driver_position: msg.driver_position,
+            });
+        }
     }
 }
 
@@ -142,6 +1019,10 @@ impl Handler<SetDriverPosition> for CentralDriver {
 pub struct CollectMoneyPassenger {
     /// Id del pasajero
     pub passenger_id: u32,
+    /// Monto a cobrar
+    pub amount: u32,
+    /// Si se cobra la multa por cancelar el viaje en lugar del viaje
+    pub cancellation_fee: bool,
 }
 
 #[async_handler]
@@ -150,7 +1031,8 @@ impl Handler<CollectMoneyPassenger> for CentralDriver {
 
     /// Maneja los mensajes de cobro de un pasajero.
     /// - Se conecta con el servicio de pagos.
-    /// - Si se conecta correctamente, envia un mensaje al servicio de pagos con el id del driver y el id del pasajero.
+    /// - Si se conecta correctamente, envia un mensaje al servicio de pagos con el id del driver, el id del pasajero
+    ///   y el monto, para cobrar el viaje o la multa por cancelarlo.
     /// - Si no se conecta correctamente, loggea un error.
     async fn handle(
         &mut self,
@@ -163,11 +1045,20 @@ impl Handler<CollectMoneyPassenger> for CentralDriver {
         let connection_with_payment = PaymentConnection::connect(self_addr).await;
 
         if let Ok(addr) = connection_with_payment {
-            let parsed_data = serde_json::to_string(&PaymentMessages::CollectPayment {
-                driver_id,
-                passenger_id: msg.passenger_id,
-            })
-            .inspect_err(|e| {
+            let message = match msg.cancellation_fee {
+                true => PaymentMessages::CollectCancellationFee {
+                    driver_id,
+                    passenger_id: msg.passenger_id,
+                    amount: msg.amount,
+                },
+                false => PaymentMessages::CollectPayment {
+                    driver_id,
+                    passenger_id: msg.passenger_id,
+                    amount: msg.amount,
+                },
+            };
+
+            let parsed_data = serde_json::to_string(&message).inspect_err(|e| {
                 log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
             });
 
@@ -189,6 +1080,8 @@ impl Handler<CollectMoneyPassenger> for CentralDriver {
 pub struct CheckPaymentResponse {
     pub passenger_id: u32,
     pub response: bool,
+    /// Si el cobro era la multa por cancelar el viaje
+    pub cancellation_fee: bool,
 }
 
 impl Handler<CheckPaymentResponse> for CentralDriver {
@@ -197,10 +1090,23 @@ impl Handler<CheckPaymentResponse> for CentralDriver {
     /// Maneja los mensajes de respuesta de cobro de un pasajero.
     /// - Si el pasajero pago, loggea un mensaje de que el pasajero pago.
     /// - Si el pasajero no pago, loggea un mensaje de que el pasajero no pago.
+    ///
+    /// Si el cobro era la multa por cancelar el viaje, lo indica en el mensaje.
     fn handle(&mut self, msg: CheckPaymentResponse, _ctx: &mut Context<Self>) -> Self::Result {
-        match msg.response {
-            true => log::info!("Passenger {} paid for the trip!", msg.passenger_id),
-            _ => log::warn!(
+        if msg.cancellation_fee {
+            match msg.response {
+                true => log::info!("Passenger {} paid the cancellation fee!", msg.passenger_id),
+                _ => log::warn!(
+                    "Passenger {} did not pay the cancellation fee!!",
+                    msg.passenger_id
+                ),
+            }
+            return;
+        }
+
+        match msg.response {
+            true => log::info!("Passenger {} paid for the trip!", msg.passenger_id),
+            _ => log::warn!(
                 "Passenger {} did not pay for the trip!!, call the police!",
                 msg.passenger_id
             ),
@@ -246,17 +1152,85 @@ pub struct InsertDriverConnection {
     pub id: u32,
     /// Direccion del actor `DriverConnection`
     pub addr: Addr<DriverConnection>,
+    /// Direccion en la que escucha el driver
+    pub driver_addr: String,
+    /// Id del driver que inicio la conexion
+    pub initiator: u32,
 }
 
 impl Handler<InsertDriverConnection> for CentralDriver {
     type Result = ();
 
     /// Maneja los mensajes de conexion con un driver.
+    /// - Si ya habia una conexion con el driver (por ejemplo, si ambos se conectaron a la vez),
+    ///   se queda con una sola: la iniciada por el driver de menor id, o la mas nueva si ambas
+    ///   fueron iniciadas por el mismo. Ambos drivers aplican la misma regla y cierran la otra.
     /// - Loggea un mensaje de conexion con el driver.
-    /// - Inserta la conexion del driver en el hashmap de conexiones con drivers.
-    fn handle(&mut self, msg: InsertDriverConnection, _ctx: &mut Context<Self>) -> Self::Result {
+    /// - Inserta la conexion del driver en el hashmap de conexiones con drivers y le avisa al
+    ///   `ConnectionSupervisor` que ya no hace falta reconectarse.
+    /// - Registra al driver en la vista de membresia y le envia la vista completa.
+    /// - Si el driver es el lider, le reenvia los pedidos de viaje guardados mientras no habia conexion con el.
+    /// - Si este driver es el lider, le notifica al nuevo driver quien es el lider y en que termino,
+    ///   asi se une sin necesidad de una nueva eleccion, y le envia una copia del estado de despacho
+    ///   y del log de asignaciones.
+    fn handle(&mut self, msg: InsertDriverConnection, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(current) = self.connection_with_drivers.get(&msg.id) {
+            let preferred_initiator = self.id.min(msg.id);
+            let current_initiator = self.link_initiators.get(&msg.id).copied();
+
+            if msg.initiator != preferred_initiator
+                && current_initiator == Some(preferred_initiator)
+            {
+                log::info!("Closing duplicated connection with driver {}", msg.id);
+                msg.addr.do_send(CloseConnection {});
+                return;
+            }
+
+            log::info!("Replacing duplicated connection with driver {}", msg.id);
+            current.do_send(CloseConnection {});
+        }
+
         log::info!("Connecting with driver {}", msg.id);
         self.connection_with_drivers.insert(msg.id, msg.addr);
+        self.link_initiators.insert(msg.id, msg.initiator);
+        self.connection_supervisor
+            .do_send(PeerConnected { driver_id: msg.id });
+
+        if let Some(member) = self.membership.join(msg.id, msg.driver_addr) {
+            self.on_member_update(member, ctx);
+        }
+
+        self.send_to_driver(
+            msg.id,
+            &DriverMessages::Gossip {
+                members: self.membership.digest(),
+            },
+        );
+
+        self.replay_buffered_trips(ctx);
+
+        if self.im_leader() {
+            self.send_to_driver(
+                msg.id,
+                &DriverMessages::Coordinator {
+                    leader_id: self.id,
+                    term: self.current_term,
+                },
+            );
+
+            self.send_dispatch_snapshot(msg.id);
+            self.send_log_entries(msg.id);
+
+            if !self.assign_zones() && !self.zone_coordinators.is_empty() {
+                self.send_to_driver(
+                    msg.id,
+                    &DriverMessages::ZoneAssignment {
+                        term: self.current_term,
+                        coordinators: self.zone_coordinators.clone(),
+                    },
+                );
+            }
+        }
     }
 }
 
@@ -265,197 +1239,127 @@ impl Handler<InsertDriverConnection> for CentralDriver {
 pub struct RemoveDriverConnection {
     /// Id del driver
     pub id: u32,
+    /// Direccion del actor `DriverConnection` de la conexion que se cerro
+    pub addr: Addr<DriverConnection>,
 }
 
 impl Handler<RemoveDriverConnection> for CentralDriver {
     type Result = ();
 
     /// Maneja los mensajes de eliminacion de conexion con un driver.
-    /// - Elimina la conexion del driver del hashmap de conexiones con drivers.
+    /// - Si la conexion cerrada no es la actual (por ejemplo, una duplicada), no hace nada.
+    /// - Elimina la conexion del driver del hashmap de conexiones con drivers y, si no se esta apagando,
+    ///   le pide al `ConnectionSupervisor` que intente reconectarse. El driver queda sospechado
+    ///   hasta que se reconecte o se lo confirme como muerto.
     /// - Loggea un mensaje de desconexion con el driver.
-    fn handle(&mut self, msg: RemoveDriverConnection, _ctx: &mut Context<Self>) -> Self::Result {
-        if let Some(_) = self.connection_with_drivers.remove(&msg.id) {
-            log::info!("Disconnecting with driver {}", msg.id);
+    /// - Olvida la posicion y la ruta del driver, para no ofrecerle viajes.
+    /// - Los viajes del driver desconectado no se reasignan todavia: puede ser una caida transitoria de
+    ///   la conexion, asi que se espera a que la membresia lo confirme como muerto.
+    /// - Si el driver desconectado era el lider, inicia una eleccion.
+    /// - Si este driver es el lider, vuelve a repartir las zonas.
+    /// - Si este driver es candidato, vuelve a contar los votos ya que la mayoria necesaria cambio.
+    fn handle(&mut self, msg: RemoveDriverConnection, ctx: &mut Context<Self>) -> Self::Result {
+        if self.connection_with_drivers.get(&msg.id) != Some(&msg.addr) {
+            return;
         }
-    }
-}
-
-#[derive(Message)]
-#[rtype(result = "()")]
-pub struct StartElection {}
-
-impl Handler<StartElection> for CentralDriver {
-    type Result = ();
-
-    /// Maneja los mensajes de inicio de eleccion.
-    /// Envia el mensaje Election a todos los drivers con id mayor al id propio.
-    /// - Si no hay drivers con mayor id que el propio se declara coordinador y le notifica a todos los conductores.
-    /// - Si hay drivers con mayor id que el propio, les envía el mensaje Election.
-    ///     - Setea un timeout para la eleccion.
-    ///     - Si no hay respuesta de los drivers con mayor id que el propio, se declara coordinador y le notifica a todos los conductores.
-    fn handle(&mut self, _msg: StartElection, ctx: &mut Context<Self>) -> Self::Result {
-        self.leader_id = None;
-        let mut higher_processes = false;
-
-        let parsed_data = serde_json::to_string(&DriverMessages::Election { sender_id: self.id })
-            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
 
-        // Send election messages to all processes with higher IDs
-        for (&id, driver) in &self.connection_with_drivers {
-            if id > self.id {
-                if let Ok(data) = &parsed_data {
-                    driver.do_send(SendAll { data: data.clone() });
-                }
+        self.connection_with_drivers.remove(&msg.id);
+        self.link_initiators.remove(&msg.id);
+        log::info!("Disconnecting with driver {}", msg.id);
 
-                higher_processes = true;
+        if !self.draining {
+            if let Some(member) = self.membership.get(msg.id) {
+                self.connection_supervisor.do_send(PeerLost {
+                    driver_id: msg.id,
+                    addr: member.addr.clone(),
+                });
             }
         }
 
-        // If no higher processes, declare Coordinator
-        if !higher_processes {
-            log::info!("[ELECTION] There is no one bigger than me!");
-            ctx.notify(Coordinator { leader_id: self.id });
-
-            for (_, driver) in &self.connection_with_drivers {
-                let parsed_data =
-                    serde_json::to_string(&DriverMessages::Coordinator { leader_id: self.id })
-                        .inspect_err(|e| {
-                            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
-                        });
-
-                if let Ok(data) = parsed_data {
-                    driver.do_send(SendAll { data });
-                }
-            }
-        } else {
-            let leader_id = self.id.clone();
-            let connection_with_drivers = self.connection_with_drivers.clone();
-
-            // Set timeout for responses
-            self.election_timeout =
-                Some(ctx.run_later(ELECTION_TIMEOUT_DURATION, move |_, ctx| {
-                    log::warn!("[ELECTION] No one answer the election");
-                    ctx.notify(Coordinator { leader_id });
-
-                    for (_, driver) in &connection_with_drivers {
-                        let parsed_data =
-                            serde_json::to_string(&DriverMessages::Coordinator { leader_id })
-                                .inspect_err(|e| {
-                                    log::error!(
-                                        "{}:{}, {}",
-                                        std::file!(),
-                                        std::line!(),
-                                        e.to_string()
-                                    )
-                                });
-
-                        if let Ok(data) = parsed_data {
-                            driver.do_send(SendAll { data });
-                        }
-                    }
-                }));
+        if let Some(member) = self.membership.suspect(msg.id) {
+            self.on_member_update(member, ctx);
         }
-    }
-}
-
-#[derive(Message)]
-#[rtype(result = "()")]
-pub struct Election {
-    pub sender_id: u32,
-}
-
-impl Handler<Election> for CentralDriver {
-    type Result = ();
-
-    /// Maneja los mensajes de eleccion.
-    /// - Si el driver recibe un mensaje de eleccion, verifica si el id del driver que envia el mensaje es menor al id propio.
-    ///
-    /// Si esto sucede envia un mensaje "Alive" al driver que envia el mensaje y envia un mensaje "StartElection" al actor `CentralDriver`
-    /// para continuar con el proceso de elección.
-
-    fn handle(&mut self, msg: Election, ctx: &mut Context<Self>) -> Self::Result {
-        log::debug!(
-            "[ELECTION] Driver {} received election message from {}",
-            self.id,
-            msg.sender_id
-        );
-
-        // If this process has higher ID, respond and start new election
-        if self.id > msg.sender_id {
-            // Send alive message to sender
-            if let Some(sender) = self.connection_with_drivers.get(&msg.sender_id) {
-                let parsed_data = serde_json::to_string(&DriverMessages::Alive {
-                    responder_id: self.id,
-                })
-                .inspect_err(|e| {
-                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
-                });
 
-                if let Ok(data) = parsed_data {
-                    sender.do_send(SendAll { data });
-                }
-            }
+        self.driver_positions.remove(&msg.id);
+        self.pool_routes.remove(&msg.id);
+        self.votes_received.remove(&msg.id);
+        self.suspected_drivers.remove(&msg.id);
+        self.assignment_log.forget_follower(msg.id);
 
-            // Start new election
+        if self.leader_id == Some(msg.id) {
+            log::warn!("[ELECTION] Lost connection with the leader {}", msg.id);
+            self.leader_id = None;
             ctx.notify(StartElection {});
+            return;
         }
+
+        self.assign_zones();
+        self.commit_assignments(ctx);
+        self.check_votes(ctx);
     }
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct Alive {
-    pub responder_id: u32,
+pub struct SuspectDriver {
+    /// Id del driver
+    pub id: u32,
 }
 
-impl Handler<Alive> for CentralDriver {
+impl Handler<SuspectDriver> for CentralDriver {
     type Result = ();
 
-    /// Maneja los mensajes de respuesta a una eleccion.
-    /// - Si el driver recibe un mensaje de respuesta, cancela el timeout de la eleccion.
-    /// - Loggea un mensaje de que el driver recibio un mensaje de respuesta.
-    fn handle(&mut self, msg: Alive, ctx: &mut Context<Self>) -> Self::Result {
-        log::debug!(
-            "[ELECTION] Driver {} received alive message from {}",
-            self.id,
-            msg.responder_id
-        );
+    /// Maneja los mensajes de sospecha de caida de un driver (no envio heartbeats a tiempo).
+    /// - Deja de contarlo para la mayoria de las elecciones y olvida su posicion, para no ofrecerle viajes.
+    /// - Lo marca como sospechado en la vista de membresia.
+    /// - Si el driver sospechado es el lider, inicia una eleccion.
+    /// - Si este driver es el lider, le saca las zonas que coordinaba.
+    fn handle(&mut self, msg: SuspectDriver, ctx: &mut Context<Self>) -> Self::Result {
+        self.suspected_drivers.insert(msg.id);
+        self.driver_positions.remove(&msg.id);
+
+        if let Some(member) = self.membership.suspect(msg.id) {
+            self.on_member_update(member, ctx);
+        }
 
-        if let Some(timeout) = self.election_timeout {
-            ctx.cancel_future(timeout);
-            self.election_timeout = None;
+        if self.leader_id == Some(msg.id) {
+            log::warn!("[ELECTION] The leader {} is suspected to be down", msg.id);
+            self.leader_id = None;
+            ctx.notify(StartElection {});
+            return;
         }
-        // Cancel election timeout as we received a response
+
+        self.assign_zones();
+        self.commit_assignments(ctx);
+        self.check_votes(ctx);
     }
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct Coordinator {
-    pub leader_id: u32,
+pub struct UnsuspectDriver {
+    /// Id del driver
+    pub id: u32,
 }
 
-impl Handler<Coordinator> for CentralDriver {
+impl Handler<UnsuspectDriver> for CentralDriver {
     type Result = ();
 
-    /// Maneja los mensajes de coordinador.
-    /// - Loggea un mensaje de que el driver recibio un mensaje de coordinador.
-    /// - Setea el id del lider con el id del driver que envia el mensaje.
-    /// - Si el driver es el lider, loggea un mensaje de que el driver es el lider.
-    /// - Envía un mensaje al actor `TripHandler` para que notifique la posicion del driver.
-    fn handle(&mut self, msg: Coordinator, _ctx: &mut Context<Self>) -> Self::Result {
-        log::info!("[ELECTION] {} is the new leader", msg.leader_id);
+    /// Maneja los mensajes de un driver sospechado que volvio a dar señales de vida.
+    /// Vuelve a contarlo para la mayoria de las elecciones y lo marca como vivo en la vista de membresia.
+    /// Si este driver es el lider, vuelve a repartir las zonas.
+    fn handle(&mut self, msg: UnsuspectDriver, ctx: &mut Context<Self>) -> Self::Result {
+        self.suspected_drivers.remove(&msg.id);
+        self.assign_zones();
 
-        self.leader_id = Some(msg.leader_id);
+        let addr = self
+            .membership
+            .get(msg.id)
+            .map(|member| member.addr.clone());
 
-        if self.im_leader() {
-            log::info!("[ELECTION] Oh!, that is me");
+        if let Some(member) = addr.and_then(|addr| self.membership.join(msg.id, addr)) {
+            self.on_member_update(member, ctx);
         }
-
-        let _ = self
-            .trip_handler
-            .try_send(ForceNotifyPosition {})
-            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
     }
 }
 
@@ -471,46 +1375,69 @@ impl Handler<RedirectNewTrip> for CentralDriver {
     type Result = Result<(), String>;
 
     /// Maneja los mensajes de redireccion de un nuevo viaje.
-    /// - Si el driver es el lider, envia un mensaje  `FindDriver` al actor con el id del pasajero, la posicion de origen y la posicion de destino.
-    /// - Si el driver no es el lider, envia un mensaje "TripRequest" al lider con el id del pasajero, la posicion de origen y la posicion de destino.
+    /// El pedido lo atiende el coordinador de la zona en la que esta el pasajero o, si no hay conexion
+    /// con el, el lider.
+    /// - Si el driver es quien atiende el pedido, envia un mensaje  `FindDriver` al actor con el id del pasajero, la posicion de origen y la posicion de destino.
+    /// - Si no, envia un mensaje "TripRequest" a quien lo atiende con el id del pasajero, la posicion de origen y la posicion de destino.
+    /// - Si no se conoce al lider o no hay conexion con el, guarda el pedido hasta que se conozca uno.
     fn handle(&mut self, msg: RedirectNewTrip, ctx: &mut Context<Self>) -> Self::Result {
+        let zone = self.zones.zone_of(&msg.source);
+
+        let dispatcher = self
+            .coordinator_of(zone)
+            .filter(|cid| self.is_reachable(*cid))
+            .or(self.leader_id.filter(|lid| self.is_reachable(*lid)));
+
         log::debug!(
-            "[TRIP] Redirect to leader a trip for passenger {}",
-            msg.passenger_id
+            "[TRIP] Redirect a trip for passenger {} in zone {} to {:?}",
+            msg.passenger_id,
+            zone,
+            dispatcher
         );
 
-        if let Some(lid) = &self.leader_id {
-            if self.im_leader() {
-                ctx.address()
-                    .try_send(FindDriver {
-                        passenger_id: msg.passenger_id,
-                        source: msg.source,
-                        destination: msg.destination,
-                    })
-                    .map_err(|e| {
-                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-                        e.to_string()
-                    })?;
-            } else {
-                let leader_addr = self.connection_with_drivers.get(lid);
+        if dispatcher == Some(self.id) {
+            ctx.address()
+                .try_send(FindDriver {
+                    passenger_id: msg.passenger_id,
+                    source: msg.source,
+                    destination: msg.destination,
+                    handoff_zones: None,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?;
 
-                if let Some(laddr) = leader_addr {
-                    let data = serde_json::to_string(&DriverMessages::TripRequest {
-                        passenger_id: msg.passenger_id,
-                        passenger_location: msg.source,
-                        destination: msg.destination,
-                    })
-                    .map_err(|e| {
-                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-                        e.to_string()
-                    })?;
+            return Ok(());
+        }
 
-                    laddr.try_send(SendAll { data }).map_err(|e| {
-                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-                        e.to_string()
-                    })?;
-                }
+        let dispatcher_addr = dispatcher.and_then(|did| self.connection_with_drivers.get(&did));
+
+        match dispatcher_addr {
+            Some(laddr) => {
+                let data = serde_json::to_string(&DriverMessages::TripRequest {
+                    passenger_id: msg.passenger_id,
+                    passenger_location: msg.source,
+                    destination: msg.destination,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?;
+
+                laddr.try_send(SendAll { data }).map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?;
             }
+            None => self.buffer_trip(
+                PendingTrip {
+                    passenger_id: msg.passenger_id,
+                    source: msg.source,
+                    destination: msg.destination,
+                },
+                ctx,
+            ),
         }
 
         Ok(())
@@ -523,31 +1450,125 @@ pub struct FindDriver {
     pub passenger_id: u32,
     pub source: Position,
     pub destination: Position,
+    /// Zonas vecinas a las que pasarle la busqueda si no se encuentra un driver.
+    /// Es None para los pedidos nuevos, que las calculan a partir de la posicion del pasajero.
+    pub handoff_zones: Option<Vec<u32>>,
 }
 
 impl Handler<FindDriver> for CentralDriver {
     type Result = ();
 
     /// Maneja los mensajes de busqueda de un driver.
-    /// Genera un actor DriverFinder y lo inicia para buscar un driver a un pasajero.
+    /// Solo busca el lider, el coordinador de la zona del pasajero o el coordinador al que otra zona
+    /// le paso la busqueda.
+    /// Genera un actor DriverFinder y lo inicia para buscar un driver a un pasajero. Si el pasajero esta
+    /// cerca del borde de otras zonas, el DriverFinder le pasa la busqueda a ellas si no encuentra un driver.
+    /// Registra la busqueda como abierta: el lider la replica en los seguidores, para que un nuevo lider
+    /// pueda retomarla si este se cae, y un coordinador de zona se la informa al lider, que la retoma si el
+    /// coordinador se cae.
+    /// Si esta activa la asignacion en conjunto, los pedidos nuevos se juntan durante BATCH_WINDOW_MS y
+    /// se asignan todos juntos al cerrar la ventana (ver `MatchBatch`).
     fn handle(&mut self, msg: FindDriver, ctx: &mut Context<Self>) -> Self::Result {
-        if !self.im_leader() {
+        let zone = self.zones.zone_of(&msg.source);
+
+        let can_dispatch =
+            self.im_leader() || self.coordinates(zone) || msg.handoff_zones.is_some();
+
+        if !can_dispatch || self.driver_finders.contains_key(&msg.passenger_id) {
             return;
         }
 
         log::debug!("[TRIP] Finding a driver for passenger {}", msg.passenger_id);
 
-        self.driver_finders.insert(
-            msg.passenger_id,
-            DriverFinder::new(
-                ctx.address().clone(),
-                msg.passenger_id,
-                msg.source,
-                msg.destination,
-                self.driver_positions.clone(),
-            )
-            .start(),
-        );
+        let trip = PendingTrip {
+            passenger_id: msg.passenger_id,
+            source: msg.source,
+            destination: msg.destination,
+        };
+
+        if self.im_leader() && msg.handoff_zones.is_none() && self.all_drivers_busy() {
+            self.enqueue_trip(trip, ctx);
+            return;
+        }
+
+        self.open_search(trip);
+
+        if msg.handoff_zones.is_none() && !self.batch_window.is_zero() {
+            if !self
+                .batched_trips
+                .iter()
+                .any(|batched| batched.passenger_id == msg.passenger_id)
+            {
+                self.batched_trips.push(trip);
+            }
+
+            if self.batch_timeout.is_none() {
+                self.batch_timeout = Some(ctx.notify_later(MatchBatch {}, self.batch_window));
+            }
+            return;
+        }
+
+        self.start_driver_finder(trip, msg.handoff_zones, None, ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct MatchBatch {}
+
+impl Handler<MatchBatch> for CentralDriver {
+    type Result = ();
+
+    /// Cierra la ventana de pedidos y los asigna en conjunto a los drivers libres conocidos, minimizando
+    /// la distancia total que recorren los drivers hasta los pasajeros (sin superar el radio de busqueda
+    /// maximo ni asignarle a un pasajero un driver que ya solto su viaje). A cada pasajero se le inicia un DriverFinder, que le ofrece el viaje primero al driver
+    /// asignado y, si no lo toma, sigue buscando como siempre.
+    fn handle(&mut self, _msg: MatchBatch, ctx: &mut Context<Self>) -> Self::Result {
+        self.batch_timeout = None;
+
+        let trips = std::mem::take(&mut self.batched_trips);
+
+        let free_drivers = self
+            .driver_positions
+            .iter()
+            .filter(|(id, _)| !self.assignment_log.driver_assigned(**id))
+            .map(|(id, position)| (*id, *position))
+            .collect::<Vec<(u32, Position)>>();
+
+        let costs = trips
+            .iter()
+            .map(|trip| {
+                let excluded = self.excluded_drivers.get(&trip.passenger_id);
+
+                free_drivers
+                    .iter()
+                    .map(|(id, position)| {
+                        let distance = position.distance_to(&trip.source);
+                        let allowed = distance <= self.max_search_radius
+                            && !excluded.is_some_and(|excluded| excluded.contains(id));
+                        allowed.then_some(distance)
+                    })
+                    .collect::<Vec<Option<u32>>>()
+            })
+            .collect::<Vec<Vec<Option<u32>>>>();
+
+        let matching = min_cost_assignment(&costs);
+
+        for (trip, matched) in trips.into_iter().zip(matching) {
+            let matched_driver = matched.map(|idx| free_drivers[idx].0);
+
+            log::info!(
+                "[MATCHING] Passenger {} matched with driver {:?}",
+                trip.passenger_id,
+                matched_driver
+            );
+
+            if self.driver_finders.contains_key(&trip.passenger_id) {
+                continue;
+            }
+
+            self.start_driver_finder(trip, None, matched_driver, ctx);
+        }
     }
 }
 
@@ -558,6 +1579,12 @@ pub struct CanHandleTrip {
     pub source: Position,
     pub destination: Position,
     pub driver_id: u32,
+    /// Id del driver que ofrece el viaje, al que se le responde
+    pub coordinator_id: u32,
+    /// Id de la oferta
+    pub offer_id: u64,
+    /// Tiempo durante el cual la oferta es valida
+    pub lease: Duration,
 }
 
 impl Handler<CanHandleTrip> for CentralDriver {
@@ -574,6 +1601,9 @@ impl Handler<CanHandleTrip> for CentralDriver {
                     passenger_location: msg.source,
                     destination: msg.destination,
                     self_id: self.id,
+                    coordinator_id: msg.coordinator_id,
+                    offer_id: msg.offer_id,
+                    lease: msg.lease,
                 })
                 .inspect_err(|e| {
                     log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
@@ -588,6 +1618,9 @@ impl Handler<CanHandleTrip> for CentralDriver {
                 passenger_id: msg.passenger_id,
                 destination: msg.destination,
                 driver_id: msg.driver_id,
+                coordinator_id: msg.coordinator_id,
+                offer_id: msg.offer_id,
+                lease_ms: msg.lease.as_millis() as u64,
             })
             .inspect_err(|e| {
                 log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
@@ -608,44 +1641,60 @@ pub struct CanHandleTripACK {
     pub passenger_id: u32,
     pub response: bool,
     pub driver_id: u32,
+    /// Id del driver que ofrecio el viaje
+    pub coordinator_id: u32,
+    /// Id de la oferta que se responde
+    pub offer_id: u64,
 }
 
 impl Handler<CanHandleTripACK> for CentralDriver {
     type Result = ();
 
-    /// Redirige al DriverFinder que consulto acerca de tomar el viaje.
-    fn handle(&mut self, msg: CanHandleTripACK, _ctx: &mut Context<Self>) -> Self::Result {
-        if self.im_leader() {
-            if let Some(df) = self.driver_finders.get(&msg.passenger_id) {
-                let _ = df
-                    .try_send(DriverACK {
-                        response: msg.response,
-                        driver_id: msg.driver_id,
-                    })
-                    .inspect_err(|e| {
-                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-                    });
+    /// Redirige al DriverFinder que consulto acerca de tomar el viaje, que puede estar en este driver
+    /// o en el driver que ofrecio el viaje.
+    /// Si la busqueda ya termino y el driver acepto, la oferta vencio y se le revoca.
+    fn handle(&mut self, msg: CanHandleTripACK, ctx: &mut Context<Self>) -> Self::Result {
+        if msg.coordinator_id == self.id {
+            match self.driver_finders.get(&msg.passenger_id) {
+                Some(df) => {
+                    let _ = df
+                        .try_send(DriverACK {
+                            passenger_id: msg.passenger_id,
+                            response: msg.response,
+                            driver_id: msg.driver_id,
+                            offer_id: msg.offer_id,
+                        })
+                        .inspect_err(|e| {
+                            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                        });
+                }
+                None if msg.response => ctx.notify(RevokeOffer {
+                    passenger_id: msg.passenger_id,
+                    driver_id: msg.driver_id,
+                    offer_id: msg.offer_id,
+                }),
+                None => (),
             }
 
             return;
         }
 
-        if let Some(lid) = &self.leader_id {
-            if let Some(leader) = self.connection_with_drivers.get(lid) {
-                let parsed_data = serde_json::to_string(&DriverMessages::CanHandleTripACK {
-                    response: msg.response,
-                    passenger_id: msg.passenger_id,
-                    driver_id: self.id,
-                })
-                .inspect_err(|e| {
+        if let Some(coordinator) = self.connection_with_drivers.get(&msg.coordinator_id) {
+            let parsed_data = serde_json::to_string(&DriverMessages::CanHandleTripACK {
+                response: msg.response,
+                passenger_id: msg.passenger_id,
+                driver_id: self.id,
+                coordinator_id: msg.coordinator_id,
+                offer_id: msg.offer_id,
+            })
+            .inspect_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            });
+
+            if let Ok(data) = parsed_data {
+                let _ = coordinator.try_send(SendAll { data }).inspect_err(|e| {
                     log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                 });
-
-                if let Ok(data) = parsed_data {
-                    let _ = leader.try_send(SendAll { data }).inspect_err(|e| {
-                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-                    });
-                }
             }
         }
     }
@@ -706,7 +1755,6 @@ impl Handler<SendTripResponse> for CentralDriver {
     /// Maneja los mensajes de respuesta de un viaje.
     /// - Si el driver recibe un mensaje de respuesta, envia un mensaje al pasajero con el estado del viaje y el detalle.
     /// - Si el driver no recibe un mensaje de respuesta, loggea un mensaje de error.
-
     fn handle(&mut self, msg: SendTripResponse, _ctx: &mut Context<Self>) -> Self::Result {
         let parsed_data = serde_json::to_string(&TripMessages::TripResponse {
             status: msg.status,
@@ -740,10 +1788,1184 @@ impl Handler<RemoveDriverFinder> for CentralDriver {
 
     /// Elimina un DriverFinder si este existe
     ///  - passenger_id: ID del pasajero que pidio el viaje
-
+    ///
+    /// La busqueda deja de estar abierta y se le notifica a los seguidores. Si la llevaba un coordinador
+    /// de zona, se lo informa al lider. Si se encontro un driver, la busqueda sigue abierta hasta que se
+    /// confirme la asignacion, por si se cae el lider antes.
     fn handle(&mut self, msg: RemoveDriverFinder, _ctx: &mut Context<Self>) -> Self::Result {
         if let Some(_) = self.driver_finders.remove(&msg.passenger_id) {
             log::info!("Removing driver finder {}", msg.passenger_id);
+
+            if let Some(lid) = self
+                .leader_id
+                .filter(|lid| *lid != self.id && self.is_reachable(*lid))
+            {
+                self.send_to_driver(
+                    lid,
+                    &DriverMessages::ZoneTripSearchDone {
+                        passenger_id: msg.passenger_id,
+                    },
+                );
+            }
+        }
+
+        if self.assignment_log.passenger_assigned(msg.passenger_id) {
+            return;
+        }
+
+        self.close_search(msg.passenger_id);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ZoneTripSearch {
+    /// Busqueda abierta por el coordinador
+    pub trip: PendingTrip,
+    /// Id del coordinador de zona que lleva la busqueda
+    pub coordinator_id: u32,
+}
+
+impl Handler<ZoneTripSearch> for CentralDriver {
+    type Result = ();
+
+    /// Registra una busqueda abierta por un coordinador de zona (solo en el lider) y la replica en los
+    /// seguidores como las propias. Si el coordinador se cae antes de terminarla, el lider la retoma.
+    fn handle(&mut self, msg: ZoneTripSearch, _ctx: &mut Context<Self>) -> Self::Result {
+        let passenger_id = msg.trip.passenger_id;
+
+        if !self.im_leader() || self.assignment_log.passenger_assigned(passenger_id) {
+            return;
+        }
+
+        log::debug!(
+            "[ZONES] Coordinator {} is searching a driver for passenger {}",
+            msg.coordinator_id,
+            passenger_id
+        );
+
+        self.zone_searches.insert(passenger_id, msg.coordinator_id);
+        self.pending_trips.insert(passenger_id, msg.trip);
+        self.broadcast(&DriverMessages::ReplicateTripSearch {
+            term: self.current_term,
+            trip: msg.trip,
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ZoneTripSearchDone {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id del coordinador de zona que llevaba la busqueda
+    pub coordinator_id: u32,
+}
+
+impl Handler<ZoneTripSearchDone> for CentralDriver {
+    type Result = ();
+
+    /// Da por terminada la busqueda de un coordinador de zona (solo en el lider).
+    /// Si la busqueda ya paso a otro coordinador, o si se encontro un driver y la asignacion todavia no
+    /// se confirmo, la busqueda sigue abierta.
+    fn handle(&mut self, msg: ZoneTripSearchDone, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader()
+            || self.zone_searches.get(&msg.passenger_id) != Some(&msg.coordinator_id)
+            || self.assignment_log.passenger_assigned(msg.passenger_id)
+        {
+            return;
+        }
+
+        self.close_search(msg.passenger_id);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Shutdown {
+    /// Canal por el que se avisa que el driver termino su viaje actual y puede apagarse
+    pub idle_tx: oneshot::Sender<()>,
+}
+
+impl Handler<Shutdown> for CentralDriver {
+    type Result = ();
+
+    /// Maneja el apagado ordenado del driver.
+    /// - Anuncia por gossip que deja el cluster, para que no se intenten reconectar con el.
+    /// - Si el driver es el lider, elige como sucesor al driver conectado y no sospechado de mayor id,
+    ///   le envia su estado de despacho y su log de asignaciones y le pide que inicie una eleccion sin
+    ///   esperar el timeout (TimeoutNow). Como el sucesor tiene el log al dia, los demas drivers lo pueden
+    ///   votar; si no junta la mayoria, el cluster elige a otro lider como ante cualquier caida.
+    ///   Sus busquedas abiertas las retoma el nuevo lider.
+    /// - Le pide al `TripHandler` que termine el viaje actual sin aceptar nuevos, el cual
+    ///   avisa por idle_tx cuando el driver quede libre.
+    fn handle(&mut self, msg: Shutdown, _ctx: &mut Context<Self>) -> Self::Result {
+        self.draining = true;
+
+        if let Some(me) = self.membership.leave() {
+            self.broadcast(&DriverMessages::Gossip { members: vec![me] });
+        }
+
+        if self.im_leader() {
+            for (_, finder) in self.driver_finders.drain() {
+                finder.do_send(StopSearch {});
+            }
+
+            let successor = self
+                .connection_with_drivers
+                .keys()
+                .filter(|id| !self.suspected_drivers.contains(id))
+                .max()
+                .copied();
+
+            match successor {
+                Some(sid) => {
+                    log::info!(
+                        "[ELECTION] Handing the leadership over to driver {} before shutting down",
+                        sid
+                    );
+
+                    self.send_dispatch_snapshot(sid);
+                    self.send_log_entries(sid);
+                    self.send_to_driver(
+                        sid,
+                        &DriverMessages::TimeoutNow {
+                            term: self.current_term,
+                        },
+                    );
+                }
+                None => {
+                    log::warn!("[ELECTION] There is no driver to hand the leadership over to");
+                }
+            }
+
+            self.leader_id = None;
+        }
+
+        let _ = self
+            .trip_handler
+            .try_send(Drain {
+                idle_tx: msg.idle_tx,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ExpireBufferedTrip {
+    /// Id del pasajero
+    passenger_id: u32,
+}
+
+impl Handler<ExpireBufferedTrip> for CentralDriver {
+    type Result = ();
+
+    /// Vence el tiempo de espera de un pedido de viaje guardado sin conocer al lider.
+    /// Le avisa al pasajero que su pedido no pudo ser atendido.
+    fn handle(&mut self, msg: ExpireBufferedTrip, ctx: &mut Context<Self>) -> Self::Result {
+        if self.buffered_trips.remove(&msg.passenger_id).is_none() {
+            return;
+        }
+
+        log::warn!(
+            "[TRIP] No leader showed up in time for the trip of passenger {}",
+            msg.passenger_id
+        );
+
+        ctx.notify(RejectTrip {
+            passenger_id: msg.passenger_id,
+            detail: "There is no leader to handle your request, try again later".to_string(),
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QueueTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+}
+
+impl Handler<QueueTrip> for CentralDriver {
+    type Result = ();
+
+    /// Maneja una busqueda que termino sin encontrar un driver.
+    /// - Si este driver no es el lider, se la reenvia. Si no hay conexion con el lider, se rechaza el pedido.
+    /// - Si todos los drivers estan en un viaje, encola el pedido hasta que se libere alguno.
+    /// - Si no, le avisa al pasajero que no hay drivers disponibles cerca.
+    fn handle(&mut self, msg: QueueTrip, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            match self.leader_id.filter(|lid| self.is_reachable(*lid)) {
+                Some(lid) => self.send_to_driver(
+                    lid,
+                    &DriverMessages::QueueTrip {
+                        passenger_id: msg.passenger_id,
+                        passenger_location: msg.source,
+                        destination: msg.destination,
+                    },
+                ),
+                None => ctx.notify(RejectTrip {
+                    passenger_id: msg.passenger_id,
+                    detail: "There are no drivers available near your location".to_string(),
+                }),
+            }
+            return;
+        }
+
+        if self.all_drivers_busy() {
+            self.enqueue_trip(
+                PendingTrip {
+                    passenger_id: msg.passenger_id,
+                    source: msg.source,
+                    destination: msg.destination,
+                },
+                ctx,
+            );
+            return;
+        }
+
+        log::info!("There are no drivers near passenger {}", msg.passenger_id);
+
+        self.excluded_drivers.remove(&msg.passenger_id);
+
+        ctx.notify(RejectTrip {
+            passenger_id: msg.passenger_id,
+            detail: "There are no drivers available near your location".to_string(),
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DriverAvailable {
+    /// Id del driver que quedo libre
+    pub driver_id: u32,
+}
+
+impl Handler<DriverAvailable> for CentralDriver {
+    type Result = ();
+
+    /// Un driver termino su viaje y quedo libre. Si este driver es el lider, despacha los pedidos
+    /// en espera; si no, se lo reenvia al lider.
+    fn handle(&mut self, msg: DriverAvailable, ctx: &mut Context<Self>) -> Self::Result {
+        if self.im_leader() {
+            log::debug!("[QUEUE] Driver {} is available again", msg.driver_id);
+            self.dispatch_waiting_trips(ctx);
+            return;
+        }
+
+        if let Some(lid) = self.leader_id {
+            self.send_to_driver(
+                lid,
+                &DriverMessages::DriverAvailable {
+                    driver_id: msg.driver_id,
+                },
+            );
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct InformPassenger {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Progreso de la busqueda
+    pub detail: String,
+}
+
+#[async_handler]
+impl Handler<InformPassenger> for CentralDriver {
+    type Result = ();
+
+    /// Le avisa al pasajero el progreso de la busqueda de su viaje con un mensaje Info,
+    /// por una conexion que se cierra luego de enviarlo.
+    async fn handle(&mut self, msg: InformPassenger, _ctx: &mut Context<Self>) -> Self::Result {
+        let parsed_data = serde_json::to_string(&TripMessages::TripResponse {
+            status: TripStatus::Info,
+            detail: msg.detail,
+        });
+
+        let passenger_id = msg.passenger_id;
+
+        let res = async move {
+            match parsed_data {
+                Ok(data) => PassengerConnection::notify(passenger_id, data).await,
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        .await;
+
+        if let Err(e) = res {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RejectTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Motivo por el que no se pudo atender el pedido
+    pub detail: String,
+}
+
+#[async_handler]
+impl Handler<RejectTrip> for CentralDriver {
+    type Result = ();
+
+    /// Se conecta con el pasajero y le avisa que su pedido de viaje no pudo ser atendido.
+    async fn handle(&mut self, msg: RejectTrip, _ctx: &mut Context<Self>) -> Self::Result {
+        let self_addr = _ctx.address();
+
+        let passenger_addr = PassengerConnection::connect(self_addr, msg.passenger_id).await;
+
+        match passenger_addr {
+            Ok(addr) => {
+                self.passengers.insert(msg.passenger_id, addr);
+
+                _ctx.notify(SendTripResponse {
+                    status: TripStatus::Error,
+                    detail: msg.detail,
+                    passenger_id: msg.passenger_id,
+                });
+            }
+            Err(e) => log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()),
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Gossip {
+    /// Vista de membresia de otro driver
+    pub members: Vec<MemberInfo>,
+}
+
+impl Handler<Gossip> for CentralDriver {
+    type Result = ();
+
+    /// Combina la vista de membresia recibida por gossip con la propia y reacciona a los cambios.
+    fn handle(&mut self, msg: Gossip, ctx: &mut Context<Self>) -> Self::Result {
+        for update in msg.members {
+            if let Some(member) = self.membership.merge(update) {
+                self.on_member_update(member, ctx);
+            }
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ConfirmMemberDead {
+    /// Id del driver
+    id: u32,
+    /// Encarnacion con la que se sospecho del driver
+    incarnation: u64,
+}
+
+impl Handler<ConfirmMemberDead> for CentralDriver {
+    type Result = ();
+
+    /// Si el driver sigue sospechado con la misma encarnacion (no lo desmintio), lo confirma como muerto.
+    fn handle(&mut self, msg: ConfirmMemberDead, ctx: &mut Context<Self>) -> Self::Result {
+        let still_suspected = self.membership.get(msg.id).is_some_and(|member| {
+            member.state == MemberState::Suspect && member.incarnation == msg.incarnation
+        });
+
+        if !still_suspected {
+            return;
+        }
+
+        if let Some(member) = self.membership.confirm_dead(msg.id) {
+            self.on_member_update(member, ctx);
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ZoneAssignment {
+    /// Termino del lider que reparte las zonas
+    pub term: u64,
+    /// Id del lider que reparte las zonas
+    pub leader_id: u32,
+    /// Id del driver que coordina cada zona, segun el numero de zona
+    pub coordinators: Vec<u32>,
+}
+
+impl Handler<ZoneAssignment> for CentralDriver {
+    type Result = ();
+
+    /// Guarda el reparto de zonas enviado por el lider.
+    /// Si este driver pasa a coordinar alguna zona, olvida las posiciones replicadas por el lider, ya que
+    /// en adelante recibe las de sus zonas directamente.
+    /// Envía un mensaje al actor `TripHandler` para que le notifique la posicion al coordinador de su zona.
+    fn handle(&mut self, msg: ZoneAssignment, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            return;
+        }
+
+        log::info!("[ZONES] Zone coordinators: {:?}", msg.coordinators);
+
+        if msg.coordinators.contains(&self.id) && !self.zone_coordinators.contains(&self.id) {
+            self.driver_positions.clear();
+        }
+
+        self.zone_coordinators = msg.coordinators;
+
+        let _ = self
+            .trip_handler
+            .try_send(ForceNotifyPosition {})
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct HandOffTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+    /// Zonas vecinas a las que todavia no se les paso la busqueda, de la mas cercana a la mas lejana
+    pub handoff_zones: Vec<u32>,
+}
+
+impl Handler<HandOffTrip> for CentralDriver {
+    type Result = ();
+
+    /// Le pasa la busqueda de un driver al coordinador de la zona vecina mas cercana al pasajero,
+    /// junto con las zonas que quedan por consultar.
+    /// Se saltean las zonas que coordina este driver, ya que sus drivers fueron consultados, y las
+    /// de coordinadores sin conexion. Si no queda ninguna, le avisa al pasajero que no hay drivers cerca.
+    fn handle(&mut self, mut msg: HandOffTrip, ctx: &mut Context<Self>) -> Self::Result {
+        while !msg.handoff_zones.is_empty() {
+            let zone = msg.handoff_zones.remove(0);
+
+            let coordinator = self
+                .coordinator_of(zone)
+                .filter(|cid| *cid != self.id && self.is_reachable(*cid));
+
+            if let Some(cid) = coordinator {
+                log::info!(
+                    "[ZONES] Handing off the trip of passenger {} to zone {} (driver {})",
+                    msg.passenger_id,
+                    zone,
+                    cid
+                );
+
+                self.send_to_driver(
+                    cid,
+                    &DriverMessages::ZoneHandoff {
+                        passenger_id: msg.passenger_id,
+                        passenger_location: msg.source,
+                        destination: msg.destination,
+                        handoff_zones: msg.handoff_zones,
+                    },
+                );
+                return;
+            }
+        }
+
+        log::info!("There are no drivers near passenger {}", msg.passenger_id);
+
+        ctx.notify(RejectTrip {
+            passenger_id: msg.passenger_id,
+            detail: "There are no drivers available near your location".to_string(),
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ProposeAssignment {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id del driver que acepto el viaje
+    pub driver_id: u32,
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+    /// Id de la oferta que acepto el driver
+    pub offer_id: u64,
+}
+
+impl Handler<ProposeAssignment> for CentralDriver {
+    type Result = ();
+
+    /// Maneja la propuesta de asignarle un viaje al driver que acepto tomarlo.
+    /// - Si este driver no es el lider, se la reenvia. Si no hay conexion con el lider, libera al driver
+    ///   y le avisa al pasajero que su pedido no pudo ser atendido.
+    /// - Si el pasajero cancelo el viaje, libera al driver.
+    /// - Si el pasajero o el driver ya tienen un viaje en el log de asignaciones, libera al driver. Si el
+    ///   ocupado es el driver, le avisa al pasajero que su pedido no pudo ser atendido.
+    /// - Si no, agrega la asignacion al log y la replica. El driver y el pasajero se enteran recien
+    ///   cuando la asignacion se confirma en la mayoria de los drivers.
+    fn handle(&mut self, msg: ProposeAssignment, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            let leader = self
+                .leader_id
+                .filter(|lid| self.connection_with_drivers.contains_key(lid));
+
+            match leader {
+                Some(lid) => self.send_to_driver(
+                    lid,
+                    &DriverMessages::ProposeAssignment {
+                        passenger_id: msg.passenger_id,
+                        driver_id: msg.driver_id,
+                        passenger_location: msg.source,
+                        destination: msg.destination,
+                        offer_id: msg.offer_id,
+                    },
+                ),
+                None => {
+                    log::warn!(
+                        "[ASSIGNMENT] There is no leader to commit the trip of passenger {}",
+                        msg.passenger_id
+                    );
+
+                    ctx.notify(RevokeOffer {
+                        passenger_id: msg.passenger_id,
+                        driver_id: msg.driver_id,
+                        offer_id: msg.offer_id,
+                    });
+
+                    ctx.notify(RejectTrip {
+                        passenger_id: msg.passenger_id,
+                        detail: "There is no leader to handle your request, try again later"
+                            .to_string(),
+                    });
+                }
+            }
+
+            return;
+        }
+
+        if self.cancelled_trips.contains(&msg.passenger_id) {
+            log::warn!(
+                "[ASSIGNMENT] Discarding driver {} for passenger {}, the trip was cancelled",
+                msg.driver_id,
+                msg.passenger_id
+            );
+
+            ctx.notify(RevokeOffer {
+                passenger_id: msg.passenger_id,
+                driver_id: msg.driver_id,
+                offer_id: msg.offer_id,
+            });
+
+            return;
+        }
+
+        if self.assignment_log.passenger_assigned(msg.passenger_id)
+            || self.driver_full(msg.driver_id)
+        {
+            log::warn!(
+                "[ASSIGNMENT] Discarding driver {} for passenger {}, one of them already has a trip",
+                msg.driver_id,
+                msg.passenger_id
+            );
+
+            ctx.notify(RevokeOffer {
+                passenger_id: msg.passenger_id,
+                driver_id: msg.driver_id,
+                offer_id: msg.offer_id,
+            });
+
+            if !self.assignment_log.passenger_assigned(msg.passenger_id) {
+                ctx.notify(RejectTrip {
+                    passenger_id: msg.passenger_id,
+                    detail: "There are no drivers available near your location".to_string(),
+                });
+            }
+
+            return;
+        }
+
+        log::info!(
+            "[ASSIGNMENT] Proposing driver {} for passenger {}",
+            msg.driver_id,
+            msg.passenger_id
+        );
+
+        self.append_assignment_op(
+            AssignmentOp::Assign(Assignment {
+                passenger_id: msg.passenger_id,
+                driver_id: msg.driver_id,
+                source: msg.source,
+                destination: msg.destination,
+                offer_id: msg.offer_id,
+            }),
+            ctx,
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ConfirmTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id del driver asignado
+    pub driver_id: u32,
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+    /// Id de la oferta que acepto el driver
+    pub offer_id: u64,
+}
+
+impl From<Assignment> for ConfirmTrip {
+    fn from(assignment: Assignment) -> Self {
+        Self {
+            passenger_id: assignment.passenger_id,
+            driver_id: assignment.driver_id,
+            source: assignment.source,
+            destination: assignment.destination,
+            offer_id: assignment.offer_id,
+        }
+    }
+}
+
+impl Handler<ConfirmTrip> for CentralDriver {
+    type Result = ();
+
+    /// Le confirma al driver asignado que el viaje es suyo. Si es este driver se lo indica al
+    /// `TripHandler`, junto con las condiciones que acepto el pasajero, si no se lo envia al driver indicado.
+    fn handle(&mut self, msg: ConfirmTrip, _ctx: &mut Context<Self>) -> Self::Result {
+        if msg.driver_id == self.id {
+            let _ = self
+                .trip_handler
+                .try_send(super::handle_trip::ConfirmTrip {
+                    passenger_id: msg.passenger_id,
+                    passenger_location: msg.source,
+                    destination: msg.destination,
+                    offer_id: msg.offer_id,
+                    terms: self
+                        .trip_terms
+                        .get(&msg.passenger_id)
+                        .cloned()
+                        .unwrap_or_default(),
+                })
+                .inspect_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                });
+
+            return;
+        }
+
+        self.send_to_driver(
+            msg.driver_id,
+            &DriverMessages::ConfirmTrip {
+                passenger_id: msg.passenger_id,
+                driver_id: msg.driver_id,
+                passenger_location: msg.source,
+                destination: msg.destination,
+                offer_id: msg.offer_id,
+            },
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RevokeOffer {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id del driver al que se le ofrecio el viaje
+    pub driver_id: u32,
+    /// Id de la oferta revocada
+    pub offer_id: u64,
+}
+
+impl Handler<RevokeOffer> for CentralDriver {
+    type Result = ();
+
+    /// Le revoca a un driver la oferta de un viaje, ya sea porque vencio o porque no le fue asignado,
+    /// para que quede libre. Si es este driver se lo indica al `TripHandler`, si no se lo envia al driver indicado.
+    fn handle(&mut self, msg: RevokeOffer, _ctx: &mut Context<Self>) -> Self::Result {
+        if msg.driver_id == self.id {
+            let _ = self
+                .trip_handler
+                .try_send(super::handle_trip::RevokeOffer {
+                    passenger_id: msg.passenger_id,
+                    offer_id: msg.offer_id,
+                })
+                .inspect_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                });
+
+            return;
+        }
+
+        self.send_to_driver(
+            msg.driver_id,
+            &DriverMessages::RevokeOffer {
+                passenger_id: msg.passenger_id,
+                driver_id: msg.driver_id,
+                offer_id: msg.offer_id,
+            },
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FinishAssignment {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id de la oferta por la que el driver tomo el viaje
+    pub offer_id: u64,
+}
+
+impl Handler<FinishAssignment> for CentralDriver {
+    type Result = ();
+
+    /// Registra en el log de asignaciones que termino el viaje de un pasajero, liberando al driver.
+    /// Si este driver no es el lider, se lo reenvia.
+    /// Si la asignacion activa del pasajero es de otra oferta (por ejemplo, porque el viaje se le
+    /// reasigno a otro driver), el aviso viene de un driver que ya no lo tiene y se descarta.
+    fn handle(&mut self, msg: FinishAssignment, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            match self.leader_id {
+                Some(lid) => self.send_to_driver(
+                    lid,
+                    &DriverMessages::FinishAssignment {
+                        passenger_id: msg.passenger_id,
+                        offer_id: msg.offer_id,
+                    },
+                ),
+                None => log::warn!(
+                    "[ASSIGNMENT] There is no leader to finish the trip of passenger {}",
+                    msg.passenger_id
+                ),
+            }
+
+            return;
+        }
+
+        let offer_id = match self.assignment_log.assignment_of(msg.passenger_id) {
+            Some(assignment) => assignment.offer_id,
+            None => return,
+        };
+
+        if offer_id != msg.offer_id {
+            log::warn!(
+                "[ASSIGNMENT] Ignoring the finish of passenger {} from revoked offer {}",
+                msg.passenger_id,
+                msg.offer_id
+            );
+            return;
+        }
+
+        log::info!(
+            "[ASSIGNMENT] Trip of passenger {} finished",
+            msg.passenger_id
+        );
+
+        self.append_assignment_op(
+            AssignmentOp::Complete {
+                passenger_id: msg.passenger_id,
+            },
+            ctx,
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CancelTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+}
+
+impl Handler<CancelTrip> for CentralDriver {
+    type Result = ();
+
+    /// Cancela el viaje de un pasajero a pedido suyo.
+    /// - Si este driver no es el lider, frena lo que tenga del viaje (ver `TripCancelled`) y se lo reenvia
+    ///   al lider, si hay conexion con el.
+    /// - Si es el lider, recuerda la cancelacion durante un tiempo para descartar las asignaciones que se
+    ///   propongan despues, saca el pedido de las colas, cierra la busqueda y le avisa a todos los drivers
+    ///   con `TripCancelled`, asi se frena la busqueda donde sea que se este haciendo y el driver asignado
+    ///   abandona el viaje.
+    fn handle(&mut self, msg: CancelTrip, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            ctx.notify(TripCancelled {
+                passenger_id: msg.passenger_id,
+            });
+
+            if let Some(lid) = self.leader_id.filter(|lid| self.is_reachable(*lid)) {
+                self.send_to_driver(
+                    lid,
+                    &DriverMessages::CancelTrip {
+                        passenger_id: msg.passenger_id,
+                    },
+                );
+            }
+
+            return;
+        }
+
+        log::info!("[TRIP] Passenger {} cancelled the trip", msg.passenger_id);
+
+        self.cancelled_trips.insert(msg.passenger_id);
+        self.excluded_drivers.remove(&msg.passenger_id);
+        ctx.notify_later(
+            ForgetCancelledTrip {
+                passenger_id: msg.passenger_id,
+            },
+            CANCELLED_TRIP_MEMORY,
+        );
+
+        self.batched_trips
+            .retain(|trip| trip.passenger_id != msg.passenger_id);
+
+        let queued = self.waiting_trips.len();
+        self.waiting_trips
+            .retain(|trip| trip.passenger_id != msg.passenger_id);
+
+        if self.waiting_trips.len() != queued {
+            self.inform_queue_positions(ctx);
+        }
+
+        self.close_search(msg.passenger_id);
+
+        self.broadcast(&DriverMessages::TripCancelled {
+            passenger_id: msg.passenger_id,
+        });
+
+        ctx.notify(TripCancelled {
+            passenger_id: msg.passenger_id,
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct TripCancelled {
+    /// Id del pasajero
+    pub passenger_id: u32,
+}
+
+impl Handler<TripCancelled> for CentralDriver {
+    type Result = ();
+
+    /// Frena lo que este driver tenga del viaje de un pasajero que lo cancelo: la busqueda, si la hace
+    /// este driver, o el pedido guardado a la espera de un lider. Ademas se lo indica al `TripHandler`,
+    /// por si es este driver el que lo lleva.
+    fn handle(&mut self, msg: TripCancelled, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(finder) = self.driver_finders.remove(&msg.passenger_id) {
+            finder.do_send(StopSearch {});
+        }
+
+        self.trip_terms.remove(&msg.passenger_id);
+
+        if let Some((_, timeout)) = self.buffered_trips.remove(&msg.passenger_id) {
+            ctx.cancel_future(timeout);
+        }
+
+        let _ = self
+            .trip_handler
+            .try_send(super::handle_trip::CancelTrip {
+                passenger_id: msg.passenger_id,
+            })
+            .inspect_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ForgetCancelledTrip {
+    /// Id del pasajero
+    passenger_id: u32,
+}
+
+impl Handler<ForgetCancelledTrip> for CentralDriver {
+    type Result = ();
+
+    /// Olvida la cancelacion de un viaje, para que el pasajero pueda volver a pedir uno.
+    fn handle(&mut self, msg: ForgetCancelledTrip, _ctx: &mut Context<Self>) -> Self::Result {
+        self.cancelled_trips.remove(&msg.passenger_id);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReleaseTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id del driver que solto el viaje
+    pub driver_id: u32,
+}
+
+impl Handler<ReleaseTrip> for CentralDriver {
+    type Result = ();
+
+    /// Un driver solto el viaje de un pasajero antes de buscarlo (por ejemplo, porque se le rompio el auto).
+    /// - Si este driver no es el lider, se lo reenvia.
+    /// - Si es el lider y el viaje sigue asignado a ese driver, registra en el log que lo solto y vuelve
+    ///   a buscarle un driver al pasajero, sin ofrecerselo a los drivers que ya lo soltaron.
+    fn handle(&mut self, msg: ReleaseTrip, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            match self.leader_id {
+                Some(lid) => self.send_to_driver(
+                    lid,
+                    &DriverMessages::ReleaseTrip {
+                        passenger_id: msg.passenger_id,
+                        driver_id: msg.driver_id,
+                    },
+                ),
+                None => log::warn!(
+                    "[ASSIGNMENT] There is no leader to reassign the trip of passenger {}",
+                    msg.passenger_id
+                ),
+            }
+
+            return;
+        }
+
+        let assignment = self
+            .assignment_log
+            .active_assignments()
+            .remove(&msg.passenger_id)
+            .filter(|assignment| assignment.driver_id == msg.driver_id);
+
+        let assignment = match assignment {
+            Some(assignment) => assignment,
+            None => return,
+        };
+
+        log::info!(
+            "[ASSIGNMENT] Driver {} released the trip of passenger {}, looking for another driver",
+            msg.driver_id,
+            msg.passenger_id
+        );
+
+        self.reassign_trip(assignment, assignment.source, ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdateTripPosition {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Posicion del auto que lleva al pasajero
+    pub position: Position,
+}
+
+impl Handler<UpdateTripPosition> for CentralDriver {
+    type Result = ();
+
+    /// Actualiza la posicion de un pasajero que va en un auto.
+    /// - Si este driver no es el lider, se la reenvia.
+    /// - Si es el lider y el pasajero tiene un driver asignado, la guarda para poder buscarle otro driver
+    ///   desde ahi si se pierde la conexion con el que lo lleva.
+    fn handle(&mut self, msg: UpdateTripPosition, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            if let Some(lid) = self.leader_id {
+                self.send_to_driver(
+                    lid,
+                    &DriverMessages::UpdateTripPosition {
+                        passenger_id: msg.passenger_id,
+                        position: msg.position,
+                    },
+                );
+            }
+
+            return;
+        }
+
+        if self.assignment_log.passenger_assigned(msg.passenger_id) {
+            self.passenger_positions
+                .insert(msg.passenger_id, msg.position);
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SurgeUpdate {
+    /// Termino del lider que envia los multiplicadores
+    pub term: u64,
+    /// Id del lider que envia los multiplicadores
+    pub leader_id: u32,
+    /// Multiplicador de cada celda con alta demanda
+    pub multipliers: HashMap<u32, f64>,
+}
+
+impl Handler<SurgeUpdate> for CentralDriver {
+    type Result = ();
+
+    /// Actualiza la copia local de los multiplicadores por demanda calculados por el lider, con los que
+    /// este driver presupuesta los pedidos que recibe.
+    fn handle(&mut self, msg: SurgeUpdate, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            return;
+        }
+
+        self.surge_multipliers = msg.multipliers;
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "FareQuote")]
+pub struct QuoteTrip {
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Paradas intermedias del viaje
+    pub stops: Vec<Position>,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+}
+
+impl Handler<QuoteTrip> for CentralDriver {
+    type Result = MessageResult<QuoteTrip>;
+
+    /// Presupuesta un viaje, pasando por sus paradas intermedias, con el multiplicador por demanda de la
+    /// celda en la que esta el pasajero.
+    fn handle(&mut self, msg: QuoteTrip, _ctx: &mut Context<Self>) -> Self::Result {
+        let surge = self
+            .surge_multipliers
+            .get(&self.surge_grid.cell_of(&msg.source))
+            .copied()
+            .unwrap_or(1.0);
+
+        MessageResult(
+            self.pricing
+                .route_quote(&msg.source, &msg.stops, &msg.destination, surge),
+        )
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordTripTerms {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Condiciones que acepto el pasajero
+    pub terms: TripTerms,
+}
+
+impl Handler<RecordTripTerms> for CentralDriver {
+    type Result = ();
+
+    /// Registra las condiciones que acepto un pasajero para su viaje: el multiplicador por demanda y
+    /// las paradas intermedias.
+    /// - Si este driver no es el lider, se las reenvia.
+    /// - Si es el lider, las loggea para auditarlas y se las envia a los demas drivers.
+    fn handle(&mut self, msg: RecordTripTerms, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            match self.leader_id {
+                Some(lid) => self.send_to_driver(
+                    lid,
+                    &DriverMessages::RecordTripTerms {
+                        passenger_id: msg.passenger_id,
+                        terms: msg.terms,
+                    },
+                ),
+                None => log::warn!(
+                    "[SURGE] There is no leader to record the surge of passenger {}",
+                    msg.passenger_id
+                ),
+            }
+
+            return;
+        }
+
+        log::info!(
+            "[SURGE] Passenger {} accepted a x{} multiplier",
+            msg.passenger_id,
+            msg.terms.surge
+        );
+
+        if !msg.terms.stops.is_empty() {
+            log::info!(
+                "[TRIP] Passenger {} will stop at {:?}",
+                msg.passenger_id,
+                msg.terms.stops
+            );
+        }
+
+        self.trip_terms.insert(msg.passenger_id, msg.terms.clone());
+        self.broadcast(&DriverMessages::ReplicateTripTerms {
+            term: self.current_term,
+            passenger_id: msg.passenger_id,
+            terms: msg.terms,
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdatePoolRoute {
+    /// Id del driver
+    pub driver_id: u32,
+    /// Ruta del auto del driver, o None si no puede sumar pasajeros
+    pub route: Option<PoolRoute>,
+}
+
+impl Handler<UpdatePoolRoute> for CentralDriver {
+    type Result = ();
+
+    /// Actualiza la ruta de un driver que lleva pasajeros y todavia tiene asientos libres, para poder
+    /// ofrecerle pedidos que le queden de camino.
+    /// - Si este driver no es el lider, se la reenvia.
+    /// - Si es el lider, la guarda (o la borra si es None) y se la envia a los demas drivers.
+    fn handle(&mut self, msg: UpdatePoolRoute, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            if let Some(lid) = self.leader_id {
+                self.send_to_driver(
+                    lid,
+                    &DriverMessages::UpdatePoolRoute {
+                        driver_id: msg.driver_id,
+                        route: msg.route,
+                    },
+                );
+            }
+
+            return;
+        }
+
+        self.set_pool_route(msg.driver_id, msg.route.clone());
+        self.broadcast(&DriverMessages::ReplicatePoolRoute {
+            term: self.current_term,
+            driver_id: msg.driver_id,
+            route: msg.route,
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DisconnectPassenger {
+    /// Id del pasajero
+    pub passenger_id: u32,
+}
+
+impl Handler<DisconnectPassenger> for CentralDriver {
+    type Result = ();
+
+    /// Cierra la conexion con un pasajero, por ejemplo cuando este driver solto su viaje, para que el
+    /// pasajero espere la conexion de otro driver.
+    fn handle(&mut self, msg: DisconnectPassenger, _ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(paddr) = self.passengers.remove(&msg.passenger_id) {
+            log::info!("Disconnecting with passenger {}", msg.passenger_id);
+            paddr.do_send(super::passenger_connection::CloseConnection {});
         }
     }
 }
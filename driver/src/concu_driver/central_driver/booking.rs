@@ -0,0 +1,242 @@
+use actix::{AsyncContext, Context, Handler, Message, MessageResult};
+use common::utils::{json_parser::Booking, time::now_ms};
+use tokio::sync::oneshot;
+
+use crate::concu_driver::{
+    assignment_log::{AssignmentOp, LogEntry},
+    json_parser::{DriverMessages, TripTerms},
+};
+
+use super::{CentralDriver, RecordTripTerms, RedirectNewTrip};
+
+impl CentralDriver {
+    /// Si este driver es el lider, registra en el log de asignaciones que empieza la busqueda de los viajes
+    /// reservados cuya hora de busqueda (la hora pedida menos la anticipacion configurada) ya llego.
+    /// La busqueda se lanza cuando se confirma la entrada (ver `apply_committed_bookings`).
+    pub(super) fn start_due_bookings(&mut self, ctx: &mut Context<Self>) {
+        if !self.im_leader() {
+            return;
+        }
+
+        let search_from = now_ms() + self.schedule_lead_time.as_millis() as u64;
+
+        let due = self
+            .assignment_log
+            .bookings()
+            .values()
+            .filter(|booking| booking.pickup_at <= search_from)
+            .cloned()
+            .collect::<Vec<Booking>>();
+
+        for booking in due {
+            self.append_assignment_op(AssignmentOp::StartBooking(booking), ctx);
+        }
+    }
+
+    /// Aplica las reservas de las entradas recien confirmadas del log de asignaciones:
+    /// - Le responde a los pasajeros que reservaron o cancelaron un viaje en este driver.
+    /// - Si este driver es el lider, por cada reserva cuya busqueda empieza registra las condiciones que el
+    ///   pasajero acepto al reservar y redirige el viaje como un pedido nuevo.
+    pub(super) fn apply_committed_bookings(
+        &mut self,
+        entries: &[LogEntry],
+        ctx: &mut Context<Self>,
+    ) {
+        for entry in entries {
+            let (booking_id, done) = match &entry.op {
+                AssignmentOp::Book(booking) => (booking.booking_id, true),
+                AssignmentOp::CancelBooking { booking_id } => (*booking_id, true),
+                AssignmentOp::StartBooking(booking) => (booking.booking_id, false),
+                _ => continue,
+            };
+
+            if let Some(reply) = self.booking_replies.remove(&booking_id) {
+                let _ = reply.send(done);
+            }
+
+            let booking = match &entry.op {
+                AssignmentOp::StartBooking(booking) if self.im_leader() => booking.clone(),
+                _ => continue,
+            };
+
+            log::info!(
+                "[SCHEDULE] Starting the search for booking {} of passenger {}",
+                booking.booking_id,
+                booking.passenger_id
+            );
+
+            ctx.notify(RecordTripTerms {
+                passenger_id: booking.passenger_id,
+                terms: TripTerms {
+                    surge: booking.quote.surge,
+                    stops: booking.stops,
+                },
+            });
+            ctx.notify(RedirectNewTrip {
+                passenger_id: booking.passenger_id,
+                source: booking.source,
+                destination: booking.destination,
+            });
+        }
+    }
+
+    /// Guarda la respuesta pendiente a un pasajero que reservo o cancelo un viaje en este driver,
+    /// descartando las de los pasajeros que ya dejaron de esperarla.
+    pub(super) fn await_booking(&mut self, booking_id: u64, reply: oneshot::Sender<bool>) {
+        self.booking_replies.retain(|_, reply| !reply.is_closed());
+        self.booking_replies.insert(booking_id, reply);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(u64, oneshot::Receiver<bool>), String>")]
+pub struct ScheduleTrip {
+    /// Viaje reservado
+    pub booking: Booking,
+}
+
+impl Handler<ScheduleTrip> for CentralDriver {
+    type Result = Result<(u64, oneshot::Receiver<bool>), String>;
+
+    /// Registra un viaje reservado para mas adelante en el log de asignaciones. Devuelve el momento en el
+    /// que se va a lanzar su busqueda, en milisegundos desde UNIX_EPOCH, y un canal por el que se avisa
+    /// cuando la reserva se confirma en el log.
+    /// - Si este driver no es el lider, se lo reenvia. Si no se conoce al lider, devuelve un error.
+    /// - Si es el lider, agrega la reserva al log. La busqueda se lanza cuando llega su hora
+    ///   (ver `start_due_bookings`).
+    fn handle(&mut self, msg: ScheduleTrip, ctx: &mut Context<Self>) -> Self::Result {
+        let search_at = msg
+            .booking
+            .pickup_at
+            .saturating_sub(self.schedule_lead_time.as_millis() as u64);
+        let (reply_tx, reply_rx) = oneshot::channel();
+
+        if !self.im_leader() {
+            match self.leader_id.filter(|lid| self.is_reachable(*lid)) {
+                Some(lid) => {
+                    self.await_booking(msg.booking.booking_id, reply_tx);
+                    self.send_to_driver(
+                        lid,
+                        &DriverMessages::ScheduleTrip {
+                            booking: msg.booking,
+                        },
+                    );
+                }
+                None => {
+                    log::warn!(
+                        "[SCHEDULE] There is no leader to store the booking of passenger {}",
+                        msg.booking.passenger_id
+                    );
+                    return Err("There is no leader to store the booking".into());
+                }
+            }
+
+            return Ok((search_at, reply_rx));
+        }
+
+        log::info!(
+            "[SCHEDULE] Passenger {} booked trip {} for pickup at {}",
+            msg.booking.passenger_id,
+            msg.booking.booking_id,
+            msg.booking.pickup_at
+        );
+
+        self.await_booking(msg.booking.booking_id, reply_tx);
+        self.append_assignment_op(AssignmentOp::Book(msg.booking), ctx);
+
+        Ok((search_at, reply_rx))
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<oneshot::Receiver<bool>>")]
+pub struct CancelBooking {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id de la reserva
+    pub booking_id: u64,
+}
+
+impl Handler<CancelBooking> for CentralDriver {
+    type Result = Option<oneshot::Receiver<bool>>;
+
+    /// Cancela la reserva de un pasajero. Si la reserva no esta confirmada en el log de asignaciones,
+    /// devuelve None. Si no, devuelve un canal por el que se avisa si la cancelacion se confirmo, o si en
+    /// cambio se confirmo antes el comienzo de la busqueda de la reserva.
+    /// - Si este driver no es el lider, le reenvia la cancelacion.
+    /// - Si es el lider y la reserva sigue pendiente, registra la cancelacion en el log.
+    fn handle(&mut self, msg: CancelBooking, ctx: &mut Context<Self>) -> Self::Result {
+        let booked = self
+            .assignment_log
+            .committed_bookings()
+            .get(&msg.booking_id)
+            .is_some_and(|booking| booking.passenger_id == msg.passenger_id);
+
+        if !booked {
+            return None;
+        }
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+
+        if !self.im_leader() {
+            if let Some(lid) = self.leader_id {
+                self.await_booking(msg.booking_id, reply_tx);
+                self.send_to_driver(
+                    lid,
+                    &DriverMessages::CancelBooking {
+                        passenger_id: msg.passenger_id,
+                        booking_id: msg.booking_id,
+                    },
+                );
+            }
+
+            return Some(reply_rx);
+        }
+
+        self.await_booking(msg.booking_id, reply_tx);
+
+        if self.assignment_log.bookings().contains_key(&msg.booking_id) {
+            log::info!(
+                "[SCHEDULE] Passenger {} cancelled booking {}",
+                msg.passenger_id,
+                msg.booking_id
+            );
+
+            self.append_assignment_op(
+                AssignmentOp::CancelBooking {
+                    booking_id: msg.booking_id,
+                },
+                ctx,
+            );
+        }
+
+        Some(reply_rx)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<Booking>")]
+pub struct ListBookings {
+    /// Id del pasajero
+    pub passenger_id: u32,
+}
+
+impl Handler<ListBookings> for CentralDriver {
+    type Result = MessageResult<ListBookings>;
+
+    /// Devuelve las reservas pendientes de un pasajero confirmadas en el log de asignaciones,
+    /// ordenadas por hora.
+    fn handle(&mut self, msg: ListBookings, _ctx: &mut Context<Self>) -> Self::Result {
+        let mut bookings = self
+            .assignment_log
+            .committed_bookings()
+            .values()
+            .filter(|booking| booking.passenger_id == msg.passenger_id)
+            .cloned()
+            .collect::<Vec<Booking>>();
+
+        bookings.sort_by_key(|booking| booking.pickup_at);
+
+        MessageResult(bookings)
+    }
+}
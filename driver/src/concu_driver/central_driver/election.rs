@@ -0,0 +1,493 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use actix::{AsyncContext, Context, Handler, Message};
+use common::utils::position::Position;
+use rand::Rng;
+
+use crate::concu_driver::{
+    assignment_log::AssignmentOp,
+    consts::ELECTION_TIMEOUT_DURATION,
+    driver_finder::StopSearch,
+    handle_trip::ForceNotifyPosition,
+    json_parser::{DriverMessages, PendingTrip},
+};
+
+use super::{CentralDriver, FindDriver};
+
+impl CentralDriver {
+    /// Resuelve el conflicto entre este driver y otro que tambien se cree lider, por ejemplo
+    /// luego de que se cure una particion de la red.
+    /// Ambos aplican la misma regla: sobrevive el lider con mayor (termino, id).
+    /// - Si este driver pierde, deja de ser lider, frena sus busquedas y le envia su estado de
+    ///   despacho al sobreviviente para que lo combine con el suyo.
+    /// - Si este driver sobrevive, le envia su mensaje de coordinador al otro para que haga lo mismo.
+    pub(super) fn resolve_split_brain(
+        &mut self,
+        rival_id: u32,
+        rival_term: u64,
+        ctx: &mut Context<Self>,
+    ) {
+        log::warn!(
+            "[SPLIT-BRAIN] Driver {} also claims to be the leader (its term {}, mine {})",
+            rival_id,
+            rival_term,
+            self.current_term
+        );
+
+        if (self.current_term, self.id) > (rival_term, rival_id) {
+            log::warn!(
+                "[SPLIT-BRAIN] I keep the leadership, asking {} to step down",
+                rival_id
+            );
+
+            self.send_to_driver(
+                rival_id,
+                &DriverMessages::Coordinator {
+                    leader_id: self.id,
+                    term: self.current_term,
+                },
+            );
+            return;
+        }
+
+        log::warn!(
+            "[SPLIT-BRAIN] Stepping down, merging {} drivers and {} open searches into {}",
+            self.driver_positions.len(),
+            self.pending_trips.len(),
+            rival_id
+        );
+
+        for (_, finder) in self.driver_finders.drain() {
+            finder.do_send(StopSearch {});
+        }
+
+        self.waiting_trips.clear();
+
+        self.send_to_driver(
+            rival_id,
+            &DriverMessages::MergeDispatchState {
+                term: self.current_term,
+                leader_id: self.id,
+                driver_positions: self.driver_positions.clone(),
+                pending_trips: self.pending_trips.values().copied().collect(),
+            },
+        );
+
+        self.current_term = rival_term;
+        self.votes_received.clear();
+        self.zone_coordinators.clear();
+        self.leader_id = Some(rival_id);
+        self.voted_for = Some(rival_id);
+
+        if let Some(timeout) = self.election_timeout.take() {
+            ctx.cancel_future(timeout);
+        }
+    }
+
+    /// Si el termino recibido es mayor al actual lo adopta, olvidando el voto y el lider
+    /// del termino anterior. Si este driver era lider o candidato, vuelve a ser seguidor.
+    /// Frena las busquedas que llevaba, ya que estan replicadas y las retoma el lider del nuevo termino.
+    pub(super) fn update_term(&mut self, term: u64, ctx: &mut Context<Self>) {
+        if term <= self.current_term {
+            return;
+        }
+
+        if self.im_leader() {
+            log::warn!("[ELECTION] Stepping down, term {} is newer than mine", term);
+        }
+
+        for (_, finder) in self.driver_finders.drain() {
+            finder.do_send(StopSearch {});
+        }
+
+        self.current_term = term;
+        self.voted_for = None;
+        self.votes_received.clear();
+        self.zone_coordinators.clear();
+        self.waiting_trips.clear();
+        self.batched_trips.clear();
+        self.zone_searches.clear();
+        self.leader_id = None;
+        self.reset_election_timeout(ctx);
+    }
+
+    /// Reinicia el timeout de eleccion con una duracion aleatoria entre
+    /// ELECTION_TIMEOUT_DURATION y el doble, para evitar que varios drivers se postulen a la vez.
+    /// Si al vencer no se conoce un lider, se inicia una nueva eleccion.
+    pub(super) fn reset_election_timeout(&mut self, ctx: &mut Context<Self>) {
+        if let Some(timeout) = self.election_timeout.take() {
+            ctx.cancel_future(timeout);
+        }
+
+        let jitter = rand::thread_rng().gen_range(0..=ELECTION_TIMEOUT_DURATION.as_millis() as u64);
+        let duration = ELECTION_TIMEOUT_DURATION + Duration::from_millis(jitter);
+
+        self.election_timeout = Some(ctx.run_later(duration, |this, ctx| {
+            this.election_timeout = None;
+
+            if this.leader_id.is_none() {
+                log::warn!("[ELECTION] No leader for term {}", this.current_term);
+                ctx.notify(StartElection {});
+            }
+        }));
+    }
+
+    /// Si este driver es candidato en el termino actual y junto la mayoria de los votos,
+    /// se declara lider y se lo notifica a todos los conductores.
+    pub(super) fn check_votes(&mut self, ctx: &mut Context<Self>) {
+        if self.leader_id.is_some()
+            || self.voted_for != Some(self.id)
+            || self.votes_received.len() < self.quorum()
+        {
+            return;
+        }
+
+        log::info!(
+            "[ELECTION] I won the election for term {} with {} votes",
+            self.current_term,
+            self.votes_received.len()
+        );
+
+        self.broadcast(&DriverMessages::Coordinator {
+            leader_id: self.id,
+            term: self.current_term,
+        });
+
+        ctx.notify(Coordinator {
+            leader_id: self.id,
+            term: self.current_term,
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StartElection {}
+
+impl Handler<StartElection> for CentralDriver {
+    type Result = ();
+
+    /// Maneja los mensajes de inicio de eleccion.
+    /// - Si este driver ya es el lider o se esta apagando, no hace nada.
+    /// - Incrementa el termino actual, se vota a si mismo y envia el mensaje RequestVote a todos los drivers.
+    /// - Si ya tiene la mayoria de los votos (por ejemplo, si es el unico driver) se declara lider.
+    /// - Setea un timeout para la eleccion, si vence sin conocer un lider se inicia una nueva eleccion
+    ///   con un termino mayor.
+    fn handle(&mut self, _msg: StartElection, ctx: &mut Context<Self>) -> Self::Result {
+        if self.im_leader() || self.draining {
+            return;
+        }
+
+        self.current_term += 1;
+        self.leader_id = None;
+        self.voted_for = Some(self.id);
+        self.votes_received = HashSet::from([self.id]);
+
+        log::info!(
+            "[ELECTION] Starting election for term {}",
+            self.current_term
+        );
+
+        self.broadcast(&DriverMessages::RequestVote {
+            term: self.current_term,
+            candidate_id: self.id,
+            last_log_index: self.assignment_log.last_index(),
+            last_log_term: self.assignment_log.last_term(),
+        });
+
+        self.reset_election_timeout(ctx);
+        self.check_votes(ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct TimeoutNow {
+    /// Termino del lider que se esta apagando
+    pub term: u64,
+    /// Id del lider que se esta apagando
+    pub leader_id: u32,
+}
+
+impl Handler<TimeoutNow> for CentralDriver {
+    type Result = ();
+
+    /// Maneja el pedido del lider que se esta apagando para que este driver lo suceda.
+    /// - Si el pedido no es del lider del termino actual, lo descarta.
+    /// - Si no, inicia una eleccion sin esperar a que venza el timeout.
+    fn handle(&mut self, msg: TimeoutNow, ctx: &mut Context<Self>) -> Self::Result {
+        if msg.term != self.current_term || self.leader_id != Some(msg.leader_id) {
+            log::debug!(
+                "[ELECTION] Ignoring stale leadership handoff from driver {} for term {}",
+                msg.leader_id,
+                msg.term
+            );
+            return;
+        }
+
+        log::info!(
+            "[ELECTION] Driver {} is handing the leadership over to me, starting an election",
+            msg.leader_id
+        );
+
+        ctx.notify(StartElection {});
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RequestVote {
+    /// Termino de la eleccion del candidato
+    pub term: u64,
+    /// Id del driver candidato
+    pub candidate_id: u32,
+    /// Indice de la ultima entrada del log de asignaciones del candidato
+    pub last_log_index: u64,
+    /// Termino de la ultima entrada del log de asignaciones del candidato
+    pub last_log_term: u64,
+}
+
+impl Handler<RequestVote> for CentralDriver {
+    type Result = ();
+
+    /// Maneja los pedidos de voto de un candidato.
+    /// - Si el termino del candidato es menor al actual, rechaza el pedido.
+    /// - Si el termino es mayor al actual, lo adopta.
+    /// - Otorga el voto solo si en este termino no voto a otro driver (un voto por termino) y si el log
+    ///   de asignaciones del candidato esta al menos tan actualizado como el propio, asi el nuevo lider
+    ///   tiene todas las asignaciones confirmadas.
+    ///
+    /// En todos los casos responde con el mensaje Vote y el termino actual, para que un candidato
+    /// atrasado se entere del termino vigente.
+    fn handle(&mut self, msg: RequestVote, ctx: &mut Context<Self>) -> Self::Result {
+        log::debug!(
+            "[ELECTION] Driver {} asked for my vote in term {}",
+            msg.candidate_id,
+            msg.term
+        );
+
+        self.update_term(msg.term, ctx);
+
+        let granted = msg.term == self.current_term
+            && (self.voted_for.is_none() || self.voted_for == Some(msg.candidate_id))
+            && self
+                .assignment_log
+                .is_up_to_date(msg.last_log_term, msg.last_log_index);
+
+        if granted {
+            self.voted_for = Some(msg.candidate_id);
+            self.reset_election_timeout(ctx);
+        }
+
+        self.send_to_driver(
+            msg.candidate_id,
+            &DriverMessages::Vote {
+                term: self.current_term,
+                voter_id: self.id,
+                granted,
+            },
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Vote {
+    /// Termino del votante
+    pub term: u64,
+    /// Id del driver que vota
+    pub voter_id: u32,
+    /// Si el voto fue otorgado
+    pub granted: bool,
+}
+
+impl Handler<Vote> for CentralDriver {
+    type Result = ();
+
+    /// Maneja las respuestas a un pedido de voto.
+    /// - Si el termino del votante es mayor al actual, lo adopta y deja de ser candidato.
+    /// - Ignora los votos de terminos anteriores o rechazados.
+    /// - Cuenta el voto y, si alcanza la mayoria, se declara lider.
+    fn handle(&mut self, msg: Vote, ctx: &mut Context<Self>) -> Self::Result {
+        log::debug!(
+            "[ELECTION] Driver {} answered my vote request for term {}: {}",
+            msg.voter_id,
+            msg.term,
+            msg.granted
+        );
+
+        self.update_term(msg.term, ctx);
+
+        if msg.term != self.current_term || !msg.granted {
+            return;
+        }
+
+        self.votes_received.insert(msg.voter_id);
+        self.check_votes(ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Coordinator {
+    /// Id del nuevo lider
+    pub leader_id: u32,
+    /// Termino en el que fue elegido
+    pub term: u64,
+}
+
+impl Handler<Coordinator> for CentralDriver {
+    type Result = ();
+
+    /// Maneja los mensajes de coordinador.
+    /// - Si este driver tambien es lider, hay dos lideres a la vez y se resuelve el conflicto.
+    /// - Si el termino es menor al actual, el mensaje es de una eleccion vieja y se descarta.
+    /// - Setea el id del lider con el id del driver que envia el mensaje y cancela el timeout de la eleccion.
+    /// - Si el driver es el lider, loggea un mensaje de que el driver es el lider, reparte las zonas,
+    ///   reinicia el progreso de replicacion de los seguidores, agrega una entrada vacia al log de
+    ///   asignaciones para reconstruir las asignaciones activas y retoma las busquedas abiertas
+    ///   replicadas por el lider anterior.
+    /// - Reenvia al lider los pedidos de viaje guardados mientras no se lo conocia.
+    /// - Envía un mensaje al actor `TripHandler` para que notifique la posicion del driver.
+    fn handle(&mut self, msg: Coordinator, ctx: &mut Context<Self>) -> Self::Result {
+        if self.im_leader() && msg.leader_id != self.id {
+            self.resolve_split_brain(msg.leader_id, msg.term, ctx);
+            return;
+        }
+
+        if msg.term < self.current_term {
+            log::debug!(
+                "[ELECTION] Ignoring coordinator {} from stale term {}",
+                msg.leader_id,
+                msg.term
+            );
+            return;
+        }
+
+        self.update_term(msg.term, ctx);
+
+        log::info!(
+            "[ELECTION] {} is the new leader for term {}",
+            msg.leader_id,
+            msg.term
+        );
+
+        self.leader_id = Some(msg.leader_id);
+        // Reconocer al lider cuenta como el voto de este termino, asi no se elige a otro en el mismo
+        self.voted_for = Some(msg.leader_id);
+
+        if let Some(timeout) = self.election_timeout.take() {
+            ctx.cancel_future(timeout);
+        }
+
+        if self.im_leader() {
+            log::info!("[ELECTION] Oh!, that is me");
+            self.assign_zones();
+            self.assignment_log
+                .become_leader(self.connection_with_drivers.keys().copied());
+            self.append_assignment_op(AssignmentOp::Noop, ctx);
+            self.resume_pending_trips(ctx);
+        }
+
+        self.replay_buffered_trips(ctx);
+
+        let _ = self
+            .trip_handler
+            .try_send(ForceNotifyPosition {})
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct MergeDispatchState {
+    /// Termino del lider que deja de serlo
+    pub term: u64,
+    /// Id del lider que deja de serlo
+    pub leader_id: u32,
+    /// Posiciones de los drivers segun su id
+    pub driver_positions: HashMap<u32, Position>,
+    /// Busquedas abiertas por el lider que deja de serlo
+    pub pending_trips: Vec<PendingTrip>,
+}
+
+impl Handler<MergeDispatchState> for CentralDriver {
+    type Result = ();
+
+    /// Combina el estado de despacho de un lider que perdio un conflicto de liderazgo con el propio.
+    /// - Si este driver ya no es lider, reenvia el estado al lider actual.
+    /// - Agrega las posiciones de los drivers que no conocia y retoma las busquedas que no tenia abiertas.
+    /// - Aumenta el termino y se vuelve a anunciar como lider, junto con su estado de despacho,
+    ///   para que todos los drivers de ambos lados de la particion lo reconozcan. Vuelve a repartir
+    ///   las zonas y replica su log de asignaciones, que pisa las entradas no confirmadas del otro lider.
+    fn handle(&mut self, msg: MergeDispatchState, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.im_leader() {
+            if let Some(lid) = self.leader_id {
+                self.send_to_driver(
+                    lid,
+                    &DriverMessages::MergeDispatchState {
+                        term: msg.term,
+                        leader_id: msg.leader_id,
+                        driver_positions: msg.driver_positions,
+                        pending_trips: msg.pending_trips,
+                    },
+                );
+            }
+
+            return;
+        }
+
+        log::warn!(
+            "[SPLIT-BRAIN] Merging {} drivers and {} open searches from former leader {}",
+            msg.driver_positions.len(),
+            msg.pending_trips.len(),
+            msg.leader_id
+        );
+
+        for (driver_id, position) in msg.driver_positions {
+            self.driver_positions.entry(driver_id).or_insert(position);
+        }
+
+        for trip in msg.pending_trips {
+            if self.pending_trips.contains_key(&trip.passenger_id)
+                || self.assignment_log.passenger_assigned(trip.passenger_id)
+            {
+                continue;
+            }
+
+            ctx.notify(FindDriver {
+                passenger_id: trip.passenger_id,
+                source: trip.source,
+                destination: trip.destination,
+                handoff_zones: None,
+            });
+        }
+
+        self.current_term = self.current_term.max(msg.term) + 1;
+        self.voted_for = Some(self.id);
+        self.votes_received.clear();
+
+        log::warn!(
+            "[SPLIT-BRAIN] Announcing myself as the leader for term {}",
+            self.current_term
+        );
+
+        self.broadcast(&DriverMessages::Coordinator {
+            leader_id: self.id,
+            term: self.current_term,
+        });
+
+        for driver_id in self.connection_with_drivers.keys() {
+            self.send_dispatch_snapshot(*driver_id);
+        }
+
+        self.zone_coordinators.clear();
+        self.assign_zones();
+        self.assignment_log
+            .become_leader(self.connection_with_drivers.keys().copied());
+        self.append_assignment_op(AssignmentOp::Noop, ctx);
+    }
+}
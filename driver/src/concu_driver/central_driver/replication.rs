@@ -0,0 +1,521 @@
+use std::collections::{HashMap, HashSet};
+
+use actix::{AsyncContext, Context, Handler, Message};
+use common::utils::position::Position;
+
+use crate::concu_driver::{
+    assignment_log::{AssignmentOp, LogEntry, LogSnapshot},
+    handle_trip::RevokeStaleTrips,
+    json_parser::{DriverMessages, PendingTrip, TripTerms},
+    pool::PoolRoute,
+};
+
+use super::{CentralDriver, ConfirmTrip, FinishAssignment, RevokeOffer};
+
+impl CentralDriver {
+    /// Verifica si un mensaje de replicacion es vigente: descarta los de terminos anteriores y
+    /// adopta el termino si es mayor al actual.
+    /// Si este driver tambien es lider, hay dos lideres a la vez y se resuelve el conflicto.
+    /// Si no conocia al lider del termino, lo reconoce a partir de su replicacion.
+    pub(super) fn is_current_replication(
+        &mut self,
+        term: u64,
+        leader_id: u32,
+        ctx: &mut Context<Self>,
+    ) -> bool {
+        if self.im_leader() && leader_id != self.id {
+            self.resolve_split_brain(leader_id, term, ctx);
+            return false;
+        }
+
+        if term < self.current_term {
+            log::debug!(
+                "[REPLICATION] Ignoring replication from stale term {}",
+                term
+            );
+            return false;
+        }
+
+        self.update_term(term, ctx);
+
+        if self.leader_id.is_none() {
+            self.leader_id = Some(leader_id);
+            self.voted_for = Some(leader_id);
+
+            if let Some(timeout) = self.election_timeout.take() {
+                ctx.cancel_future(timeout);
+            }
+        }
+
+        !self.im_leader()
+    }
+
+    /// Le envia a un seguidor las entradas del log de asignaciones que todavia no tiene.
+    pub(super) fn send_log_entries(&self, driver_id: u32) {
+        let request = self.assignment_log.append_request(driver_id);
+
+        self.send_to_driver(
+            driver_id,
+            &DriverMessages::AppendEntries {
+                term: self.current_term,
+                snapshot: request.snapshot,
+                prev_index: request.prev_index,
+                prev_term: request.prev_term,
+                entries: request.entries,
+                leader_commit: self.assignment_log.commit_index(),
+            },
+        );
+    }
+
+    /// Si este driver es el lider, replica el log de asignaciones en todos los drivers conectados.
+    /// Ademas de al agregar entradas, se llama en cada heartbeat: asi, si se pierde un pedido de
+    /// replicacion o su respuesta, se le vuelven a enviar al seguidor las entradas que le faltan y el
+    /// indice de confirmacion, aunque no se agreguen entradas nuevas.
+    pub(super) fn replicate_log(&self) {
+        if !self.im_leader() {
+            return;
+        }
+
+        for driver_id in self.connection_with_drivers.keys() {
+            self.send_log_entries(*driver_id);
+        }
+    }
+
+    /// Agrega una operacion al log de asignaciones (solo el lider), la replica e intenta confirmarla.
+    pub(super) fn append_assignment_op(&mut self, op: AssignmentOp, ctx: &mut Context<Self>) {
+        self.assignment_log.append(self.current_term, op);
+        self.replicate_log();
+        self.commit_assignments(ctx);
+    }
+
+    /// Confirma las entradas del log replicadas en la mayoria de los drivers y aplica las recien confirmadas:
+    /// - Por cada asignacion, cierra la busqueda del pasajero y le confirma el viaje al driver, que recien
+    ///   ahi va a buscar al pasajero. Si el pasajero cancelo el viaje mientras se replicaba la asignacion,
+    ///   en lugar de confirmarla la termina y libera al driver.
+    /// - Al confirmar la entrada vacia de su termino, el lider vuelve a confirmar todas las asignaciones
+    ///   activas, por si el lider anterior se cayo antes de avisarle a los drivers.
+    /// - Aplica las reservas confirmadas (ver `apply_committed_bookings`).
+    ///
+    /// Si se confirmaron entradas, se lo hace saber a los seguidores.
+    pub(super) fn commit_assignments(&mut self, ctx: &mut Context<Self>) {
+        if !self.im_leader() {
+            return;
+        }
+
+        let committed = self
+            .assignment_log
+            .advance_commit(self.current_term, self.quorum());
+
+        if committed.is_empty() {
+            return;
+        }
+
+        self.track_fairness(&committed);
+        self.forget_trip_terms(&committed);
+        self.revoke_released_trips(&committed);
+        self.apply_committed_bookings(&committed, ctx);
+
+        for entry in committed {
+            match entry.op {
+                AssignmentOp::Assign(assignment) => {
+                    log::info!(
+                        "[ASSIGNMENT] Committed driver {} for passenger {}",
+                        assignment.driver_id,
+                        assignment.passenger_id
+                    );
+
+                    self.excluded_drivers.remove(&assignment.passenger_id);
+                    self.close_search(assignment.passenger_id);
+
+                    if self.cancelled_trips.contains(&assignment.passenger_id) {
+                        ctx.notify(RevokeOffer {
+                            passenger_id: assignment.passenger_id,
+                            driver_id: assignment.driver_id,
+                            offer_id: assignment.offer_id,
+                        });
+                        ctx.notify(FinishAssignment {
+                            passenger_id: assignment.passenger_id,
+                            offer_id: assignment.offer_id,
+                        });
+                        continue;
+                    }
+
+                    ctx.notify(ConfirmTrip::from(assignment));
+                }
+                AssignmentOp::Noop if entry.term == self.current_term => {
+                    for assignment in self.assignment_log.active_assignments().into_values() {
+                        log::info!(
+                            "[ASSIGNMENT] Restoring the trip of passenger {} with driver {}",
+                            assignment.passenger_id,
+                            assignment.driver_id
+                        );
+                        ctx.notify(ConfirmTrip::from(assignment));
+                    }
+                }
+                AssignmentOp::Complete { passenger_id }
+                | AssignmentOp::Release { passenger_id, .. } => {
+                    self.passenger_positions.remove(&passenger_id);
+                }
+                _ => (),
+            }
+        }
+
+        self.assignment_log.compact(self.log_compaction_threshold);
+        self.replicate_log();
+    }
+
+    /// Olvida las condiciones aceptadas de los viajes terminados en las entradas recien confirmadas del
+    /// log de asignaciones.
+    pub(super) fn forget_trip_terms(&mut self, entries: &[LogEntry]) {
+        for entry in entries {
+            if let AssignmentOp::Complete { passenger_id } = entry.op {
+                self.trip_terms.remove(&passenger_id);
+            }
+        }
+    }
+
+    /// Le revoca al `TripHandler` los viajes que el log registra como soltados, por si este driver es el
+    /// que los tenia y siguio con el viaje sin enterarse (por ejemplo, si quedo aislado y se lo dio por
+    /// muerto). La oferta de la asignacion liberada sirve de fence: si no es la del viaje en curso, el
+    /// `TripHandler` no hace nada.
+    pub(super) fn revoke_released_trips(&self, entries: &[LogEntry]) {
+        for entry in entries {
+            if let AssignmentOp::Release {
+                passenger_id,
+                offer_id,
+            } = entry.op
+            {
+                let _ = self
+                    .trip_handler
+                    .try_send(crate::concu_driver::handle_trip::RevokeOffer {
+                        passenger_id,
+                        offer_id,
+                    })
+                    .inspect_err(|e| {
+                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    });
+            }
+        }
+    }
+
+    /// Le indica al `TripHandler` que abandone los viajes que el log ya no le asigna a este driver.
+    /// Se usa al instalar un snapshot, ya que las entradas compactadas no se reproducen una por una.
+    pub(super) fn revoke_stale_trips(&self) {
+        let offer_ids = self
+            .assignment_log
+            .active_assignments()
+            .into_values()
+            .filter(|assignment| assignment.driver_id == self.id)
+            .map(|assignment| assignment.offer_id)
+            .collect::<HashSet<u64>>();
+
+        let _ = self
+            .trip_handler
+            .try_send(RevokeStaleTrips { offer_ids })
+            .inspect_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            });
+    }
+
+    /// Envia al driver con el id dado una copia del estado de despacho del lider: las posiciones
+    /// de los drivers y las busquedas abiertas.
+    pub(super) fn send_dispatch_snapshot(&self, driver_id: u32) {
+        self.send_to_driver(
+            driver_id,
+            &DriverMessages::DispatchSnapshot {
+                term: self.current_term,
+                driver_positions: self.driver_positions.clone(),
+                pending_trips: self.pending_trips.values().copied().collect(),
+            },
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReplicatePosition {
+    /// Termino del lider que replica
+    pub term: u64,
+    /// Id del lider que replica
+    pub leader_id: u32,
+    /// Id del driver
+    pub driver_id: u32,
+    /// Posicion del driver
+    pub driver_position: Position,
+}
+
+impl Handler<ReplicatePosition> for CentralDriver {
+    type Result = ();
+
+    /// Actualiza la copia local de la posicion de un driver enviada por el lider.
+    /// Los coordinadores de zona la ignoran, ya que reciben las posiciones de sus zonas directamente.
+    fn handle(&mut self, msg: ReplicatePosition, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx)
+            || self.zone_coordinators.contains(&self.id)
+        {
+            return;
+        }
+
+        self.driver_positions
+            .insert(msg.driver_id, msg.driver_position);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReplicateTripSearch {
+    /// Termino del lider que replica
+    pub term: u64,
+    /// Id del lider que replica
+    pub leader_id: u32,
+    /// Busqueda abierta por el lider
+    pub trip: PendingTrip,
+}
+
+impl Handler<ReplicateTripSearch> for CentralDriver {
+    type Result = ();
+
+    /// Guarda la copia local de una busqueda abierta por el lider.
+    fn handle(&mut self, msg: ReplicateTripSearch, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            return;
+        }
+
+        log::debug!(
+            "[REPLICATION] Leader is searching a driver for passenger {}",
+            msg.trip.passenger_id
+        );
+
+        self.pending_trips.insert(msg.trip.passenger_id, msg.trip);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReplicateTripSearchDone {
+    /// Termino del lider que replica
+    pub term: u64,
+    /// Id del lider que replica
+    pub leader_id: u32,
+    /// Id del pasajero
+    pub passenger_id: u32,
+}
+
+impl Handler<ReplicateTripSearchDone> for CentralDriver {
+    type Result = ();
+
+    /// Elimina la copia local de una busqueda que el lider dio por terminada.
+    fn handle(&mut self, msg: ReplicateTripSearchDone, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            return;
+        }
+
+        self.pending_trips.remove(&msg.passenger_id);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DispatchSnapshot {
+    /// Termino del lider que replica
+    pub term: u64,
+    /// Id del lider que replica
+    pub leader_id: u32,
+    /// Posiciones de los drivers segun su id
+    pub driver_positions: HashMap<u32, Position>,
+    /// Busquedas abiertas por el lider
+    pub pending_trips: Vec<PendingTrip>,
+}
+
+impl Handler<DispatchSnapshot> for CentralDriver {
+    type Result = ();
+
+    /// Reemplaza la copia local del estado de despacho por la enviada por el lider.
+    fn handle(&mut self, msg: DispatchSnapshot, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            return;
+        }
+
+        log::debug!(
+            "[REPLICATION] Received a snapshot with {} drivers and {} open searches",
+            msg.driver_positions.len(),
+            msg.pending_trips.len()
+        );
+
+        self.driver_positions = msg.driver_positions;
+        self.pending_trips = msg
+            .pending_trips
+            .into_iter()
+            .map(|trip| (trip.passenger_id, trip))
+            .collect();
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AppendEntries {
+    /// Termino del lider
+    pub term: u64,
+    /// Id del lider
+    pub leader_id: u32,
+    /// Snapshot a instalar antes de agregar las entradas, si este driver necesita entradas ya compactadas
+    pub snapshot: Option<LogSnapshot>,
+    /// Indice de la entrada anterior a las enviadas
+    pub prev_index: u64,
+    /// Termino de la entrada anterior a las enviadas
+    pub prev_term: u64,
+    /// Entradas a agregar
+    pub entries: Vec<LogEntry>,
+    /// Indice de la ultima entrada confirmada por el lider
+    pub leader_commit: u64,
+}
+
+impl Handler<AppendEntries> for CentralDriver {
+    type Result = ();
+
+    /// Agrega al log de asignaciones local las entradas enviadas por el lider y le responde hasta
+    /// donde tiene replicado el log. Si el log no coincide con el del lider, le responde que no,
+    /// para que el lider le envie entradas anteriores.
+    fn handle(&mut self, msg: AppendEntries, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            if msg.term < self.current_term {
+                self.send_to_driver(
+                    msg.leader_id,
+                    &DriverMessages::AppendEntriesACK {
+                        term: self.current_term,
+                        driver_id: self.id,
+                        success: false,
+                        match_index: 0,
+                    },
+                );
+            }
+
+            return;
+        }
+
+        let previous_commit = self.assignment_log.commit_index();
+
+        let installs_snapshot = msg.snapshot.is_some();
+
+        let result = self.assignment_log.handle_append(
+            msg.snapshot,
+            msg.prev_index,
+            msg.prev_term,
+            msg.entries,
+            msg.leader_commit,
+        );
+
+        let committed = self.assignment_log.committed_since(previous_commit);
+        self.track_fairness(&committed);
+        self.forget_trip_terms(&committed);
+        self.revoke_released_trips(&committed);
+        self.apply_committed_bookings(&committed, ctx);
+
+        if installs_snapshot && result.is_some() {
+            self.revoke_stale_trips();
+        }
+
+        self.assignment_log.compact(self.log_compaction_threshold);
+
+        self.send_to_driver(
+            msg.leader_id,
+            &DriverMessages::AppendEntriesACK {
+                term: self.current_term,
+                driver_id: self.id,
+                success: result.is_some(),
+                match_index: result.unwrap_or(self.assignment_log.last_index()),
+            },
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AppendEntriesACK {
+    /// Termino del seguidor
+    pub term: u64,
+    /// Id del seguidor
+    pub driver_id: u32,
+    /// Si el seguidor agrego las entradas
+    pub success: bool,
+    /// Indice de la ultima entrada replicada en el seguidor
+    pub match_index: u64,
+}
+
+impl Handler<AppendEntriesACK> for CentralDriver {
+    type Result = ();
+
+    /// Maneja la respuesta de un seguidor a la replicacion del log de asignaciones.
+    /// - Si el termino del seguidor es mayor al actual, lo adopta y deja de ser lider.
+    /// - Si el seguidor agrego las entradas, intenta confirmarlas.
+    /// - Si no, le vuelve a enviar el log desde una entrada anterior.
+    fn handle(&mut self, msg: AppendEntriesACK, ctx: &mut Context<Self>) -> Self::Result {
+        self.update_term(msg.term, ctx);
+
+        if !self.im_leader() || msg.term != self.current_term {
+            return;
+        }
+
+        self.assignment_log
+            .handle_ack(msg.driver_id, msg.success, msg.match_index);
+
+        if !msg.success {
+            self.send_log_entries(msg.driver_id);
+            return;
+        }
+
+        self.commit_assignments(ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReplicateTripTerms {
+    /// Termino del lider que replica
+    pub term: u64,
+    /// Id del lider que replica
+    pub leader_id: u32,
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Condiciones que acepto el pasajero
+    pub terms: TripTerms,
+}
+
+impl Handler<ReplicateTripTerms> for CentralDriver {
+    type Result = ();
+
+    /// Guarda la copia local de las condiciones que acepto un pasajero, enviada por el lider.
+    fn handle(&mut self, msg: ReplicateTripTerms, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            return;
+        }
+
+        self.trip_terms.insert(msg.passenger_id, msg.terms);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReplicatePoolRoute {
+    /// Termino del lider que replica
+    pub term: u64,
+    /// Id del lider que replica
+    pub leader_id: u32,
+    /// Id del driver
+    pub driver_id: u32,
+    /// Ruta del auto del driver, o None si no puede sumar pasajeros
+    pub route: Option<PoolRoute>,
+}
+
+impl Handler<ReplicatePoolRoute> for CentralDriver {
+    type Result = ();
+
+    /// Guarda la copia local de la ruta de un driver, enviada por el lider.
+    fn handle(&mut self, msg: ReplicatePoolRoute, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.is_current_replication(msg.term, msg.leader_id, ctx) {
+            return;
+        }
+
+        self.set_pool_route(msg.driver_id, msg.route);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{str::FromStr, time::Duration};
+
+use common::utils::{pricing::Pricing, seeds::parse_seeds};
+
+use super::consts::{DEFAULT_BASE_FARE, DEFAULT_FARE_PER_BLOCK, DEFAULT_FARE_PER_WAITING_SECOND};
+
+/// Lee la variable de entorno `name` y la parsea.
+/// Si la variable no esta definida o no se puede parsear, devuelve `default`.
+pub fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Lee la variable de entorno `name` como una cantidad de milisegundos.
+/// Si la variable no esta definida o no se puede parsear, devuelve `default`.
+pub fn env_duration_ms(name: &str, default: Duration) -> Duration {
+    Duration::from_millis(env_or(name, default.as_millis() as u64))
+}
+
+/// Lee la cantidad fija de drivers del cluster, sobre la que se calcula la mayoria de las elecciones y
+/// del log de asignaciones. Se configura con CLUSTER_SIZE o, si no esta definida, es la cantidad de
+/// semillas de DRIVER_SEEDS (que entonces deben ser todos los drivers del cluster).
+/// Si no se puede determinar, devuelve None.
+pub fn cluster_size_from_env() -> Option<usize> {
+    cluster_size(
+        std::env::var("CLUSTER_SIZE").ok().as_deref(),
+        std::env::var("DRIVER_SEEDS").ok().as_deref(),
+    )
+}
+
+/// Tamaño del cluster segun los valores de CLUSTER_SIZE y DRIVER_SEEDS (ver `cluster_size_from_env`).
+fn cluster_size(configured: Option<&str>, seeds: Option<&str>) -> Option<usize> {
+    match configured {
+        Some(size) => size.trim().parse().ok(),
+        None => seeds.map(|seeds| parse_seeds(seeds).len()),
+    }
+    .filter(|size| *size > 0)
+}
+
+/// Lee las tarifas de los viajes de las variables de entorno BASE_FARE, FARE_PER_BLOCK y
+/// FARE_PER_WAITING_SECOND, con los valores por defecto de `consts`.
+pub fn pricing_from_env() -> Pricing {
+    Pricing::new(
+        env_or("BASE_FARE", DEFAULT_BASE_FARE),
+        env_or("FARE_PER_BLOCK", DEFAULT_FARE_PER_BLOCK),
+        env_or("FARE_PER_WAITING_SECOND", DEFAULT_FARE_PER_WAITING_SECOND),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_size() {
+        assert_eq!(cluster_size(Some("3"), Some("127.0.0.1:8080")), Some(3));
+        assert_eq!(
+            cluster_size(None, Some("127.0.0.1:8080,127.0.0.2:8080")),
+            Some(2)
+        );
+        assert_eq!(cluster_size(Some("0"), None), None);
+        assert_eq!(cluster_size(Some("tres"), None), None);
+        assert_eq!(cluster_size(None, None), None);
+    }
+}
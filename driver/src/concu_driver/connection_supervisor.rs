@@ -0,0 +1,223 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, SpawnHandle};
+use actix_async_handler::async_handler;
+
+use super::{
+    central_driver::CentralDriver,
+    config::env_duration_ms,
+    connections_handler::DriverConnectionsHandler,
+    consts::{DEFAULT_RECONNECT_INITIAL_BACKOFF, DEFAULT_RECONNECT_MAX_BACKOFF},
+};
+
+pub struct ConnectionSupervisor {
+    /// Direccion del actor CentralDriver
+    central_driver: Addr<CentralDriver>,
+    /// Id de este driver
+    id: u32,
+    /// Direccion en la que escucha este driver
+    self_addr: String,
+    /// Direcciones de los drivers a los que reconectarse, segun su id
+    addrs: HashMap<u32, String>,
+    /// Espera actual antes del proximo intento de reconexion, segun el id del driver
+    backoffs: HashMap<u32, Duration>,
+    /// Intentos de reconexion programados, segun el id del driver
+    scheduled: HashMap<u32, SpawnHandle>,
+    /// Espera antes del primer intento de reconexion
+    initial_backoff: Duration,
+    /// Espera maxima entre intentos de reconexion
+    max_backoff: Duration,
+}
+
+impl Actor for ConnectionSupervisor {
+    type Context = Context<Self>;
+}
+
+impl ConnectionSupervisor {
+    /// Crea un nuevo supervisor de conexiones con:
+    /// - La dirección del actor `CentralDriver`
+    /// - El ID del driver.
+    /// - La direccion en la que escucha el driver.
+    ///
+    /// Las esperas entre reintentos se configuran con las variables de entorno
+    /// RECONNECT_INITIAL_BACKOFF_MS y RECONNECT_MAX_BACKOFF_MS.
+    pub fn new(central_driver: Addr<CentralDriver>, id: u32, self_addr: String) -> Self {
+        Self {
+            central_driver,
+            id,
+            self_addr,
+            addrs: HashMap::new(),
+            backoffs: HashMap::new(),
+            scheduled: HashMap::new(),
+            initial_backoff: env_duration_ms(
+                "RECONNECT_INITIAL_BACKOFF_MS",
+                DEFAULT_RECONNECT_INITIAL_BACKOFF,
+            ),
+            max_backoff: env_duration_ms("RECONNECT_MAX_BACKOFF_MS", DEFAULT_RECONNECT_MAX_BACKOFF),
+        }
+    }
+
+    /// Programa un intento de conexion con el driver, si no estaba programado.
+    /// Solo el driver de menor id de cada par se conecta, asi ambos no se conectan a la vez.
+    fn watch(&mut self, driver_id: u32, addr: String, ctx: &mut Context<Self>) {
+        if driver_id < self.id || addr.is_empty() {
+            return;
+        }
+
+        self.addrs.insert(driver_id, addr);
+
+        if !self.scheduled.contains_key(&driver_id) {
+            self.schedule_reconnect(driver_id, ctx);
+        }
+    }
+
+    /// Programa un intento de reconexion con el driver luego de la espera actual.
+    fn schedule_reconnect(&mut self, driver_id: u32, ctx: &mut Context<Self>) {
+        let backoff = *self
+            .backoffs
+            .entry(driver_id)
+            .or_insert(self.initial_backoff);
+
+        let handle = ctx.notify_later(Reconnect { driver_id }, backoff);
+        self.scheduled.insert(driver_id, handle);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PeerLost {
+    /// Id del driver
+    pub driver_id: u32,
+    /// Direccion en la que escucha el driver
+    pub addr: String,
+}
+
+impl Handler<PeerLost> for ConnectionSupervisor {
+    type Result = ();
+
+    /// Maneja la perdida de la conexion con un driver, programando su reconexion.
+    fn handle(&mut self, msg: PeerLost, ctx: &mut Context<Self>) -> Self::Result {
+        log::info!("Will try to reconnect with driver {}", msg.driver_id);
+
+        self.watch(msg.driver_id, msg.addr, ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PeerDiscovered {
+    /// Id del driver
+    pub driver_id: u32,
+    /// Direccion en la que escucha el driver
+    pub addr: String,
+}
+
+impl Handler<PeerDiscovered> for ConnectionSupervisor {
+    type Result = ();
+
+    /// Maneja el descubrimiento por gossip de un driver con el que no hay conexion, programando la conexion.
+    fn handle(&mut self, msg: PeerDiscovered, ctx: &mut Context<Self>) -> Self::Result {
+        log::debug!("Discovered driver {} at {}", msg.driver_id, msg.addr);
+
+        self.watch(msg.driver_id, msg.addr, ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PeerDead {
+    /// Id del driver
+    pub driver_id: u32,
+}
+
+impl Handler<PeerDead> for ConnectionSupervisor {
+    type Result = ();
+
+    /// Deja de intentar conectarse con un driver confirmado como muerto.
+    fn handle(&mut self, msg: PeerDead, ctx: &mut Context<Self>) -> Self::Result {
+        self.addrs.remove(&msg.driver_id);
+        self.backoffs.remove(&msg.driver_id);
+
+        if let Some(handle) = self.scheduled.remove(&msg.driver_id) {
+            log::info!("Giving up reconnecting with driver {}", msg.driver_id);
+            ctx.cancel_future(handle);
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PeerConnected {
+    /// Id del driver
+    pub driver_id: u32,
+}
+
+impl Handler<PeerConnected> for ConnectionSupervisor {
+    type Result = ();
+
+    /// Cancela los reintentos de reconexion con el driver y reinicia su espera.
+    fn handle(&mut self, msg: PeerConnected, ctx: &mut Context<Self>) -> Self::Result {
+        self.backoffs.remove(&msg.driver_id);
+
+        if let Some(handle) = self.scheduled.remove(&msg.driver_id) {
+            ctx.cancel_future(handle);
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Reconnect {
+    /// Id del driver
+    driver_id: u32,
+}
+
+#[async_handler]
+impl Handler<Reconnect> for ConnectionSupervisor {
+    type Result = ();
+
+    /// Intenta reconectarse con el driver.
+    /// Si falla, duplica la espera (hasta la espera maxima) y programa un nuevo intento.
+    /// Si el intento fue cancelado o no se conoce la direccion del driver, no hace nada.
+    async fn handle(&mut self, msg: Reconnect, _ctx: &mut Context<Self>) -> Self::Result {
+        let addr = match self.scheduled.remove(&msg.driver_id) {
+            Some(_) => self.addrs.get(&msg.driver_id).cloned(),
+            None => None,
+        };
+
+        let self_id = self.id;
+        let self_addr = self.self_addr.clone();
+        let cd_addr = self.central_driver.clone();
+
+        let res = async move {
+            match addr {
+                Some(addr) => Some(
+                    DriverConnectionsHandler::dial_driver(self_id, &self_addr, &addr, &cd_addr)
+                        .await,
+                ),
+                None => None,
+            }
+        }
+        .await;
+
+        if let Some(Err(e)) = res {
+            let backoff = self
+                .backoffs
+                .get(&msg.driver_id)
+                .copied()
+                .unwrap_or(self.initial_backoff);
+
+            let next_backoff = (backoff * 2).min(self.max_backoff);
+
+            log::debug!(
+                "Could not reconnect with driver {}, retrying in {:?}: {}",
+                msg.driver_id,
+                next_backoff,
+                e
+            );
+
+            self.backoffs.insert(msg.driver_id, next_backoff);
+            self.schedule_reconnect(msg.driver_id, _ctx);
+        }
+    }
+}
@@ -2,8 +2,9 @@ use std::time::Duration;
 
 use actix::{Actor, Addr, AsyncContext};
 use common::utils::{
-    consts::{HOST, MAX_DRIVER_PORT, MIN_DRIVER_PORT},
-    json_parser::TripMessages,
+    json_parser::{Booking, TripMessages, TripStatus},
+    position::Position,
+    pricing::FareQuote,
 };
 use tokio::{
     io::{split, AsyncBufReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf},
@@ -13,10 +14,17 @@ use tokio::{
 };
 use tokio_stream::wrappers::LinesStream;
 
-use crate::concu_driver::{central_driver::StartElection, json_parser::CommonMessages};
+use crate::concu_driver::{
+    central_driver::StartElection,
+    json_parser::{CommonMessages, TripTerms},
+};
 
 use super::{
-    central_driver::{CentralDriver, InsertDriverConnection, RedirectNewTrip},
+    central_driver::{
+        CancelBooking, CancelTrip, CentralDriver, InsertDriverConnection, ListBookings, QuoteTrip,
+        RecordTripTerms, RedirectNewTrip, ScheduleTrip,
+    },
+    consts::{BOOKING_COMMIT_TIMEOUT, IDENTIFICATION_TIMEOUT, QUOTE_ANSWER_TIMEOUT},
     driver_connection::DriverConnection,
 };
 
@@ -24,64 +32,113 @@ pub struct DriverConnectionsHandler;
 
 impl DriverConnectionsHandler {
     /// Corre el setUp del driver a la hora de crearse
+    /// - self_addr: direccion en la que escucha el driver
+    /// - seeds: direcciones de los drivers a traves de los cuales unirse al cluster
     pub fn run(
         id: u32,
+        self_addr: String,
+        seeds: Vec<String>,
         central_driver_addr: Addr<CentralDriver>,
     ) -> JoinHandle<Result<(), String>> {
-        actix::spawn(async move { Self::setup(&central_driver_addr, id).await })
+        actix::spawn(async move { Self::setup(&central_driver_addr, id, self_addr, seeds).await })
     }
 
-    /// Conecta a todos los drivers
-    ///
-    /// Mientras el driver_id sea menor o igual al maximo de drivers, se conecta a cada uno
+    /// Se une al cluster conectandose a cada una de las semillas.
+    /// El resto de los drivers se conocen luego por gossip.
     async fn connect_all_drivers(
         self_id: u32,
+        self_addr: &str,
+        seeds: &[String],
         central_driver_addr: &Addr<CentralDriver>,
     ) -> Result<(), String> {
-        let mut driver_id = 0;
+        for seed in seeds {
+            if seed == self_addr {
+                continue;
+            }
 
-        let max_id = MAX_DRIVER_PORT - MIN_DRIVER_PORT;
+            let _ = Self::dial_driver(self_id, self_addr, seed, central_driver_addr).await;
+        }
 
-        while driver_id <= max_id {
-            let addr = format!("{}:{}", HOST, MIN_DRIVER_PORT + driver_id);
+        Ok(())
+    }
 
-            if let Ok(mut socket) = TcpStream::connect(addr.clone()).await {
-                let request = serde_json::to_string(&CommonMessages::Identification {
-                    id: self_id,
-                    type_: 'D',
-                })
-                .map_err(|e| {
-                    format!("Error connecting with {}, reason: {}", addr, e.to_string())
-                })?;
+    /// Se conecta con el driver que escucha en addr, se identifica, espera su identificacion y crea
+    /// el actor DriverConnection para la conexion, en la que este driver figura como el que la inicio.
+    /// Devuelve el id del driver, o un error si no esta escuchando conexiones o si es este mismo driver.
+    pub async fn dial_driver(
+        self_id: u32,
+        self_addr: &str,
+        addr: &str,
+        central_driver_addr: &Addr<CentralDriver>,
+    ) -> Result<u32, String> {
+        let mut socket = TcpStream::connect(addr)
+            .await
+            .map_err(|e| format!("Error connecting with {}, reason: {}", addr, e.to_string()))?;
+
+        let request = serde_json::to_string(&CommonMessages::Identification {
+            id: self_id,
+            type_: 'D',
+            addr: Some(self_addr.to_string()),
+        })
+        .map_err(|e| format!("Error connecting with {}, reason: {}", addr, e.to_string()))?;
 
-                socket
-                    .write_all((request + "\n").as_bytes())
-                    .await
-                    .map_err(|e| {
-                        format!("Error connecting with {}, reason: {}", addr, e.to_string())
-                    })?;
+        socket
+            .write_all((request + "\n").as_bytes())
+            .await
+            .map_err(|e| format!("Error connecting with {}, reason: {}", addr, e.to_string()))?;
 
-                let (r, w) = split(socket);
+        let (r, w) = split(socket);
+
+        let mut reader = BufReader::new(r);
+
+        let mut str_response = String::new();
 
-                Self::connect_with_driver(central_driver_addr, r, w, driver_id).await?;
+        timeout(IDENTIFICATION_TIMEOUT, reader.read_line(&mut str_response))
+            .await
+            .map_err(|e| format!("Error connecting with {}, reason: {}", addr, e.to_string()))?
+            .map_err(|e| format!("Error connecting with {}, reason: {}", addr, e.to_string()))?;
+
+        let driver_id = match serde_json::from_str(&str_response) {
+            Ok(CommonMessages::Identification { id, .. }) => id,
+            Err(e) => {
+                return Err(format!(
+                    "Error connecting with {}, reason: {}",
+                    addr,
+                    e.to_string()
+                ))
             }
+        };
 
-            driver_id += 1;
+        if driver_id == self_id {
+            return Err(format!("{} is my own address", addr));
         }
 
-        Ok(())
+        Self::connect_with_driver(
+            central_driver_addr,
+            reader,
+            w,
+            driver_id,
+            addr.to_string(),
+            self_id,
+        )
+        .await?;
+
+        Ok(driver_id)
     }
 
     /// Setea el driver
-    /// - Se conecta con todos los drivers
+    /// - Se conecta con todas las semillas
     /// - Comienza una nueva elección
     /// - Se pone a escuchar por nuevas conexiones
     ///
-    /// Puede tener dos posibles conexiones:
-    ///  - Con un driver: Se crea un nuevo actor DriverConnection y se le pasa un stream de lineas para que escuche los mensajes
-    /// - Con un pasajero: Se lee del stream para ver si recibio algun mensaje y lo handlea como debe
-    async fn setup(central_driver_addr: &Addr<CentralDriver>, id: u32) -> Result<(), String> {
-        Self::connect_all_drivers(id, central_driver_addr).await?;
+    /// Cada conexion aceptada se atiende en su propia tarea (ver `handle_connection`).
+    async fn setup(
+        central_driver_addr: &Addr<CentralDriver>,
+        id: u32,
+        self_addr: String,
+        seeds: Vec<String>,
+    ) -> Result<(), String> {
+        Self::connect_all_drivers(id, &self_addr, &seeds, central_driver_addr).await?;
 
         // raise election
         central_driver_addr
@@ -91,11 +148,9 @@ impl DriverConnectionsHandler {
                 e.to_string()
             })?;
 
-        let self_addr = format!("{}:{}", HOST, MIN_DRIVER_PORT + id);
-
         log::info!("My addr is {}", self_addr);
 
-        let listener = TcpListener::bind(self_addr).await.map_err(|e| {
+        let listener = TcpListener::bind(&self_addr).await.map_err(|e| {
             log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
             e.to_string()
         })?;
@@ -110,58 +165,131 @@ impl DriverConnectionsHandler {
 
             log::debug!("Connection accepted from {}", addr);
 
-            let (mut r, w) = split(socket);
+            let central_driver_addr = central_driver_addr.clone();
+            let self_addr = self_addr.clone();
+
+            actix::spawn(async move {
+                let _ = Self::handle_connection(&central_driver_addr, socket, id, &self_addr).await;
+            });
+        }
+    }
+
+    /// Atiende una conexion entrante, en una tarea propia para no frenar a las demas conexiones
+    /// (por ejemplo, mientras un pasajero responde el presupuesto o espera que se confirme su reserva).
+    /// Lee la identificacion de quien se conecto, esperandola hasta IDENTIFICATION_TIMEOUT:
+    ///  - Con un driver: Se le responde con la identificacion de este driver, se crea un nuevo actor DriverConnection
+    ///    y se le pasa un stream de lineas para que escuche los mensajes
+    /// - Con un pasajero: Se lee del stream para ver si recibio algun mensaje y lo handlea como debe
+    async fn handle_connection(
+        central_driver_addr: &Addr<CentralDriver>,
+        socket: TcpStream,
+        id: u32,
+        self_addr: &str,
+    ) -> Result<(), String> {
+        let (r, mut w) = split(socket);
 
-            let mut reader = BufReader::new(&mut r);
+        let mut reader = BufReader::new(r);
 
-            let mut str_response = String::new();
+        let mut str_response = String::new();
 
-            reader.read_line(&mut str_response).await.map_err(|e| {
+        timeout(IDENTIFICATION_TIMEOUT, reader.read_line(&mut str_response))
+            .await
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?
+            .map_err(|e| {
                 log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                 e.to_string()
             })?;
 
-            if str_response.is_empty() {
-                log::error!("Error receiving identification");
-            }
+        if str_response.is_empty() {
+            log::error!("Error receiving identification");
+        }
 
-            let response: CommonMessages = serde_json::from_str(&str_response).map_err(|e| {
-                log::error!(
-                    "{}:{}, {}, str: {}, len: {}",
-                    std::file!(),
-                    std::line!(),
-                    e.to_string(),
-                    str_response,
-                    str_response.len()
-                );
-                e.to_string()
-            })?;
+        let response: CommonMessages = serde_json::from_str(&str_response).map_err(|e| {
+            log::error!(
+                "{}:{}, {}, str: {}, len: {}",
+                std::file!(),
+                std::line!(),
+                e.to_string(),
+                str_response,
+                str_response.len()
+            );
+            e.to_string()
+        })?;
 
-            match response {
-                CommonMessages::Identification { id, type_ } => match type_ {
-                    'D' => {
-                        let _ = Self::connect_with_driver(central_driver_addr, r, w, id).await;
-                    }
-                    'P' => {
-                        let _ =
-                            Self::handle_passenger_connection(central_driver_addr, w, id, reader)
-                                .await;
-                    }
-                    _ => (),
-                },
-            }
+        match response {
+            CommonMessages::Identification {
+                id: driver_id,
+                type_,
+                addr: driver_addr,
+            } => match type_ {
+                'D' => {
+                    let _ = Self::reply_identification(&mut w, id, self_addr).await;
+
+                    let _ = Self::connect_with_driver(
+                        central_driver_addr,
+                        reader,
+                        w,
+                        driver_id,
+                        driver_addr.unwrap_or_default(),
+                        driver_id,
+                    )
+                    .await;
+                }
+                'P' => {
+                    let _ = Self::handle_passenger_connection(
+                        central_driver_addr,
+                        w,
+                        driver_id,
+                        reader,
+                    )
+                    .await;
+                }
+                _ => (),
+            },
         }
+
+        Ok(())
+    }
+
+    /// Le responde a un driver que se conecto con la identificacion de este driver,
+    /// asi conoce su id y su direccion.
+    async fn reply_identification(
+        w: &mut WriteHalf<TcpStream>,
+        self_id: u32,
+        self_addr: &str,
+    ) -> Result<(), String> {
+        let data = serde_json::to_string(&CommonMessages::Identification {
+            id: self_id,
+            type_: 'D',
+            addr: Some(self_addr.to_string()),
+        })
+        .map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        w.write_all((data + "\n").as_bytes()).await.map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })
     }
 
     /// Creas el Actor DriverConnection y le agregas un stream de lineas para que escuche los mensajes
+    /// - driver_addr: direccion en la que escucha el driver
+    /// - initiator: id del driver que inicio la conexion, usado para descartar conexiones duplicadas
     async fn connect_with_driver(
         central_driver_addr: &Addr<CentralDriver>,
-        r: ReadHalf<TcpStream>,
+        reader: BufReader<ReadHalf<TcpStream>>,
         w: WriteHalf<TcpStream>,
         driver_id: u32,
+        driver_addr: String,
+        initiator: u32,
     ) -> Result<(), String> {
         let driver_conn = DriverConnection::create(|ctx| {
-            ctx.add_stream(LinesStream::new(BufReader::new(r).lines()));
+            ctx.add_stream(LinesStream::new(reader.lines()));
             DriverConnection::new(central_driver_addr.clone(), w, driver_id)
         });
 
@@ -169,6 +297,8 @@ impl DriverConnectionsHandler {
             .try_send(InsertDriverConnection {
                 id: driver_id,
                 addr: driver_conn,
+                driver_addr,
+                initiator,
             })
             .map_err(|e| {
                 log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
@@ -178,16 +308,24 @@ impl DriverConnectionsHandler {
 
     /// Conecta con un pasajero
     /// Lee del strem para ver si recibio algun mensaje.
-    /// - En el caso de recirlo lo parsea y espera otro mensaje 'Listening', luego le envia el mensaje RedirectTrip al central_driver
-    /// - En el caso de no recibir ambos, devuelve un error
+    /// - En el caso de recirlo lo parsea y le envia el presupuesto del viaje (ver `agree_quote`). Si el pasajero
+    ///   lo acepta, registra sus condiciones (ver `RecordTripTerms`), espera otro mensaje 'Listening', luego
+    ///   le envia el mensaje RedirectTrip al central_driver
+    /// - Si el pedido trae una hora de busqueda y el pasajero acepta el presupuesto, reserva el viaje
+    ///   (ver `schedule_trip`)
+    /// - En el caso de recibir un mensaje 'Cancel', cancela el viaje del pasajero (ver `handle_passenger_cancellation`)
+    /// - En el caso de recibir un mensaje 'ListBookings' o 'CancelBooking', le responde las reservas del
+    ///   pasajero o cancela la reserva pedida (ver `handle_booking_list` y `handle_booking_cancellation`)
+    /// - En el caso de no recibir ninguno, devuelve un error
     ///
-    /// Luego se envia un mensaje de confirmacion al pasajero de que su viaje esta siendo procesado
+    /// Luego se envia un mensaje de confirmacion al pasajero de que su viaje esta siendo procesado,
+    /// con el presupuesto del viaje
     ///
     async fn handle_passenger_connection(
         central_driver_addr: &Addr<CentralDriver>,
         mut w: WriteHalf<TcpStream>,
         passenger_id: u32,
-        mut reader: BufReader<&mut ReadHalf<TcpStream>>,
+        mut reader: BufReader<ReadHalf<TcpStream>>,
     ) -> Result<(), String> {
         let mut str_response = String::new();
 
@@ -217,14 +355,74 @@ impl DriverConnectionsHandler {
             TripMessages::TripRequest {
                 source,
                 destination,
-            } => (source, destination),
+                stops,
+                pickup_at,
+            } => (source, destination, stops, pickup_at),
+            TripMessages::Cancel {} => {
+                return Self::handle_passenger_cancellation(central_driver_addr, w, passenger_id)
+                    .await;
+            }
+            TripMessages::ListBookings {} => {
+                return Self::handle_booking_list(central_driver_addr, w, passenger_id).await;
+            }
+            TripMessages::CancelBooking { booking_id } => {
+                return Self::handle_booking_cancellation(
+                    central_driver_addr,
+                    w,
+                    passenger_id,
+                    booking_id,
+                )
+                .await;
+            }
             _ => {
                 log::error!("{}:{}, TripRequest expected", std::file!(), std::line!());
                 return Err("TripRequest expected".into());
             }
         };
 
-        let (source, destination) = trip_data;
+        let (source, destination, stops, pickup_at) = trip_data;
+
+        let quote = match Self::agree_quote(
+            central_driver_addr,
+            &mut w,
+            &mut reader,
+            passenger_id,
+            source,
+            destination,
+            &stops,
+        )
+        .await?
+        {
+            Some(quote) => quote,
+            None => return Ok(()),
+        };
+
+        if let Some(pickup_at) = pickup_at {
+            let booking = Booking {
+                booking_id: rand::random(),
+                passenger_id,
+                source,
+                destination,
+                stops,
+                pickup_at,
+                quote,
+            };
+
+            return Self::schedule_trip(central_driver_addr, w, booking).await;
+        }
+
+        central_driver_addr
+            .try_send(RecordTripTerms {
+                passenger_id,
+                terms: TripTerms {
+                    surge: quote.surge,
+                    stops,
+                },
+            })
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
 
         let mut listen_message = String::new();
 
@@ -277,7 +475,7 @@ impl DriverConnectionsHandler {
         };
 
         let parsed_data = serde_json::to_string(&TripMessages::TripResponse {
-            status: common::utils::json_parser::TripStatus::RequestDelivered,
+            status: TripStatus::RequestDelivered { quote },
             detail: "Your request has been delivered, a driver will pick you up soon".to_string(),
         })
         .inspect_err(|e| {
@@ -298,4 +496,240 @@ impl DriverConnectionsHandler {
 
         Ok(())
     }
+
+    /// Presupuesta el viaje de un pasajero, con sus paradas intermedias y el multiplicador por demanda de
+    /// su zona (ver `QuoteTrip`), se lo envia y espera su respuesta.
+    /// - Si el pasajero acepta el presupuesto, lo devuelve.
+    /// - Si lo rechaza, devuelve None.
+    /// - Si no responde a tiempo, devuelve un error.
+    async fn agree_quote(
+        central_driver_addr: &Addr<CentralDriver>,
+        w: &mut WriteHalf<TcpStream>,
+        reader: &mut BufReader<ReadHalf<TcpStream>>,
+        passenger_id: u32,
+        source: Position,
+        destination: Position,
+        stops: &[Position],
+    ) -> Result<Option<FareQuote>, String> {
+        let quote = central_driver_addr
+            .send(QuoteTrip {
+                source,
+                stops: stops.to_vec(),
+                destination,
+            })
+            .await
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
+
+        log::info!(
+            "[PRICING] Quoted ${} for the trip of passenger {} ({} blocks, x{} surge)",
+            quote.amount,
+            passenger_id,
+            quote.distance,
+            quote.surge
+        );
+
+        let data = serde_json::to_string(&TripMessages::Quote { quote }).map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        w.write_all((data + "\n").as_bytes()).await.map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        w.flush().await.map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        let mut answer = String::new();
+
+        timeout(QUOTE_ANSWER_TIMEOUT, reader.read_line(&mut answer))
+            .await
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
+
+        match serde_json::from_str(&answer) {
+            Ok(TripMessages::QuoteAnswer { accepted: true }) => Ok(Some(quote)),
+            Ok(TripMessages::QuoteAnswer { accepted: false }) => {
+                log::info!(
+                    "[PRICING] Passenger {} rejected the quote of ${} (x{} surge)",
+                    passenger_id,
+                    quote.amount,
+                    quote.surge
+                );
+
+                Ok(None)
+            }
+            _ => {
+                log::error!("{}:{}, QuoteAnswer expected", std::file!(), std::line!());
+                Err("QuoteAnswer expected".into())
+            }
+        }
+    }
+
+    /// Le pide al central_driver que cancele el viaje de un pasajero, que todavia no tiene un driver
+    /// que lo lleve, y le confirma al pasajero que su viaje fue cancelado.
+    async fn handle_passenger_cancellation(
+        central_driver_addr: &Addr<CentralDriver>,
+        mut w: WriteHalf<TcpStream>,
+        passenger_id: u32,
+    ) -> Result<(), String> {
+        central_driver_addr
+            .try_send(CancelTrip { passenger_id })
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
+
+        let data = serde_json::to_string(&TripMessages::TripResponse {
+            status: TripStatus::Cancelled,
+            detail: "Your trip has been cancelled".to_string(),
+        })
+        .map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        w.write_all((data + "\n").as_bytes()).await.map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        w.flush().await.map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })
+    }
+    /// Reserva el viaje de un pasajero para mas adelante (ver `ScheduleTrip`) y, cuando la reserva se
+    /// confirma en el log de asignaciones, le responde al pasajero el id de la reserva y el momento en el
+    /// que se va a empezar a buscar un driver. Si no se pudo reservar o no se confirmo a tiempo, le
+    /// responde un error.
+    async fn schedule_trip(
+        central_driver_addr: &Addr<CentralDriver>,
+        mut w: WriteHalf<TcpStream>,
+        booking: Booking,
+    ) -> Result<(), String> {
+        let booking_id = booking.booking_id;
+
+        let scheduled = central_driver_addr
+            .send(ScheduleTrip { booking })
+            .await
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
+
+        let scheduled = match scheduled {
+            Ok((search_at, committed)) => match timeout(BOOKING_COMMIT_TIMEOUT, committed).await {
+                Ok(Ok(true)) => Ok(search_at),
+                _ => Err("The booking could not be confirmed".to_string()),
+            },
+            Err(e) => Err(e),
+        };
+
+        let response = match scheduled {
+            Ok(search_at) => TripMessages::TripResponse {
+                status: TripStatus::Scheduled {
+                    booking_id,
+                    search_at,
+                },
+                detail: format!("Your trip has been booked with id {}", booking_id),
+            },
+            Err(e) => TripMessages::TripResponse {
+                status: TripStatus::Error,
+                detail: e,
+            },
+        };
+
+        Self::send_trip_message(&mut w, &response).await
+    }
+
+    /// Le responde al pasajero sus reservas pendientes (ver `ListBookings`).
+    async fn handle_booking_list(
+        central_driver_addr: &Addr<CentralDriver>,
+        mut w: WriteHalf<TcpStream>,
+        passenger_id: u32,
+    ) -> Result<(), String> {
+        let bookings = central_driver_addr
+            .send(ListBookings { passenger_id })
+            .await
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
+
+        Self::send_trip_message(&mut w, &TripMessages::Bookings { bookings }).await
+    }
+
+    /// Le pide al central_driver que cancele una reserva del pasajero (ver `CancelBooking`) y, cuando la
+    /// cancelacion se confirma en el log de asignaciones, le confirma al pasajero que fue cancelada.
+    /// Si el pasajero no tiene una reserva con ese id, o si no se pudo cancelar, le responde un error.
+    async fn handle_booking_cancellation(
+        central_driver_addr: &Addr<CentralDriver>,
+        mut w: WriteHalf<TcpStream>,
+        passenger_id: u32,
+        booking_id: u64,
+    ) -> Result<(), String> {
+        let cancelled = central_driver_addr
+            .send(CancelBooking {
+                passenger_id,
+                booking_id,
+            })
+            .await
+            .map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
+
+        let response = match cancelled {
+            Some(committed) => match timeout(BOOKING_COMMIT_TIMEOUT, committed).await {
+                Ok(Ok(true)) => TripMessages::TripResponse {
+                    status: TripStatus::Cancelled,
+                    detail: format!("Your booking {} has been cancelled", booking_id),
+                },
+                _ => TripMessages::TripResponse {
+                    status: TripStatus::Error,
+                    detail: format!("Your booking {} could not be cancelled", booking_id),
+                },
+            },
+            None => TripMessages::TripResponse {
+                status: TripStatus::Error,
+                detail: format!("You have no upcoming booking with id {}", booking_id),
+            },
+        };
+
+        Self::send_trip_message(&mut w, &response).await
+    }
+
+    /// Serializa un mensaje y se lo envia al pasajero
+    async fn send_trip_message(
+        w: &mut WriteHalf<TcpStream>,
+        message: &TripMessages,
+    ) -> Result<(), String> {
+        let data = serde_json::to_string(message).map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        w.write_all((data + "\n").as_bytes()).await.map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })?;
+
+        w.flush().await.map_err(|e| {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            e.to_string()
+        })
+    }
 }
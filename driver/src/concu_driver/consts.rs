@@ -6,3 +6,39 @@ pub const POSITION_NOTIFICATION_INTERVAL: Duration = Duration::from_secs(5);
 pub const TAKE_TRIP_TIMEOUT_MS: Duration = Duration::from_millis(300);
 pub const DEFAULT_TAKE_TRIP_PROBABILTY: f64 = 1.0;
 pub const TRIP_GO_TO_SLEEP: Duration = Duration::from_millis(750);
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+pub const DEFAULT_SUSPICION_TIMEOUT: Duration = Duration::from_millis(2000);
+pub const DEFAULT_RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+pub const DEFAULT_RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(8);
+pub const DEFAULT_LEADERLESS_TRIP_DEADLINE: Duration = Duration::from_secs(5);
+pub const IDENTIFICATION_TIMEOUT: Duration = Duration::from_millis(500);
+pub const DEFAULT_GOSSIP_INTERVAL: Duration = Duration::from_millis(1000);
+pub const DEFAULT_GOSSIP_FANOUT: usize = 3;
+pub const DEFAULT_MEMBER_DEAD_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const DEFAULT_ZONE_ROWS: u32 = 1;
+pub const DEFAULT_ZONE_COLS: u32 = 1;
+pub const DEFAULT_ASSIGNMENT_CONFIRM_TIMEOUT: Duration = Duration::from_millis(3000);
+pub const DEFAULT_LOG_COMPACTION_THRESHOLD: usize = 100;
+pub const DEFAULT_OFFER_FANOUT: usize = 3;
+pub const DEFAULT_SEARCH_RADIUS_STEP: u32 = 10;
+pub const DEFAULT_MAX_SEARCH_RADIUS: u32 = 30;
+pub const DEFAULT_SEARCH_GIVE_UP: Duration = Duration::from_millis(5000);
+pub const DEFAULT_BATCH_WINDOW: Duration = Duration::from_millis(0);
+pub const DEFAULT_DISPATCH_STRATEGY: &str = "nearest";
+pub const DEFAULT_FAIRNESS_FACTOR: f64 = 1.0;
+pub const CANCELLED_TRIP_MEMORY: Duration = Duration::from_millis(3000);
+pub const DEFAULT_RELEASE_TRIP_PROBABILITY: f64 = 0.0;
+pub const DEFAULT_BASE_FARE: u32 = 500;
+pub const DEFAULT_FARE_PER_BLOCK: u32 = 40;
+pub const DEFAULT_FARE_PER_WAITING_SECOND: u32 = 10;
+pub const DEFAULT_SURGE_GRID_ROWS: u32 = 4;
+pub const DEFAULT_SURGE_GRID_COLS: u32 = 4;
+pub const DEFAULT_SURGE_SENSITIVITY: f64 = 0.5;
+pub const DEFAULT_MAX_SURGE: f64 = 2.0;
+pub const SURGE_UPDATE_INTERVAL: Duration = Duration::from_millis(1000);
+pub const QUOTE_ANSWER_TIMEOUT: Duration = Duration::from_millis(500);
+pub const DEFAULT_SEAT_CAPACITY: usize = 1;
+pub const DEFAULT_MAX_POOL_DETOUR: u32 = 10;
+pub const DEFAULT_SCHEDULE_LEAD_TIME: Duration = Duration::from_millis(5000);
+pub const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_millis(1000);
+pub const BOOKING_COMMIT_TIMEOUT: Duration = Duration::from_millis(3000);
@@ -0,0 +1,246 @@
+use std::{sync::Arc, time::Duration};
+
+use common::utils::position::Position;
+use rand::seq::SliceRandom;
+
+/// Historial de un conductor, para ordenar a los candidatos
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DriverHistory {
+    /// Indice en el log de asignaciones del ultimo viaje que se le asigno, 0 si nunca tuvo uno
+    pub last_served: u64,
+    /// Viajes completados en la sesion
+    pub completed_trips: u32,
+    /// Tiempo desde su ultimo viaje, cero si esta en un viaje
+    pub idle_time: Duration,
+}
+
+/// Conductor candidato a tomar un viaje
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// Id del conductor
+    pub driver_id: u32,
+    /// Posicion del conductor
+    pub position: Position,
+    /// Historial del conductor
+    pub history: DriverHistory,
+}
+
+/// Estrategia con la que se ordenan los conductores a los que se les ofrece un viaje.
+pub trait DispatchStrategy: Send + Sync {
+    /// Nombre de la estrategia, para los logs
+    fn name(&self) -> &'static str;
+
+    /// Ordena del mejor al peor a los candidatos, que ya estan dentro del radio de busqueda.
+    fn rank(&self, source: &Position, candidates: &mut [Candidate]);
+}
+
+/// Primero los conductores mas cercanos al pasajero (distancia Manhattan)
+pub struct Nearest;
+
+impl DispatchStrategy for Nearest {
+    fn name(&self) -> &'static str {
+        "nearest"
+    }
+
+    fn rank(&self, source: &Position, candidates: &mut [Candidate]) {
+        candidates.sort_by_key(|c| (c.position.distance_to(source), c.driver_id));
+    }
+}
+
+/// Primero los conductores que llegarian antes a buscar al pasajero.
+/// Un conductor avanza en ambos ejes a la vez, en promedio `AVERAGE_STEP` cuadras por eje en cada paso,
+/// asi que la cantidad de pasos depende del eje en el que esta mas lejos.
+pub struct EtaWeighted;
+
+impl EtaWeighted {
+    /// Cuadras que avanza en promedio un conductor por eje en cada paso
+    const AVERAGE_STEP: u32 = 2;
+
+    /// Cantidad estimada de pasos hasta llegar al pasajero
+    fn eta(source: &Position, position: &Position) -> u32 {
+        let dx = source.x.abs_diff(position.x);
+        let dy = source.y.abs_diff(position.y);
+
+        dx.max(dy).div_ceil(Self::AVERAGE_STEP)
+    }
+}
+
+impl DispatchStrategy for EtaWeighted {
+    fn name(&self) -> &'static str {
+        "eta"
+    }
+
+    fn rank(&self, source: &Position, candidates: &mut [Candidate]) {
+        candidates.sort_by_key(|c| {
+            (
+                Self::eta(source, &c.position),
+                c.position.distance_to(source),
+                c.driver_id,
+            )
+        });
+    }
+}
+
+/// Primero los conductores que hace mas que no tienen un viaje, para repartir los viajes.
+/// Entre los que tuvieron su ultimo viaje a la vez (o nunca tuvieron uno), primero los mas cercanos.
+pub struct LeastRecentlyServed;
+
+impl DispatchStrategy for LeastRecentlyServed {
+    fn name(&self) -> &'static str {
+        "least_recently_served"
+    }
+
+    fn rank(&self, source: &Position, candidates: &mut [Candidate]) {
+        candidates.sort_by_key(|c| {
+            (
+                c.history.last_served,
+                c.position.distance_to(source),
+                c.driver_id,
+            )
+        });
+    }
+}
+
+/// Pondera la distancia con el trabajo de cada conductor en la sesion: cada viaje completado lo aleja
+/// `factor` cuadras y cada `IDLE_SECS_PER_BLOCK` segundos sin viajes lo acerca `factor` cuadras.
+/// Con factor 0 es igual a nearest.
+pub struct Fair {
+    /// Peso del trabajo de cada conductor frente a la distancia
+    factor: f64,
+}
+
+impl Fair {
+    /// Segundos sin viajes que equivalen a una cuadra de distancia
+    const IDLE_SECS_PER_BLOCK: f64 = 10.0;
+
+    /// Crea la estrategia con el factor dado.
+    pub fn new(factor: f64) -> Self {
+        Self { factor }
+    }
+
+    /// Puntaje de un candidato, cuanto menor mejor
+    fn score(&self, source: &Position, candidate: &Candidate) -> f64 {
+        let workload = candidate.history.completed_trips as f64
+            - candidate.history.idle_time.as_secs_f64() / Self::IDLE_SECS_PER_BLOCK;
+
+        candidate.position.distance_to(source) as f64 + self.factor * workload
+    }
+}
+
+impl DispatchStrategy for Fair {
+    fn name(&self) -> &'static str {
+        "fair"
+    }
+
+    fn rank(&self, source: &Position, candidates: &mut [Candidate]) {
+        candidates.sort_by(|a, b| {
+            self.score(source, a)
+                .total_cmp(&self.score(source, b))
+                .then(a.driver_id.cmp(&b.driver_id))
+        });
+    }
+}
+
+/// Los conductores en un orden al azar
+pub struct Random;
+
+impl DispatchStrategy for Random {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn rank(&self, _source: &Position, candidates: &mut [Candidate]) {
+        candidates.shuffle(&mut rand::thread_rng());
+    }
+}
+
+/// Devuelve la estrategia con el nombre dado: nearest, eta, least_recently_served, fair o random.
+/// El factor solo lo usa fair. Si el nombre no es conocido, usa nearest.
+pub fn strategy_from_name(name: &str, fairness_factor: f64) -> Arc<dyn DispatchStrategy> {
+    match name {
+        "eta" => Arc::new(EtaWeighted),
+        "least_recently_served" => Arc::new(LeastRecentlyServed),
+        "fair" => Arc::new(Fair::new(fairness_factor)),
+        "random" => Arc::new(Random),
+        "nearest" => Arc::new(Nearest),
+        _ => {
+            log::warn!("Unknown dispatch strategy {}, using nearest", name);
+            Arc::new(Nearest)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(driver_id: u32, x: u32, y: u32, last_served: u64) -> Candidate {
+        Candidate {
+            driver_id,
+            position: Position::new(x, y),
+            history: DriverHistory {
+                last_served,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn with_work(mut candidate: Candidate, completed_trips: u32, idle_secs: u64) -> Candidate {
+        candidate.history.completed_trips = completed_trips;
+        candidate.history.idle_time = Duration::from_secs(idle_secs);
+        candidate
+    }
+
+    fn ranked(strategy: &dyn DispatchStrategy, mut candidates: Vec<Candidate>) -> Vec<u32> {
+        strategy.rank(&Position::new(0, 0), &mut candidates);
+        candidates.iter().map(|c| c.driver_id).collect()
+    }
+
+    #[test]
+    fn test_nearest() {
+        let candidates = vec![
+            candidate(1, 6, 0, 0),
+            candidate(2, 2, 2, 0),
+            candidate(3, 1, 0, 0),
+        ];
+
+        assert_eq!(ranked(&Nearest, candidates), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_eta_prefers_diagonal_moves() {
+        // Ambos estan a 8 cuadras, pero en diagonal se avanza por los dos ejes a la vez
+        let candidates = vec![candidate(1, 8, 0, 0), candidate(2, 4, 4, 0)];
+
+        assert_eq!(ranked(&EtaWeighted, candidates.clone()), vec![2, 1]);
+        assert_eq!(ranked(&Nearest, candidates), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_least_recently_served() {
+        let candidates = vec![
+            candidate(1, 1, 0, 7),
+            candidate(2, 9, 0, 0),
+            candidate(3, 5, 0, 3),
+        ];
+
+        assert_eq!(ranked(&LeastRecentlyServed, candidates), vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn test_fair_weighs_work_against_distance() {
+        let busy = with_work(candidate(1, 2, 0, 0), 5, 0);
+        let idle = with_work(candidate(2, 6, 0, 0), 0, 60);
+
+        assert_eq!(ranked(&Fair::new(1.0), vec![busy, idle]), vec![2, 1]);
+        assert_eq!(ranked(&Fair::new(0.0), vec![busy, idle]), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_strategy_from_name() {
+        assert_eq!(strategy_from_name("eta", 1.0).name(), "eta");
+        assert_eq!(strategy_from_name("fair", 1.0).name(), "fair");
+        assert_eq!(strategy_from_name("random", 1.0).name(), "random");
+        assert_eq!(strategy_from_name("unknown", 1.0).name(), "nearest");
+    }
+}
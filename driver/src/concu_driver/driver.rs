@@ -1,9 +1,24 @@
 use std::error::Error;
 
 use actix_rt::System;
-use tokio::join;
+use common::utils::{
+    consts::{HOST, MIN_DRIVER_PORT},
+    seeds::driver_seeds,
+};
+use tokio::{
+    select,
+    signal::{
+        ctrl_c,
+        unix::{signal, SignalKind},
+    },
+    sync::oneshot,
+};
 
-use super::{central_driver::CentralDriver, connections_handler::DriverConnectionsHandler};
+use super::{
+    central_driver::{CentralDriver, Shutdown},
+    config::{cluster_size_from_env, env_or},
+    connections_handler::DriverConnectionsHandler,
+};
 
 /// Inicia el driver con el id pasado por parametro
 pub fn drive(id: u32) -> Result<(), Box<dyn Error>> {
@@ -13,17 +28,68 @@ pub fn drive(id: u32) -> Result<(), Box<dyn Error>> {
 }
 
 /// Conecta el driver con el id pasado por parametro
+///
+/// La direccion en la que escucha se configura con la variable de entorno DRIVER_ADDR
+/// (por defecto, el puerto MIN_DRIVER_PORT + id en HOST) y las semillas a traves de las cuales
+/// se une al cluster con DRIVER_SEEDS. El tamaño del cluster es obligatorio: se toma de CLUSTER_SIZE
+/// o, si no esta definida, de la cantidad de semillas (ver `cluster_size_from_env`), y si no se
+/// conoce el driver no arranca.
+///
+/// Al recibir SIGINT o SIGTERM deja de aceptar conexiones y se apaga de forma ordenada:
+/// si es el lider le entrega el liderazgo a otro driver y, si esta llevando a un pasajero,
+/// termina el viaje antes de salir.
 async fn connect_all(id: u32) -> Result<(), Box<dyn Error>> {
-    let cdriver = CentralDriver::create_new(id);
+    let self_addr = env_or("DRIVER_ADDR", format!("{}:{}", HOST, MIN_DRIVER_PORT + id));
 
-    let drivers_conn_task = DriverConnectionsHandler::run(id, cdriver.clone());
+    let cluster_size = cluster_size_from_env().ok_or_else(|| {
+        log::error!("The cluster size is unknown, set CLUSTER_SIZE or DRIVER_SEEDS");
+        "The cluster size is unknown, set CLUSTER_SIZE or DRIVER_SEEDS".to_string()
+    })?;
 
-    let (drivers_conn_join,) = join!(drivers_conn_task);
+    let cdriver = CentralDriver::create_new(id, self_addr.clone(), cluster_size);
 
-    drivers_conn_join.map_err(|e| {
+    let mut drivers_conn_task =
+        DriverConnectionsHandler::run(id, self_addr, driver_seeds(), cdriver.clone());
+
+    select! {
+        drivers_conn_join = &mut drivers_conn_task => {
+            drivers_conn_join.map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })??;
+        }
+        signal_result = shutdown_signal() => {
+            signal_result?;
+
+            log::info!("Shutdown requested, waiting until i am idle");
+
+            drivers_conn_task.abort();
+
+            let (idle_tx, idle_rx) = oneshot::channel();
+
+            cdriver.send(Shutdown { idle_tx }).await.map_err(|e| {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                e.to_string()
+            })?;
+
+            let _ = idle_rx.await;
+
+            log::info!("Bye!");
+        }
+    }
+
+    Ok(())
+}
+
+/// Espera hasta recibir SIGINT o SIGTERM
+async fn shutdown_signal() -> Result<(), String> {
+    let mut sigterm = signal(SignalKind::terminate()).map_err(|e| {
         log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
         e.to_string()
-    })??;
+    })?;
 
-    Ok(())
+    select! {
+        res = ctrl_c() => res.map_err(|e| e.to_string()),
+        _ = sigterm.recv() => Ok(()),
+    }
 }
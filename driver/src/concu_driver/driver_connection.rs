@@ -1,4 +1,6 @@
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, StreamHandler};
+use std::time::{Duration, Instant};
+
+use actix::{Actor, ActorContext, Addr, AsyncContext, Context, Handler, Message, StreamHandler};
 use actix_async_handler::async_handler;
 use tokio::{
     io::{AsyncWriteExt, WriteHalf},
@@ -9,9 +11,17 @@ use crate::concu_driver::central_driver::RemoveDriverConnection;
 
 use super::{
     central_driver::{
-        Alive, CanHandleTrip, CanHandleTripACK, CentralDriver, Coordinator, Election,
-        RedirectNewTrip, SetDriverPosition, StartElection,
+        AppendEntries, AppendEntriesACK, CanHandleTrip, CanHandleTripACK, CancelBooking,
+        CancelTrip, CentralDriver, ConfirmTrip, Coordinator, DispatchSnapshot, DriverAvailable,
+        FindDriver, FinishAssignment, Gossip, MergeDispatchState, ProposeAssignment, QueueTrip,
+        RecordTripTerms, RedirectNewTrip, ReleaseTrip, ReplicatePoolRoute, ReplicatePosition,
+        ReplicateTripSearch, ReplicateTripSearchDone, ReplicateTripTerms, RequestVote, RevokeOffer,
+        ScheduleTrip, SetDriverPosition, SurgeUpdate, SuspectDriver, TimeoutNow, TripCancelled,
+        UnsuspectDriver, UpdatePoolRoute, UpdateTripPosition, Vote, ZoneAssignment, ZoneTripSearch,
+        ZoneTripSearchDone,
     },
+    config::env_duration_ms,
+    consts::{DEFAULT_HEARTBEAT_INTERVAL, DEFAULT_SUSPICION_TIMEOUT},
     json_parser::DriverMessages,
 };
 
@@ -22,6 +32,14 @@ pub struct DriverConnection {
     driver_write_stream: Option<WriteHalf<TcpStream>>,
     /// ID del driver
     driver_id: u32,
+    /// Momento en el que se recibio el ultimo mensaje del driver
+    last_seen: Instant,
+    /// Si el driver es sospechoso de estar caido
+    suspected: bool,
+    /// Cada cuanto se envia un heartbeat al driver
+    heartbeat_interval: Duration,
+    /// Tiempo sin recibir mensajes del driver a partir del cual se lo sospecha caido
+    suspicion_timeout: Duration,
 }
 
 impl DriverConnection {
@@ -30,6 +48,9 @@ impl DriverConnection {
     /// - El stream de escritura
     /// - ID del driver.
     /// - Retorna la conexión con el driver.
+    ///
+    /// El intervalo de heartbeats y el timeout de sospecha se configuran con las variables de entorno
+    /// HEARTBEAT_INTERVAL_MS y SUSPICION_TIMEOUT_MS.
     pub fn new(
         self_driver_addr: Addr<CentralDriver>,
         wstream: WriteHalf<TcpStream>,
@@ -39,6 +60,36 @@ impl DriverConnection {
             central_driver: self_driver_addr,
             driver_write_stream: Some(wstream),
             driver_id,
+            last_seen: Instant::now(),
+            suspected: false,
+            heartbeat_interval: env_duration_ms(
+                "HEARTBEAT_INTERVAL_MS",
+                DEFAULT_HEARTBEAT_INTERVAL,
+            ),
+            suspicion_timeout: env_duration_ms("SUSPICION_TIMEOUT_MS", DEFAULT_SUSPICION_TIMEOUT),
+        }
+    }
+
+    /// Envia un heartbeat al driver y verifica si se recibio algun mensaje suyo dentro del
+    /// timeout de sospecha. Si no es asi, le notifica al `CentralDriver` que el driver es sospechoso
+    /// de estar caido (por ejemplo, si esta colgado pero su socket sigue abierto).
+    fn heartbeat(&mut self, ctx: &mut Context<Self>) {
+        if let Ok(data) = serde_json::to_string(&DriverMessages::Heartbeat {})
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()))
+        {
+            ctx.notify(SendAll { data });
+        }
+
+        if !self.suspected && self.last_seen.elapsed() > self.suspicion_timeout {
+            log::warn!(
+                "No heartbeat from driver {} in {:?}, suspecting it is down",
+                self.driver_id,
+                self.suspicion_timeout
+            );
+
+            self.suspected = true;
+            self.central_driver
+                .do_send(SuspectDriver { id: self.driver_id });
         }
     }
 }
@@ -46,10 +97,20 @@ impl DriverConnection {
 impl StreamHandler<Result<String, std::io::Error>> for DriverConnection {
     /// Maneja los mensajes recibidos desde los drivers.
     /// Verifica si el mensaje es un mensaje válido y en caso de serlo envía un mensaje a si mismo "RecvAll" con el mensaje recibido.
+    /// Cualquier mensaje recibido cuenta como señal de vida del driver.
     fn handle(&mut self, msg: Result<String, std::io::Error>, ctx: &mut Self::Context) {
         if let Ok(data) = msg {
             // log::debug!("recv {}", data);
 
+            self.last_seen = Instant::now();
+
+            if self.suspected {
+                log::info!("Driver {} is alive again", self.driver_id);
+                self.suspected = false;
+                self.central_driver
+                    .do_send(UnsuspectDriver { id: self.driver_id });
+            }
+
             let _ = ctx.address().try_send(RecvAll { data }).inspect_err(|e| {
                 log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
             });
@@ -59,15 +120,15 @@ impl StreamHandler<Result<String, std::io::Error>> for DriverConnection {
     /// Maneja la finalización del flujo asociado al actor `DriverConnection`.
     /// Envia un mensaje al actor `CentralDriver` para eliminar la conexión con el driver.
     ///
-    /// Inicia una elección(porque puede ser la situación en la que el driver que se desconectó era el líder).
-    fn finished(&mut self, _ctx: &mut Self::Context) {
+    /// Si el driver que se desconectó era el líder, el `CentralDriver` inicia una elección.
+    fn finished(&mut self, ctx: &mut Self::Context) {
         // if let Some(did) = self.driver_id {
         log::warn!("Broken pipe with driver {}", self.driver_id);
-        self.central_driver
-            .do_send(RemoveDriverConnection { id: self.driver_id });
+        self.central_driver.do_send(RemoveDriverConnection {
+            id: self.driver_id,
+            addr: ctx.address(),
+        });
         // }
-        // Election
-        self.central_driver.do_send(StartElection {});
 
         // ctx.stop();
     }
@@ -75,6 +136,11 @@ impl StreamHandler<Result<String, std::io::Error>> for DriverConnection {
 
 impl Actor for DriverConnection {
     type Context = Context<Self>;
+
+    /// Al iniciar el actor, comienza a enviar heartbeats periodicamente al driver.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.heartbeat_interval, |this, ctx| this.heartbeat(ctx));
+    }
 }
 
 #[derive(Message)]
@@ -145,25 +211,54 @@ impl Handler<RecvAll> for DriverConnection {
         })?;
 
         match data {
-            DriverMessages::Election { sender_id } => {
+            DriverMessages::RequestVote {
+                term,
+                candidate_id,
+                last_log_index,
+                last_log_term,
+            } => {
+                self.central_driver
+                    .try_send(RequestVote {
+                        term,
+                        candidate_id,
+                        last_log_index,
+                        last_log_term,
+                    })
+                    .map_err(|e| {
+                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                        e.to_string()
+                    })?;
+            }
+            DriverMessages::Vote {
+                term,
+                voter_id,
+                granted,
+            } => {
                 self.central_driver
-                    .try_send(Election { sender_id })
+                    .try_send(Vote {
+                        term,
+                        voter_id,
+                        granted,
+                    })
                     .map_err(|e| {
                         log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                         e.to_string()
                     })?;
             }
-            DriverMessages::Alive { responder_id } => {
+            DriverMessages::TimeoutNow { term } => {
                 self.central_driver
-                    .try_send(Alive { responder_id })
+                    .try_send(TimeoutNow {
+                        term,
+                        leader_id: self.driver_id,
+                    })
                     .map_err(|e| {
                         log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                         e.to_string()
                     })?;
             }
-            DriverMessages::Coordinator { leader_id } => {
+            DriverMessages::Coordinator { leader_id, term } => {
                 self.central_driver
-                    .try_send(Coordinator { leader_id })
+                    .try_send(Coordinator { leader_id, term })
                     .map_err(|e| {
                         log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                         e.to_string()
@@ -188,6 +283,9 @@ impl Handler<RecvAll> for DriverConnection {
                 passenger_location,
                 destination,
                 driver_id,
+                coordinator_id,
+                offer_id,
+                lease_ms,
             } => {
                 self.central_driver
                     .try_send(CanHandleTrip {
@@ -195,6 +293,9 @@ impl Handler<RecvAll> for DriverConnection {
                         source: passenger_location,
                         destination,
                         driver_id,
+                        coordinator_id,
+                        offer_id,
+                        lease: Duration::from_millis(lease_ms),
                     })
                     .map_err(|e| {
                         log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
@@ -205,12 +306,16 @@ impl Handler<RecvAll> for DriverConnection {
                 response,
                 passenger_id,
                 driver_id,
+                coordinator_id,
+                offer_id,
             } => {
                 self.central_driver
                     .try_send(CanHandleTripACK {
                         response,
                         passenger_id,
                         driver_id,
+                        coordinator_id,
+                        offer_id,
                     })
                     .map_err(|e| {
                         log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
@@ -232,8 +337,400 @@ impl Handler<RecvAll> for DriverConnection {
                     log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                     e.to_string()
                 })?,
+            DriverMessages::ReplicatePosition {
+                term,
+                driver_id,
+                driver_position,
+            } => self
+                .central_driver
+                .try_send(ReplicatePosition {
+                    term,
+                    leader_id: self.driver_id,
+                    driver_id,
+                    driver_position,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ReplicateTripSearch { term, trip } => self
+                .central_driver
+                .try_send(ReplicateTripSearch {
+                    term,
+                    leader_id: self.driver_id,
+                    trip,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ReplicateTripSearchDone { term, passenger_id } => self
+                .central_driver
+                .try_send(ReplicateTripSearchDone {
+                    term,
+                    leader_id: self.driver_id,
+                    passenger_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ZoneTripSearch { trip } => self
+                .central_driver
+                .try_send(ZoneTripSearch {
+                    trip,
+                    coordinator_id: self.driver_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ZoneTripSearchDone { passenger_id } => self
+                .central_driver
+                .try_send(ZoneTripSearchDone {
+                    passenger_id,
+                    coordinator_id: self.driver_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::Heartbeat {} => (),
+            DriverMessages::Gossip { members } => self
+                .central_driver
+                .try_send(Gossip { members })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::DispatchSnapshot {
+                term,
+                driver_positions,
+                pending_trips,
+            } => self
+                .central_driver
+                .try_send(DispatchSnapshot {
+                    term,
+                    leader_id: self.driver_id,
+                    driver_positions,
+                    pending_trips,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::MergeDispatchState {
+                term,
+                leader_id,
+                driver_positions,
+                pending_trips,
+            } => self
+                .central_driver
+                .try_send(MergeDispatchState {
+                    term,
+                    leader_id,
+                    driver_positions,
+                    pending_trips,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ZoneAssignment { term, coordinators } => self
+                .central_driver
+                .try_send(ZoneAssignment {
+                    term,
+                    leader_id: self.driver_id,
+                    coordinators,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ZoneHandoff {
+                passenger_id,
+                passenger_location,
+                destination,
+                handoff_zones,
+            } => self
+                .central_driver
+                .try_send(FindDriver {
+                    passenger_id,
+                    source: passenger_location,
+                    destination,
+                    handoff_zones: Some(handoff_zones),
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ProposeAssignment {
+                passenger_id,
+                driver_id,
+                passenger_location,
+                destination,
+                offer_id,
+            } => self
+                .central_driver
+                .try_send(ProposeAssignment {
+                    passenger_id,
+                    driver_id,
+                    source: passenger_location,
+                    destination,
+                    offer_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::AppendEntries {
+                term,
+                snapshot,
+                prev_index,
+                prev_term,
+                entries,
+                leader_commit,
+            } => self
+                .central_driver
+                .try_send(AppendEntries {
+                    term,
+                    leader_id: self.driver_id,
+                    snapshot,
+                    prev_index,
+                    prev_term,
+                    entries,
+                    leader_commit,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::AppendEntriesACK {
+                term,
+                driver_id,
+                success,
+                match_index,
+            } => self
+                .central_driver
+                .try_send(AppendEntriesACK {
+                    term,
+                    driver_id,
+                    success,
+                    match_index,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ConfirmTrip {
+                passenger_id,
+                driver_id,
+                passenger_location,
+                destination,
+                offer_id,
+            } => self
+                .central_driver
+                .try_send(ConfirmTrip {
+                    passenger_id,
+                    driver_id,
+                    source: passenger_location,
+                    destination,
+                    offer_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::RevokeOffer {
+                passenger_id,
+                driver_id,
+                offer_id,
+            } => self
+                .central_driver
+                .try_send(RevokeOffer {
+                    passenger_id,
+                    driver_id,
+                    offer_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::FinishAssignment {
+                passenger_id,
+                offer_id,
+            } => self
+                .central_driver
+                .try_send(FinishAssignment {
+                    passenger_id,
+                    offer_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::QueueTrip {
+                passenger_id,
+                passenger_location,
+                destination,
+            } => self
+                .central_driver
+                .try_send(QueueTrip {
+                    passenger_id,
+                    source: passenger_location,
+                    destination,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::DriverAvailable { driver_id } => self
+                .central_driver
+                .try_send(DriverAvailable { driver_id })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::CancelTrip { passenger_id } => self
+                .central_driver
+                .try_send(CancelTrip { passenger_id })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::TripCancelled { passenger_id } => self
+                .central_driver
+                .try_send(TripCancelled { passenger_id })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ReleaseTrip {
+                passenger_id,
+                driver_id,
+            } => self
+                .central_driver
+                .try_send(ReleaseTrip {
+                    passenger_id,
+                    driver_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::UpdateTripPosition {
+                passenger_id,
+                position,
+            } => self
+                .central_driver
+                .try_send(UpdateTripPosition {
+                    passenger_id,
+                    position,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::SurgeUpdate { term, multipliers } => self
+                .central_driver
+                .try_send(SurgeUpdate {
+                    term,
+                    leader_id: self.driver_id,
+                    multipliers,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::RecordTripTerms {
+                passenger_id,
+                terms,
+            } => self
+                .central_driver
+                .try_send(RecordTripTerms {
+                    passenger_id,
+                    terms,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ReplicateTripTerms {
+                term,
+                passenger_id,
+                terms,
+            } => self
+                .central_driver
+                .try_send(ReplicateTripTerms {
+                    term,
+                    leader_id: self.driver_id,
+                    passenger_id,
+                    terms,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::UpdatePoolRoute { driver_id, route } => self
+                .central_driver
+                .try_send(UpdatePoolRoute { driver_id, route })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ReplicatePoolRoute {
+                term,
+                driver_id,
+                route,
+            } => self
+                .central_driver
+                .try_send(ReplicatePoolRoute {
+                    term,
+                    leader_id: self.driver_id,
+                    driver_id,
+                    route,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::ScheduleTrip { booking } => self
+                .central_driver
+                .try_send(ScheduleTrip { booking })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
+            DriverMessages::CancelBooking {
+                passenger_id,
+                booking_id,
+            } => self
+                .central_driver
+                .try_send(CancelBooking {
+                    passenger_id,
+                    booking_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
         }
 
         Ok(())
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseConnection {}
+
+impl Handler<CloseConnection> for DriverConnection {
+    type Result = ();
+
+    /// Cierra la conexion con el driver deteniendo el actor, por ejemplo cuando es una conexion duplicada.
+    fn handle(&mut self, _msg: CloseConnection, ctx: &mut Context<Self>) -> Self::Result {
+        log::debug!("Closing a connection with driver {}", self.driver_id);
+        self.driver_write_stream = None;
+        ctx.stop();
+    }
+}
@@ -1,33 +1,80 @@
-use std::collections::{HashMap, VecDeque};
-
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, SpawnHandle};
-use actix_async_handler::async_handler;
-use common::utils::{json_parser::TripStatus, position::Position};
-use rayon::{
-    iter::{IntoParallelIterator, ParallelIterator},
-    slice::ParallelSliceMut,
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::Duration,
 };
 
+use actix::{Actor, ActorContext, Addr, AsyncContext, Context, Handler, Message, SpawnHandle};
+use common::utils::position::Position;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
 use crate::concu_driver::{
-    central_driver::{CanHandleTrip, ConnectWithPassenger, RemoveDriverFinder, SendTripResponse},
+    central_driver::{
+        CanHandleTrip, HandOffTrip, InformPassenger, ProposeAssignment, QueueTrip,
+        RemoveDriverFinder, RevokeOffer,
+    },
     consts::TAKE_TRIP_TIMEOUT_MS,
 };
 
-use super::{central_driver::CentralDriver, consts::MAX_DISTANCE};
+use super::{
+    central_driver::CentralDriver,
+    config::{env_duration_ms, env_or},
+    consts::{
+        DEFAULT_MAX_SEARCH_RADIUS, DEFAULT_OFFER_FANOUT, DEFAULT_SEARCH_GIVE_UP,
+        DEFAULT_SEARCH_RADIUS_STEP, MAX_DISTANCE,
+    },
+    dispatch::{Candidate, DispatchStrategy, DriverHistory},
+    json_parser::PendingTrip,
+    pool::PoolRoute,
+};
 
 pub struct DriverFinder {
     /// Direccion del actor CentralDriver
     central_driver: Addr<CentralDriver>,
-    /// Timeout para la recepcion de confirmacion de un driver
+    /// Id del driver que busca, al que le responden los drivers consultados
+    coordinator_id: u32,
+    /// Timeout para la recepcion de confirmacion de los drivers consultados en la ronda actual
     driver_ack_timeout: Option<SpawnHandle>,
+    /// Ofertas vigentes, con el id de la oferta segun el id del conductor consultado
+    outstanding_offers: HashMap<u32, u64>,
+    /// Numero de la ronda de ofertas actual
+    offer_round: u64,
+    /// Cantidad de conductores a los que se les ofrece el viaje a la vez
+    offer_fanout: usize,
+    /// Tiempo durante el cual es valida cada oferta
+    offer_lease: Duration,
     /// Id del pasajero
     passenger_id: Option<u32>,
     /// Posicion inicial del pasajero
     source: Position,
     /// Posicion destino del pasajero
     destination: Position,
-    /// Conductores cercanos a la posicion inicial del pasajero
+    /// Posiciones conocidas de los conductores, segun su id
+    driver_positions: HashMap<u32, Position>,
+    /// Historial de cada conductor, segun su id
+    histories: HashMap<u32, DriverHistory>,
+    /// Estrategia con la que se ordenan los conductores a consultar
+    strategy: Arc<dyn DispatchStrategy>,
+    /// Conductores cercanos a la posicion inicial del pasajero que falta consultar
     nearby_drivers: VecDeque<u32>,
+    /// Conductores consultados durante la busqueda
+    asked_drivers: HashSet<u32>,
+    /// Radio de busqueda actual (en cuadras)
+    search_radius: u32,
+    /// Cuanto se amplia el radio de busqueda en cada paso
+    search_radius_step: u32,
+    /// Radio de busqueda maximo
+    max_search_radius: u32,
+    /// Tiempo que se espera, con el radio maximo, a que se acerque algun conductor antes de rendirse
+    search_give_up: Duration,
+    /// Timeout para rendirse si no aparece ningun conductor con el radio maximo
+    give_up_timeout: Option<SpawnHandle>,
+    /// Zonas vecinas a las que pasarle la busqueda si ningun conductor cercano toma el viaje
+    handoff_zones: Vec<u32>,
+    /// Conductor asignado al pasajero en la asignacion en conjunto, el unico de la primera ronda
+    matched_driver: Option<u32>,
+    /// Conductores que ya soltaron el viaje del pasajero, a los que no se les vuelve a ofrecer
+    excluded_drivers: HashSet<u32>,
 }
 
 impl Actor for DriverFinder {
@@ -37,167 +84,489 @@ impl Actor for DriverFinder {
     fn started(&mut self, ctx: &mut Self::Context) {
         if let Some(pid) = self.passenger_id {
             log::debug!(
-                "[TRIP] Nearby drivers for passenger {}: {:?}",
+                "[TRIP] Nearby drivers for passenger {} ({}): {:?}",
                 pid,
+                self.strategy.name(),
                 self.nearby_drivers
             );
 
             ctx.notify(AskDrivers {
-                previous_driver: None,
+                previous_drivers: Vec::new(),
             });
         }
     }
 }
 
 impl DriverFinder {
-    /// Crea un nuevo struct DriverFinder
+    /// Crea un nuevo struct DriverFinder para el pedido de viaje dado.
+    /// Los conductores a consultar se ordenan con la estrategia de despacho dada, segun su posicion e historial.
+    /// La validez de cada oferta se configura con la variable de entorno OFFER_LEASE_MS, y la cantidad
+    /// de conductores consultados a la vez con OFFER_FANOUT.
+    /// La busqueda arranca con un radio de MAX_DISTANCE cuadras y se amplia de a SEARCH_RADIUS_STEP
+    /// cuadras hasta MAX_SEARCH_RADIUS. Con el radio maximo se espera SEARCH_GIVE_UP_MS milisegundos
+    /// a que se acerque algun conductor.
     pub fn new(
         central_driver: Addr<CentralDriver>,
-        passenger_id: u32,
-        source: Position,
-        destination: Position,
+        coordinator_id: u32,
+        trip: PendingTrip,
         driver_positions: HashMap<u32, Position>,
+        histories: HashMap<u32, DriverHistory>,
+        handoff_zones: Vec<u32>,
+        strategy: Arc<dyn DispatchStrategy>,
     ) -> Self {
-        Self {
+        let mut finder = Self {
             central_driver,
+            coordinator_id,
             driver_ack_timeout: None,
-            passenger_id: Some(passenger_id),
-            source,
-            destination,
-            nearby_drivers: Self::filter_nearby_drivers(&source, &driver_positions),
+            outstanding_offers: HashMap::new(),
+            offer_round: 0,
+            offer_fanout: env_or("OFFER_FANOUT", DEFAULT_OFFER_FANOUT).max(1),
+            offer_lease: env_duration_ms("OFFER_LEASE_MS", TAKE_TRIP_TIMEOUT_MS),
+            passenger_id: Some(trip.passenger_id),
+            source: trip.source,
+            destination: trip.destination,
+            nearby_drivers: VecDeque::new(),
+            driver_positions,
+            histories,
+            strategy,
+            asked_drivers: HashSet::new(),
+            search_radius: MAX_DISTANCE,
+            search_radius_step: env_or("SEARCH_RADIUS_STEP", DEFAULT_SEARCH_RADIUS_STEP).max(1),
+            max_search_radius: env_or("MAX_SEARCH_RADIUS", DEFAULT_MAX_SEARCH_RADIUS)
+                .max(MAX_DISTANCE),
+            search_give_up: env_duration_ms("SEARCH_GIVE_UP_MS", DEFAULT_SEARCH_GIVE_UP),
+            give_up_timeout: None,
+            handoff_zones,
+            matched_driver: None,
+            excluded_drivers: HashSet::new(),
+        };
+
+        finder.nearby_drivers = finder.rank_nearby_drivers(MAX_DISTANCE);
+        finder
+    }
+
+    /// Hace que la primera ronda de ofertas sea solo para el conductor dado, el que se le asigno al
+    /// pasajero en la asignacion en conjunto de los pedidos.
+    pub fn with_matched_driver(mut self, driver_id: u32) -> Self {
+        self.matched_driver = Some(driver_id);
+        self
+    }
+
+    /// Hace que no se le ofrezca el viaje a los conductores dados, los que ya soltaron el viaje del pasajero.
+    pub fn with_excluded_drivers(mut self, drivers: HashSet<u32>) -> Self {
+        self.nearby_drivers.retain(|did| !drivers.contains(did));
+        self.excluded_drivers = drivers;
+        self
+    }
+
+    /// Suma como candidatos a los conductores que ya llevan pasajeros, si les queda algun asiento libre y
+    /// sumar al pasajero les alarga la ruta a lo sumo `max_detour` cuadras. Se los considera en la
+    /// posicion en la que van.
+    pub fn with_pool_routes(mut self, routes: &HashMap<u32, PoolRoute>, max_detour: u32) -> Self {
+        let passenger_id = match self.passenger_id {
+            Some(pid) => pid,
+            None => return self,
+        };
+
+        for (driver_id, route) in routes {
+            let on_the_way = route.has_free_seat()
+                && route
+                    .plan(passenger_id, self.source, self.destination)
+                    .is_some_and(|(detour, _)| detour <= max_detour);
+
+            if on_the_way {
+                self.driver_positions.insert(*driver_id, route.position);
+            }
         }
+
+        self.nearby_drivers = self.rank_nearby_drivers(self.search_radius);
+        self
     }
 
-    /// Filtra los drivers cercanos a una posicion dada.
-    fn filter_nearby_drivers(
-        source: &Position,
-        driver_positions: &HashMap<u32, Position>,
-    ) -> VecDeque<u32> {
-        let mut distances = driver_positions
+    /// Filtra los drivers que estan a lo sumo a `radius` cuadras del pasajero (salvo los excluidos)
+    /// y los ordena con la estrategia de despacho.
+    fn rank_nearby_drivers(&self, radius: u32) -> VecDeque<u32> {
+        let source = self.source;
+
+        let mut candidates = self
+            .driver_positions
             .clone()
             .into_par_iter()
-            .map(|(k, v)| (k, v.distance_to(&source)))
-            .filter(|(_, v)| *v <= MAX_DISTANCE)
-            .collect::<Vec<(u32, u32)>>();
+            .filter(|(driver_id, position)| {
+                position.distance_to(&source) <= radius
+                    && !self.excluded_drivers.contains(driver_id)
+            })
+            .map(|(driver_id, position)| Candidate {
+                driver_id,
+                position,
+                history: self.histories.get(&driver_id).copied().unwrap_or_default(),
+            })
+            .collect::<Vec<Candidate>>();
 
-        distances.par_sort_by(|(_, a), (_, b)| a.cmp(&b));
+        self.strategy.rank(&source, &mut candidates);
 
-        let nearby_drivers = distances
-            .into_par_iter()
-            .map(|(k, _)| k)
-            .collect::<VecDeque<u32>>();
+        candidates.into_iter().map(|c| c.driver_id).collect()
+    }
+
+    /// Se llama cuando no quedan conductores por consultar con el radio actual.
+    /// - Si el radio no llego al maximo, lo amplia, le avisa al pasajero y consulta a los conductores
+    ///   que quedaron dentro del nuevo radio.
+    /// - Si el pasajero esta cerca de otras zonas, notifica el mensaje NoDrivers para pasarles la busqueda.
+    /// - Si no, espera a que se acerque algun conductor e inicia el timeout give_up_timeout, que al vencer
+    ///   notifica el mensaje GiveUp.
+    fn widen_search(&mut self, passenger_id: u32, ctx: &mut Context<Self>) {
+        if self.search_radius < self.max_search_radius {
+            self.search_radius =
+                (self.search_radius + self.search_radius_step).min(self.max_search_radius);
+
+            self.nearby_drivers = self
+                .rank_nearby_drivers(self.search_radius)
+                .into_iter()
+                .filter(|did| !self.asked_drivers.contains(did))
+                .collect();
+
+            log::info!(
+                "[TRIP] Widening the search for passenger {} to {} blocks: {:?}",
+                passenger_id,
+                self.search_radius,
+                self.nearby_drivers
+            );
 
-        nearby_drivers
+            self.central_driver.do_send(InformPassenger {
+                passenger_id,
+                detail: format!(
+                    "Looking for drivers up to {} blocks away from you",
+                    self.search_radius
+                ),
+            });
+
+            ctx.notify(AskDrivers {
+                previous_drivers: Vec::new(),
+            });
+            return;
+        }
+
+        if !self.handoff_zones.is_empty() {
+            ctx.notify(NoDrivers { passenger_id });
+            return;
+        }
+
+        if self.give_up_timeout.is_some() {
+            return;
+        }
+
+        log::info!(
+            "[TRIP] Waiting for a driver to get close to passenger {}",
+            passenger_id
+        );
+
+        self.central_driver.do_send(InformPassenger {
+            passenger_id,
+            detail: "There are no drivers available near your location yet, waiting for one"
+                .to_string(),
+        });
+
+        self.give_up_timeout = Some(ctx.notify_later(GiveUp {}, self.search_give_up));
+    }
+
+    /// Revoca las ofertas vigentes, para que los conductores consultados no tomen el viaje.
+    fn revoke_outstanding_offers(&mut self, passenger_id: u32) {
+        for (driver_id, offer_id) in self.outstanding_offers.drain() {
+            self.central_driver.do_send(RevokeOffer {
+                passenger_id,
+                driver_id,
+                offer_id,
+            });
+        }
     }
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 struct AskDrivers {
-    /// Id del conductor previamente consultado
-    previous_driver: Option<u32>,
+    /// Ids de los conductores consultados en la ronda anterior
+    previous_drivers: Vec<u32>,
 }
 
 impl Handler<AskDrivers> for DriverFinder {
     type Result = ();
 
-    /// Consulta uno por uno a los conductores desde el mas cercano al mas lejano (en rangod), para ver si
-    /// quieren / pueden tomar el viaje.
-    /// Inicia el timeout driver_ack_timeout de TAKE_TRIP_TIMEOUT_MS milisegundos que vuelve a notificar este mensaje.
+    /// Consulta por rondas a los conductores desde el mas cercano al mas lejano (en rango), para ver si
+    /// quieren / pueden tomar el viaje. En cada ronda se le ofrece el viaje a la vez a los OFFER_FANOUT
+    /// conductores mas cercanos que todavia no se consultaron, salvo la primera ronda de un pedido asignado
+    /// en conjunto, que es solo para el conductor asignado.
+    /// Cada consulta es una oferta con un id propio, valida por OFFER_LEASE_MS milisegundos (TAKE_TRIP_TIMEOUT_MS
+    /// por defecto). Inicia el timeout driver_ack_timeout que, al vencer las ofertas de la ronda, notifica
+    /// el mensaje OfferExpired.
+    /// Si no quedan conductores por consultar, amplia la busqueda (ver `widen_search`).
     fn handle(&mut self, msg: AskDrivers, ctx: &mut Context<Self>) -> Self::Result {
-        if self.passenger_id.is_none() {
+        if self.passenger_id.is_none() || !self.outstanding_offers.is_empty() {
             return;
         }
 
-        if let Some(prevd) = msg.previous_driver {
+        if !msg.previous_drivers.is_empty() {
             log::debug!(
-                "[TRIP] Driver {} can not take the trip or did not answer",
-                prevd
+                "[TRIP] Drivers {:?} can not take the trip or did not answer",
+                msg.previous_drivers
             )
         }
 
         let pid = self.passenger_id.unwrap();
 
-        let poped_id = self.nearby_drivers.pop_front();
+        let batch = match self.matched_driver.take() {
+            Some(did) => {
+                self.nearby_drivers.retain(|nearby| *nearby != did);
+                vec![did]
+            }
+            None => {
+                let count = self.offer_fanout.min(self.nearby_drivers.len());
+                self.nearby_drivers.drain(..count).collect::<Vec<u32>>()
+            }
+        };
 
-        if let None = poped_id {
-            ctx.notify(NoDrivers { passenger_id: pid });
+        if batch.is_empty() {
+            self.widen_search(pid, ctx);
             return;
         }
 
-        let did = poped_id.unwrap();
+        for did in batch {
+            let offer_id = rand::random::<u64>();
 
-        log::info!(
-            "[TRIP] Asking driver {} if it will take the trip for passenger {}",
-            did,
-            pid
-        );
+            log::info!(
+                "[TRIP] Asking driver {} if it will take the trip for passenger {} (offer {})",
+                did,
+                pid,
+                offer_id
+            );
 
-        let _ = self
-            .central_driver
-            .try_send(CanHandleTrip {
-                passenger_id: pid,
-                source: self.source,
-                destination: self.destination,
-                driver_id: did,
-            })
-            .inspect_err(|e| {
-                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-            });
+            let _ = self
+                .central_driver
+                .try_send(CanHandleTrip {
+                    passenger_id: pid,
+                    source: self.source,
+                    destination: self.destination,
+                    driver_id: did,
+                    coordinator_id: self.coordinator_id,
+                    offer_id,
+                    lease: self.offer_lease,
+                })
+                .inspect_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                });
+
+            self.asked_drivers.insert(did);
+            self.outstanding_offers.insert(did, offer_id);
+        }
 
+        self.offer_round += 1;
         self.driver_ack_timeout = Some(ctx.notify_later(
-            AskDrivers {
-                previous_driver: Some(did),
+            OfferExpired {
+                round: self.offer_round,
             },
-            TAKE_TRIP_TIMEOUT_MS,
+            self.offer_lease,
         ));
     }
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+struct OfferExpired {
+    /// Numero de la ronda de ofertas
+    round: u64,
+}
+
+impl Handler<OfferExpired> for DriverFinder {
+    type Result = ();
+
+    /// Vencen las ofertas de la ronda sin que ningun conductor acepte: se revocan las que siguen sin
+    /// respuesta, para que no las acepten tarde, y se consulta a los siguientes conductores.
+    fn handle(&mut self, msg: OfferExpired, ctx: &mut Context<Self>) -> Self::Result {
+        if msg.round != self.offer_round {
+            return;
+        }
+
+        self.driver_ack_timeout = None;
+
+        let previous_drivers = self.outstanding_offers.keys().copied().collect();
+
+        if let Some(pid) = self.passenger_id {
+            self.revoke_outstanding_offers(pid);
+        }
+
+        ctx.notify(AskDrivers { previous_drivers });
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct DriverACK {
+    /// Id del pasajero
+    pub passenger_id: u32,
     /// Id del conductor que envia el ACK
     pub driver_id: u32,
     /// Valor del ACK
     pub response: bool,
+    /// Id de la oferta que se responde
+    pub offer_id: u64,
 }
 
 impl Handler<DriverACK> for DriverFinder {
     type Result = ();
 
-    /// Recepcion de respuesta de un driver. Cancela el timeout driver_ack_timeout.
-    /// En caso afirmativo, se deja de buscar un conductor.
-    /// En caso negativo, el actor se notifica el mensaje AskDrivers para seguir consultando a los demas.
+    /// Recepcion de respuesta de un driver.
+    /// Si la respuesta no es de una oferta vigente (por ejemplo, si llego despues de que la oferta venciera
+    /// o de que otro conductor aceptara), se la ignora y, si el driver acepto, se le revoca la oferta.
+    /// En caso afirmativo gana la primera aceptacion: se cancela el timeout driver_ack_timeout, se revocan
+    /// las demas ofertas vigentes, se deja de buscar un conductor y se le propone la asignacion al lider,
+    /// que la registra en el log de asignaciones antes de confirmarle el viaje al driver.
+    /// En caso negativo, si ya respondieron todos los conductores de la ronda, se cancela el timeout y el
+    /// actor se notifica el mensaje AskDrivers para seguir consultando a los demas.
     fn handle(&mut self, msg: DriverACK, ctx: &mut Context<Self>) -> Self::Result {
-        if let Some(fut) = self.driver_ack_timeout.take() {
-            ctx.cancel_future(fut);
+        if self.outstanding_offers.get(&msg.driver_id) != Some(&msg.offer_id) {
+            if msg.response {
+                log::warn!(
+                    "[TRIP] Driver {} accepted the expired offer {} for passenger {}",
+                    msg.driver_id,
+                    msg.offer_id,
+                    msg.passenger_id
+                );
+
+                self.central_driver.do_send(RevokeOffer {
+                    passenger_id: msg.passenger_id,
+                    driver_id: msg.driver_id,
+                    offer_id: msg.offer_id,
+                });
+            }
+
+            return;
         }
 
+        self.outstanding_offers.remove(&msg.driver_id);
+
         if !msg.response {
-            ctx.notify(AskDrivers {
-                previous_driver: Some(msg.driver_id),
-            });
+            if self.outstanding_offers.is_empty() {
+                if let Some(fut) = self.driver_ack_timeout.take() {
+                    ctx.cancel_future(fut);
+                }
+
+                ctx.notify(AskDrivers {
+                    previous_drivers: vec![msg.driver_id],
+                });
+            }
             return;
         }
 
+        if let Some(fut) = self.driver_ack_timeout.take() {
+            ctx.cancel_future(fut);
+        }
+
+        if let Some(fut) = self.give_up_timeout.take() {
+            ctx.cancel_future(fut);
+        }
+
         if self.passenger_id.is_none() {
             return;
         }
 
         let pid = self.passenger_id.take().unwrap();
 
+        self.revoke_outstanding_offers(pid);
+
         log::info!(
             "[TRIP] Driver {} will take the trip for passenger {}",
             msg.driver_id,
             pid
         );
 
+        self.central_driver.do_send(ProposeAssignment {
+            passenger_id: pid,
+            driver_id: msg.driver_id,
+            source: self.source,
+            destination: self.destination,
+            offer_id: msg.offer_id,
+        });
+
         self.central_driver
             .do_send(RemoveDriverFinder { passenger_id: pid });
     }
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdateDriverPosition {
+    /// Id del conductor
+    pub driver_id: u32,
+    /// Nueva posicion del conductor
+    pub driver_position: Position,
+}
+
+impl Handler<UpdateDriverPosition> for DriverFinder {
+    type Result = ();
+
+    /// Actualiza la posicion conocida de un conductor. Los conductores excluidos nunca se consultan.
+    /// - Si el conductor quedo fuera del radio de busqueda (o esta en un viaje), deja de estar entre
+    ///   los conductores a consultar.
+    /// - Si quedo dentro del radio y no tiene una oferta vigente, se lo vuelve a agregar para consultarlo,
+    ///   aunque ya haya rechazado el viaje. Si no hay una ronda de ofertas en curso, se notifica el
+    ///   mensaje AskDrivers para reintentar la busqueda.
+    fn handle(&mut self, msg: UpdateDriverPosition, ctx: &mut Context<Self>) -> Self::Result {
+        self.driver_positions
+            .insert(msg.driver_id, msg.driver_position);
+
+        if self.passenger_id.is_none() || self.excluded_drivers.contains(&msg.driver_id) {
+            return;
+        }
+
+        let in_range = msg.driver_position.distance_to(&self.source) <= self.search_radius;
+
+        if !in_range {
+            self.nearby_drivers.retain(|did| *did != msg.driver_id);
+            return;
+        }
+
+        if self.outstanding_offers.contains_key(&msg.driver_id)
+            || self.nearby_drivers.contains(&msg.driver_id)
+        {
+            return;
+        }
+
+        self.nearby_drivers.push_back(msg.driver_id);
+
+        if self.outstanding_offers.is_empty() {
+            ctx.notify(AskDrivers {
+                previous_drivers: Vec::new(),
+            });
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct GiveUp {}
+
+impl Handler<GiveUp> for DriverFinder {
+    type Result = ();
+
+    /// Vence el tiempo de espera con el radio maximo sin que ningun conductor tome el viaje:
+    /// se revocan las ofertas vigentes y se notifica el mensaje NoDrivers.
+    fn handle(&mut self, _msg: GiveUp, ctx: &mut Context<Self>) -> Self::Result {
+        self.give_up_timeout = None;
+
+        let pid = match self.passenger_id {
+            Some(pid) => pid,
+            None => return,
+        };
+
+        if let Some(fut) = self.driver_ack_timeout.take() {
+            ctx.cancel_future(fut);
+        }
+
+        self.revoke_outstanding_offers(pid);
+
+        ctx.notify(NoDrivers { passenger_id: pid });
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 struct NoDrivers {
@@ -205,52 +574,67 @@ struct NoDrivers {
     passenger_id: u32,
 }
 
-#[async_handler]
 impl Handler<NoDrivers> for DriverFinder {
     type Result = ();
 
     /// Notifica al central driver que el viaje para el pasajero no tiene conductores libres cercanos.
-    async fn handle(&mut self, msg: NoDrivers, _ctx: &mut Context<Self>) -> Self::Result {
-        let pid = self.passenger_id.take().unwrap();
+    /// Si el pasajero esta cerca de otras zonas, le pide al central driver que les pase la busqueda.
+    /// Si no, le pide que encole el pedido hasta que se libere un driver o, si hay drivers libres lejos,
+    /// que le avise al pasajero que no hay drivers disponibles.
+    fn handle(&mut self, msg: NoDrivers, _ctx: &mut Context<Self>) -> Self::Result {
+        let pid = match self.passenger_id.take() {
+            Some(pid) => pid,
+            None => return,
+        };
+
+        if !self.handoff_zones.is_empty() {
+            self.central_driver
+                .do_send(RemoveDriverFinder { passenger_id: pid });
+
+            self.central_driver.do_send(HandOffTrip {
+                passenger_id: pid,
+                source: self.source,
+                destination: self.destination,
+                handoff_zones: std::mem::take(&mut self.handoff_zones),
+            });
+            return;
+        }
+
+        self.central_driver
+            .do_send(RemoveDriverFinder { passenger_id: pid });
 
-        let cd_addr = self.central_driver.clone();
+        self.central_driver.do_send(QueueTrip {
+            passenger_id: msg.passenger_id,
+            source: self.source,
+            destination: self.destination,
+        });
+    }
+}
 
-        let res = async move {
-            cd_addr
-                .send(ConnectWithPassenger {
-                    passenger_id: msg.passenger_id,
-                })
-                .await
-                .map_err(|e| {
-                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-                    e.to_string()
-                })
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StopSearch {}
+
+impl Handler<StopSearch> for DriverFinder {
+    type Result = ();
+
+    /// Frena la busqueda sin responderle al pasajero, por ejemplo cuando el driver deja de ser lider
+    /// y otro lider retoma la busqueda. Cancela los timeouts driver_ack_timeout y give_up_timeout, revoca
+    /// las ofertas vigentes y detiene el actor.
+    fn handle(&mut self, _msg: StopSearch, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(fut) = self.driver_ack_timeout.take() {
+            ctx.cancel_future(fut);
         }
-        .await;
-
-        match res {
-            Ok(Ok(_)) => {
-                let detail = format!("There are no drivers available near your location");
-
-                log::info!("There are no drivers near passenger {}", msg.passenger_id);
-
-                let _ = self
-                    .central_driver
-                    .try_send(SendTripResponse {
-                        status: TripStatus::Error,
-                        detail,
-                        passenger_id: msg.passenger_id,
-                    })
-                    .inspect_err(|e| {
-                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-                    });
-            }
-            _ => (),
+
+        if let Some(fut) = self.give_up_timeout.take() {
+            ctx.cancel_future(fut);
         }
 
-        self.passenger_id = None;
+        if let Some(pid) = self.passenger_id.take() {
+            log::info!("[TRIP] Stopping the search for passenger {}", pid);
+            self.revoke_outstanding_offers(pid);
+        }
 
-        self.central_driver
-            .do_send(RemoveDriverFinder { passenger_id: pid });
+        ctx.stop();
     }
 }
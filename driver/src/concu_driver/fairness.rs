@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Trabajo de un driver en la sesion
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DriverStats {
+    /// Viajes completados
+    pub completed_trips: u32,
+    /// Tiempo desde su ultimo viaje (o desde el inicio de la sesion si no tuvo ninguno), cero si esta en un viaje
+    pub idle_time: Duration,
+}
+
+/// Registro del trabajo de cada driver, a partir de las asignaciones confirmadas del log.
+pub struct FairnessTracker {
+    /// Momento en el que empezo la sesion
+    session_start: Instant,
+    /// Viajes completados por cada driver, segun su id
+    completed_trips: HashMap<u32, u32>,
+    /// Momento en el que cada driver termino su ultimo viaje, segun su id
+    last_trip_end: HashMap<u32, Instant>,
+    /// Driver que lleva a cada pasajero, segun el id del pasajero
+    active_trips: HashMap<u32, u32>,
+}
+
+impl FairnessTracker {
+    /// Crea un registro vacio, con la sesion empezando ahora.
+    pub fn new(session_start: Instant) -> Self {
+        Self {
+            session_start,
+            completed_trips: HashMap::new(),
+            last_trip_end: HashMap::new(),
+            active_trips: HashMap::new(),
+        }
+    }
+
+    /// Registra que se le asigno a un driver el viaje de un pasajero.
+    pub fn record_assignment(&mut self, passenger_id: u32, driver_id: u32) {
+        self.active_trips.insert(passenger_id, driver_id);
+    }
+
+    /// Registra que termino el viaje de un pasajero y devuelve el driver que lo llevo, si se lo conoce.
+    pub fn record_completion(&mut self, passenger_id: u32, now: Instant) -> Option<u32> {
+        let driver_id = self.active_trips.remove(&passenger_id)?;
+
+        *self.completed_trips.entry(driver_id).or_default() += 1;
+        self.last_trip_end.insert(driver_id, now);
+
+        Some(driver_id)
+    }
+
+    /// Registra que el driver solto el viaje de un pasajero sin llevarlo, por lo que no cuenta como completado.
+    pub fn record_release(&mut self, passenger_id: u32) {
+        self.active_trips.remove(&passenger_id);
+    }
+
+    /// Devuelve el trabajo de un driver en la sesion.
+    pub fn stats(&self, driver_id: u32, now: Instant) -> DriverStats {
+        let busy = self.active_trips.values().any(|id| *id == driver_id);
+
+        let idle_since = self
+            .last_trip_end
+            .get(&driver_id)
+            .copied()
+            .unwrap_or(self.session_start);
+
+        DriverStats {
+            completed_trips: self
+                .completed_trips
+                .get(&driver_id)
+                .copied()
+                .unwrap_or_default(),
+            idle_time: match busy {
+                true => Duration::ZERO,
+                false => now.saturating_duration_since(idle_since),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_since_session_start() {
+        let start = Instant::now();
+        let tracker = FairnessTracker::new(start);
+
+        let stats = tracker.stats(1, start + Duration::from_secs(30));
+        assert_eq!(stats.completed_trips, 0);
+        assert_eq!(stats.idle_time, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_completed_trips_reset_idle_time() {
+        let start = Instant::now();
+        let mut tracker = FairnessTracker::new(start);
+
+        tracker.record_assignment(7, 1);
+        assert_eq!(
+            tracker.stats(1, start + Duration::from_secs(5)).idle_time,
+            Duration::ZERO
+        );
+
+        assert_eq!(
+            tracker.record_completion(7, start + Duration::from_secs(10)),
+            Some(1)
+        );
+        let stats = tracker.stats(1, start + Duration::from_secs(12));
+        assert_eq!(stats.completed_trips, 1);
+        assert_eq!(stats.idle_time, Duration::from_secs(2));
+
+        assert_eq!(tracker.record_completion(7, start), None);
+        assert_eq!(tracker.stats(1, start).completed_trips, 1);
+    }
+
+    #[test]
+    fn test_released_trips_are_not_completed() {
+        let start = Instant::now();
+        let mut tracker = FairnessTracker::new(start);
+
+        tracker.record_assignment(7, 1);
+        tracker.record_release(7);
+
+        let stats = tracker.stats(1, start + Duration::from_secs(4));
+        assert_eq!(stats.completed_trips, 0);
+        assert_eq!(stats.idle_time, Duration::from_secs(4));
+        assert_eq!(tracker.record_completion(7, start), None);
+    }
+}
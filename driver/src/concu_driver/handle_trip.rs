@@ -1,26 +1,88 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
 use crate::concu_driver::{
-    central_driver::{CollectMoneyPassenger, SendTripResponse},
-    consts::{DEFAULT_TAKE_TRIP_PROBABILTY, TRIP_GO_TO_SLEEP},
+    central_driver::{
+        CollectMoneyPassenger, DisconnectPassenger, DriverAvailable, FinishAssignment, ReleaseTrip,
+        SendTripResponse, UpdatePoolRoute, UpdateTripPosition,
+    },
+    consts::{
+        DEFAULT_MAX_POOL_DETOUR, DEFAULT_RELEASE_TRIP_PROBABILITY, DEFAULT_SEAT_CAPACITY,
+        DEFAULT_TAKE_TRIP_PROBABILTY, TRIP_GO_TO_SLEEP,
+    },
+    json_parser::TripTerms,
+    pool::{PoolRoute, Stop, StopKind},
 };
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message};
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, SpawnHandle};
 use actix_async_handler::async_handler;
-use common::utils::{json_parser::TripStatus, position::Position};
+use common::utils::{json_parser::TripStatus, position::Position, pricing::Pricing};
 use rand::Rng;
+use tokio::sync::oneshot;
 
 use super::{
     central_driver::{CentralDriver, ConnectWithPassenger, NotifyPositionToLeader},
-    consts::POSITION_NOTIFICATION_INTERVAL,
+    config::{env_duration_ms, env_or, pricing_from_env},
+    consts::{DEFAULT_ASSIGNMENT_CONFIRM_TIMEOUT, POSITION_NOTIFICATION_INTERVAL},
 };
 
+/// Pasajero que lleva o va a buscar un driver
+struct Rider {
+    /// Id de la oferta por la que se tomo el viaje
+    offer_id: u64,
+    /// Posicion inicial del pasajero
+    source: Position,
+    /// Posicion destino del pasajero
+    destination: Position,
+    /// Paradas intermedias del pasajero, en orden
+    waypoints: Vec<Position>,
+    /// Multiplicador por demanda que acepto el pasajero
+    surge: f64,
+    /// Si el pasajero ya subio al auto
+    picked_up: bool,
+    /// Momento en el que llego a la puerta del pasajero, mientras lo espera
+    pickup_arrival: Option<Instant>,
+    /// Tiempo que espero al pasajero en su puerta
+    waiting_time: Duration,
+    /// Cuadras que le corresponde pagar al pasajero, repartiendo cada tramo entre los que iban en el auto
+    shared_blocks: f64,
+}
+
 pub struct TripHandler {
     /// Direccion del actor CentralDriver
     central_driver: Addr<CentralDriver>,
-    /// Posicion actual del driver
+    /// Id de este driver
+    id: u32,
+    /// Posicion actual del driver, None mientras recorre su ruta
     current_location: Option<Position>,
-    /// Id del pasajero actual
-    passenger_id: Option<u32>,
+    /// Pasajeros que lleva o va a buscar el driver, segun su id
+    riders: HashMap<u32, Rider>,
+    /// Paradas que faltan recorrer, en orden, para buscar y dejar a los pasajeros
+    stops: Vec<Stop>,
+    /// Posicion del driver en su ruta
+    route_position: Position,
+    /// Numero de la ruta actual, para descartar los pasos de una ruta anterior
+    route_id: u64,
+    /// Asientos del auto, con 1 no se comparten viajes
+    seat_capacity: usize,
+    /// Cuadras que puede alargarse la ruta para sumar a un pasajero
+    max_pool_detour: u32,
     // Variable de entorno 'TEST' para simplificar casos de interes a la hora de testear
     test_env_var: Result<String, std::env::VarError>,
+    /// Si el driver se esta apagando, en cuyo caso no acepta nuevos viajes
+    draining: bool,
+    /// Canal por el que se avisa que el driver quedo libre y puede apagarse
+    idle_tx: Option<oneshot::Sender<()>>,
+    /// Oferta aceptada que espera la confirmacion del lider, segun su id,
+    /// junto con el timeout tras el cual se la libera
+    reserved_trip: Option<(u64, SpawnHandle)>,
+    /// Tiempo maximo que se espera la confirmacion de un viaje aceptado
+    confirm_timeout: Duration,
+    /// Probabilidad de soltar el viaje en cada paso camino a buscar al pasajero
+    release_trip_probability: f64,
+    /// Tarifas con las que se cobran los viajes
+    pricing: Pricing,
 }
 
 impl Actor for TripHandler {
@@ -38,6 +100,11 @@ impl TripHandler {
     /// - El ID del driver.
     /// - El valor de la variable de entorno TEST, si esta esta definida
     /// - Una posicion inicial aleatoria o fija en caso de que este definida la variable de entorno TEST
+    /// - El tiempo maximo de espera de la confirmacion de un viaje, de la variable de entorno ASSIGNMENT_CONFIRM_TIMEOUT_MS
+    /// - La probabilidad de soltar un viaje antes de buscar al pasajero, de la variable de entorno RELEASE_TRIP_PROBABILITY
+    /// - Las tarifas de los viajes (ver `pricing_from_env`)
+    /// - Los asientos del auto, de la variable de entorno SEAT_CAPACITY, y el desvio maximo para sumar
+    ///   pasajeros, de MAX_POOL_DETOUR
     pub fn new(central_driver: Addr<CentralDriver>, self_id: u32) -> Self {
         let test_env_var: Result<String, std::env::VarError> = std::env::var("TEST");
 
@@ -48,13 +115,318 @@ impl TripHandler {
 
         Self {
             central_driver,
+            id: self_id,
             current_location: Some(pos),
-            passenger_id: None,
+            riders: HashMap::new(),
+            stops: Vec::new(),
+            route_position: pos,
+            route_id: 0,
+            seat_capacity: env_or("SEAT_CAPACITY", DEFAULT_SEAT_CAPACITY).max(1),
+            max_pool_detour: env_or("MAX_POOL_DETOUR", DEFAULT_MAX_POOL_DETOUR),
             test_env_var,
+            draining: false,
+            idle_tx: None,
+            reserved_trip: None,
+            confirm_timeout: env_duration_ms(
+                "ASSIGNMENT_CONFIRM_TIMEOUT_MS",
+                DEFAULT_ASSIGNMENT_CONFIRM_TIMEOUT,
+            ),
+            release_trip_probability: env_or(
+                "RELEASE_TRIP_PROBABILITY",
+                DEFAULT_RELEASE_TRIP_PROBABILITY,
+            )
+            .clamp(0.0, 1.0),
+            pricing: pricing_from_env(),
+        }
+    }
+
+    /// Ruta actual del auto, con los pasajeros que van en el
+    fn pool_route(&self) -> PoolRoute {
+        PoolRoute {
+            position: self.route_position,
+            stops: self.stops.clone(),
+            aboard: self.riders.values().filter(|rider| rider.picked_up).count(),
+            capacity: self.seat_capacity,
+        }
+    }
+
+    /// Busca donde sumar a un pasajero a la ruta actual, si el auto comparte viajes y el desvio no supera
+    /// MAX_POOL_DETOUR cuadras. Devuelve las nuevas paradas.
+    fn plan_pool(
+        &self,
+        passenger_id: u32,
+        source: Position,
+        destination: Position,
+    ) -> Option<Vec<Stop>> {
+        if self.seat_capacity <= 1 {
+            return None;
         }
+
+        self.pool_route()
+            .plan(passenger_id, source, destination)
+            .filter(|(detour, _)| *detour <= self.max_pool_detour)
+            .map(|(_, stops)| stops)
+    }
+
+    /// Le envia al lider la ruta del auto si todavia puede sumar pasajeros, o None si no, para que le
+    /// ofrezca pedidos que le queden de camino. Si el auto no comparte viajes, no hace nada.
+    fn report_route(&self) {
+        if self.seat_capacity <= 1 {
+            return;
+        }
+
+        let route = Some(self.pool_route())
+            .filter(|route| !self.draining && !self.riders.is_empty() && route.has_free_seat());
+
+        let _ = self
+            .central_driver
+            .try_send(UpdatePoolRoute {
+                driver_id: self.id,
+                route,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+    }
+
+    /// Saca a un pasajero y sus paradas de la ruta. Si no quedan pasajeros, el driver vuelve a tener
+    /// una posicion fija. Devuelve si el pasajero estaba en la ruta.
+    fn drop_rider(&mut self, passenger_id: u32) -> bool {
+        if self.riders.remove(&passenger_id).is_none() {
+            return false;
+        }
+
+        self.stops.retain(|stop| stop.passenger_id != passenger_id);
+
+        if self.riders.is_empty() && self.current_location.is_none() {
+            self.current_location = Some(self.route_position);
+        }
+
+        self.report_route();
+        true
+    }
+
+    /// Saca al pasajero de la ruta y le avisa al lider que termino su viaje, para que libere la asignacion.
+    /// Si no quedan pasajeros, queda disponible (ver `become_available`).
+    fn release_trip(&mut self, passenger_id: u32) {
+        let offer_id = match self.riders.get(&passenger_id) {
+            Some(rider) => rider.offer_id,
+            None => return,
+        };
+
+        self.drop_rider(passenger_id);
+
+        let _ = self
+            .central_driver
+            .try_send(FinishAssignment {
+                passenger_id,
+                offer_id,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        if self.riders.is_empty() {
+            self.become_available();
+        }
+    }
+
+    /// Suelta el viaje de un pasajero antes de buscarlo, como si se hubiera roto el auto: le avisa al
+    /// pasajero y cierra la conexion con el, para que espere a otro driver, y le pide al lider que le
+    /// busque otro. Luego queda disponible (ver `become_available`).
+    fn release_before_pickup(&mut self, passenger_id: u32, current_position: Position) {
+        log::warn!(
+            "[TRIP] The car broke down, releasing the trip for passenger {}",
+            passenger_id
+        );
+
+        let _ = self
+            .central_driver
+            .try_send(SendTripResponse {
+                passenger_id,
+                status: TripStatus::Info,
+                detail: format!(
+                    "Sorry, driver {} can't pick you up. Looking for another driver",
+                    self.id
+                ),
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        let _ = self
+            .central_driver
+            .try_send(DisconnectPassenger { passenger_id })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        let _ = self
+            .central_driver
+            .try_send(ReleaseTrip {
+                passenger_id,
+                driver_id: self.id,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        self.route_position = current_position;
+        self.drop_rider(passenger_id);
+
+        self.become_available();
+    }
+
+    /// Reparte las cuadras recorridas en un paso entre los pasajeros que van en el auto.
+    fn share_blocks(&mut self, blocks: u32) {
+        let aboard = self.riders.values().filter(|rider| rider.picked_up).count();
+
+        if aboard == 0 {
+            return;
+        }
+
+        for rider in self.riders.values_mut().filter(|rider| rider.picked_up) {
+            rider.shared_blocks += blocks as f64 / aboard as f64;
+        }
+    }
+
+    /// Llega a la puerta de un pasajero: le avisa y empieza a contar el tiempo que lo espera.
+    fn pick_up(&mut self, passenger_id: u32) {
+        let rider = match self.riders.get_mut(&passenger_id) {
+            Some(rider) => rider,
+            None => return,
+        };
+
+        rider.picked_up = true;
+        rider.pickup_arrival = Some(Instant::now());
+
+        let _ = self
+            .central_driver
+            .try_send(SendTripResponse {
+                passenger_id,
+                status: TripStatus::Info,
+                detail: "I am at your door, come out!".to_string(),
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        log::info!("[TRIP] Passenger {} picked up", passenger_id);
+    }
+
+    /// Llega a una parada intermedia de un pasajero: le avisa cuantas paradas le quedan y hacia donde sigue.
+    fn reach_waypoint(&mut self, passenger_id: u32, position: Position) {
+        let total = match self.riders.get(&passenger_id) {
+            Some(rider) => rider.waypoints.len(),
+            None => return,
+        };
+
+        let next = self
+            .stops
+            .iter()
+            .find(|stop| stop.passenger_id == passenger_id)
+            .map(|stop| stop.position);
+        let remaining = self
+            .stops
+            .iter()
+            .filter(|stop| stop.passenger_id == passenger_id && stop.kind == StopKind::Waypoint)
+            .count();
+
+        log::info!(
+            "[TRIP] Reached stop {} of {} for passenger {} at {:?}",
+            total - remaining,
+            total,
+            passenger_id,
+            position
+        );
+
+        let detail = match next {
+            Some(next) => format!(
+                "We reached stop {} of {}, now heading to {:?}",
+                total - remaining,
+                total,
+                next
+            ),
+            None => format!("We reached stop {} of {}", total - remaining, total),
+        };
+
+        let _ = self
+            .central_driver
+            .try_send(SendTripResponse {
+                passenger_id,
+                status: TripStatus::Info,
+                detail,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+    }
+
+    /// Llega al destino de un pasajero: le avisa el precio de su parte del viaje, solicita el cobro
+    /// (ver `Pricing::shared_quote` y `Pricing::fare`) y libera su viaje (ver `release_trip`).
+    fn drop_off(&mut self, passenger_id: u32) {
+        let rider = match self.riders.get(&passenger_id) {
+            Some(rider) => rider,
+            None => return,
+        };
+
+        log::info!(
+            "[TRIP] Arrived at destination for passenger {}",
+            passenger_id
+        );
+
+        let quote = self.pricing.route_quote(
+            &rider.source,
+            &rider.waypoints,
+            &rider.destination,
+            rider.surge,
+        );
+        let share = self.pricing.shared_quote(&quote, rider.shared_blocks);
+        let amount = self.pricing.fare(&share, rider.waiting_time);
+
+        log::info!(
+            "[PRICING] Fare for passenger {}: ${} (x{} surge, {} of {} blocks, waited {:?})",
+            passenger_id,
+            amount,
+            quote.surge,
+            share.distance,
+            quote.distance,
+            rider.waiting_time
+        );
+
+        let detail = format!(
+            "We have arrived at our destination, we hope you enjoyed the trip. Your fare is ${}",
+            amount
+        );
+
+        let _ = self
+            .central_driver
+            .try_send(SendTripResponse {
+                passenger_id,
+                status: TripStatus::Success,
+                detail,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        let _ = self
+            .central_driver
+            .try_send(CollectMoneyPassenger {
+                passenger_id,
+                amount,
+                cancellation_fee: false,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        self.release_trip(passenger_id);
+    }
+
+    /// Si el driver se esta apagando, avisa que ya quedo libre. Si no, notifica su posicion y le avisa
+    /// al lider que esta disponible, para que despache los pedidos en espera.
+    fn become_available(&mut self) {
+        if let Some(idle_tx) = self.idle_tx.take() {
+            log::info!("Trip finished, now i can rest");
+            let _ = idle_tx.send(());
+            return;
+        }
+
+        log::info!("Now i'm ready for another trip!");
+
+        self.notify_pos();
+
+        let _ = self
+            .central_driver
+            .try_send(DriverAvailable { driver_id: self.id })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
     }
 
     /// Simula la posición del driver y la notifica al `CentralDriver`.
+    /// Si el driver se esta apagando notifica que se encuentra en el 'infinito', para que no se le ofrezcan viajes.
     fn notify_pos(&mut self) {
         if let Some(mut position) = self.current_location.take() {
             // Simulate position change
@@ -63,11 +435,14 @@ impl TripHandler {
                 Err(_) => position.simulate(),
             }
 
+            let driver_location = match self.draining {
+                true => Position::infinity(),
+                false => position.clone(),
+            };
+
             let _ = self
                 .central_driver
-                .try_send(NotifyPositionToLeader {
-                    driver_location: position.clone(),
-                })
+                .try_send(NotifyPositionToLeader { driver_location })
                 .inspect_err(|e| {
                     log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
                 });
@@ -82,113 +457,98 @@ impl TripHandler {
 struct GoTo {
     /// Posicion actual del driver
     current_position: Position,
-    /// Posicion destino siguiente
-    next_position: Position,
-    /// Id del pasajero
-    passenger_id: u32,
-    /// Posicion inicial del pasajero
-    passenger_location: Position,
-    /// Posicion destino del pasajero
-    destination: Position,
+    /// Numero de la ruta que se recorre
+    route_id: u64,
 }
 
 impl Handler<GoTo> for TripHandler {
     type Result = ();
 
-    /// - Simula un viaje, luego de una simulacion se encola nuevamente este mensaje para continuar la simulacion
-    ///     hasta el destino del pasajero
-    /// - Se mueve hasta la posición del pasajero.
-    /// - Le notifica al Central Driver que llego a la posición del pasajero
-    /// - Se mueve hasta la posición de destino.
-    /// - Le notifica al Central Driver que llego a la posición destino
-    /// - Le notifica al Central Driver para que solicite el cobro del viaje realizado
-    /// - Limpia el estado del viaje.
+    /// - Simula la ruta del auto, luego de una simulacion se encola nuevamente este mensaje para continuar la
+    ///   simulacion hasta dejar al ultimo pasajero
+    /// - Se mueve hacia la proxima parada. Si lleva a un solo pasajero y todavia no lo busco, en cada paso
+    ///   puede soltar el viaje con probabilidad RELEASE_TRIP_PROBABILITY, para que el lider le busque otro
+    ///   driver al pasajero.
+    /// - Reparte las cuadras recorridas entre los pasajeros que van en el auto.
+    /// - En la puerta de un pasajero, le notifica al Central Driver que llego y cuenta el tiempo que lo espera
+    /// - En cada parada intermedia de un pasajero, le avisa al pasajero que termino ese tramo (ver `reach_waypoint`)
+    /// - En el destino de un pasajero, le notifica al Central Driver que llego, con el precio de su parte del
+    ///   viaje, y que solicite el cobro (ver `drop_off`)
+    /// - Le avisa al Central Driver la posicion de los pasajeros que van en el auto en cada paso, para que el
+    ///   lider pueda buscarles otro driver si se pierde la conexion con este.
+    /// - Cuando deja al ultimo pasajero, queda disponible.
     fn handle(&mut self, msg: GoTo, ctx: &mut Context<Self>) -> Self::Result {
-        if let None = self.passenger_id {
-            self.current_location = Some(msg.current_position);
+        if msg.route_id != self.route_id || self.riders.is_empty() {
+            return;
+        }
+
+        let next_stop = match self.stops.first() {
+            Some(stop) => *stop,
+            None => return,
+        };
+
+        if next_stop.kind == StopKind::Pickup
+            && self.riders.len() == 1
+            && rand::thread_rng().gen_bool(self.release_trip_probability)
+        {
+            self.release_before_pickup(next_stop.passenger_id, msg.current_position);
             return;
         }
 
+        for rider in self.riders.values_mut() {
+            if let Some(arrival) = rider.pickup_arrival.take() {
+                rider.waiting_time = arrival.elapsed();
+            }
+        }
+
         let mut current_position = msg.current_position;
-        current_position.go_to(&msg.next_position);
+        current_position.go_to(&next_stop.position);
         log::debug!(
-            "Current {:?} -> Destination {:?}",
+            "Current {:?} -> Next stop {:?}",
             current_position,
-            msg.next_position
+            next_stop.position
         );
 
-        if current_position == msg.passenger_location {
-            let _ = self
-                .central_driver
-                .try_send(SendTripResponse {
-                    passenger_id: msg.passenger_id,
-                    status: TripStatus::Info,
-                    detail: format!("I am at your door, come out!"),
-                })
-                .inspect_err(|e| {
-                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
-                });
+        self.share_blocks(msg.current_position.distance_to(&current_position));
+        self.route_position = current_position;
 
-            log::info!("[TRIP] Passenger {} picked up", msg.passenger_id);
+        while let Some(stop) = self
+            .stops
+            .first()
+            .copied()
+            .filter(|stop| stop.position == current_position)
+        {
+            self.stops.remove(0);
 
-            ctx.notify_later(
-                GoTo {
-                    current_position,
-                    next_position: msg.destination,
-                    passenger_id: msg.passenger_id,
-                    passenger_location: msg.passenger_location,
-                    destination: msg.destination,
-                },
-                TRIP_GO_TO_SLEEP,
-            );
+            match stop.kind {
+                StopKind::Pickup => self.pick_up(stop.passenger_id),
+                StopKind::Waypoint => self.reach_waypoint(stop.passenger_id, stop.position),
+                StopKind::Dropoff => self.drop_off(stop.passenger_id),
+            }
+        }
 
+        if self.riders.is_empty() {
             return;
-        } else if current_position == msg.destination {
-            log::info!(
-                "[TRIP] Arrived at destination for passenger {}",
-                msg.passenger_id
-            );
-
-            let detail =
-                format!("We have arrived at our destination, we hope you enjoyed the trip");
-
-            let _ = self
-                .central_driver
-                .try_send(SendTripResponse {
-                    passenger_id: msg.passenger_id,
-                    status: TripStatus::Success,
-                    detail,
-                })
-                .inspect_err(|e| {
-                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
-                });
+        }
 
+        for (passenger_id, _) in self.riders.iter().filter(|(_, rider)| rider.picked_up) {
             let _ = self
                 .central_driver
-                .try_send(CollectMoneyPassenger {
-                    passenger_id: msg.passenger_id,
+                .try_send(UpdateTripPosition {
+                    passenger_id: *passenger_id,
+                    position: current_position,
                 })
                 .inspect_err(|e| {
                     log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
                 });
-
-            ctx.notify(ClearPassenger {
-                disconnected: false,
-                passenger_id: msg.passenger_id,
-            });
-
-            self.current_location = Some(current_position);
-
-            return;
         }
 
+        self.report_route();
+
         ctx.notify_later(
             GoTo {
                 current_position,
-                next_position: msg.next_position,
-                passenger_id: msg.passenger_id,
-                passenger_location: msg.passenger_location,
-                destination: msg.destination,
+                route_id: msg.route_id,
             },
             TRIP_GO_TO_SLEEP,
         );
@@ -206,22 +566,36 @@ pub struct CanHandleTrip {
     pub destination: Position,
     /// Id de este driver
     pub self_id: u32,
+    /// Id del driver que ofrece el viaje
+    pub coordinator_id: u32,
+    /// Id de la oferta
+    pub offer_id: u64,
+    /// Tiempo durante el cual la oferta es valida
+    pub lease: Duration,
 }
 
-#[async_handler]
 impl Handler<CanHandleTrip> for TripHandler {
     type Result = ();
 
     /// Maneja los mensajes recibidos desde el pasajero.
     /// Simula la situación de si el driver puede tomar el viaje o no.
-    /// - Si el driver puede tomar el viaje, se conecta con el Central Driver y le envia el mensaje `ConnectWithPassenger` para que se conecte con el pasajero.
-    ///     - Si la conexión fue exitosa, le envia un mensaje al Central Driver con el mensaje `SendTripResponse` para notificarle al pasajero que el driver esta en camino.
-    ///     - Ademas de notificarle que se encuentra en el 'infinito' con el fin de que no sea tomado en cuenta para proximos viajes
-    ///     - Inicia el viaje enviando un mensaje al actor con el mensaje `GoTo`.
-    /// - Si el driver no puede tomar el viaje, retorna `false` al CentralDriver con el mensaje 'CanHandleTripACK'.
-    async fn handle(&mut self, msg: CanHandleTrip, _ctx: &mut Context<Self>) -> Self::Result {
+    /// - Si el driver puede tomar el viaje, reserva la oferta hasta que el lider le confirme la asignacion con
+    ///   el mensaje `ConfirmTrip`, se la revoquen con `RevokeOffer`, o hasta que venzan la validez de la oferta
+    ///   y ASSIGNMENT_CONFIRM_TIMEOUT_MS. Mientras tanto rechaza otros viajes.
+    /// - Si ya lleva pasajeros, solo puede tomar el viaje si le queda algun asiento libre y le queda de
+    ///   camino (ver `plan_pool`).
+    /// - Responde al CentralDriver con el mensaje 'CanHandleTripACK', `false` si no puede tomar el viaje,
+    ///   se esta apagando o ya tiene un viaje reservado.
+    fn handle(&mut self, msg: CanHandleTrip, ctx: &mut Context<Self>) -> Self::Result {
         let mut rng = rand::thread_rng();
-        let response = self.passenger_id.is_none()
+        let on_the_way = self.riders.is_empty()
+            || self
+                .plan_pool(msg.passenger_id, msg.passenger_location, msg.destination)
+                .is_some();
+        let response = !self.draining
+            && !self.riders.contains_key(&msg.passenger_id)
+            && on_the_way
+            && self.reserved_trip.is_none()
             && rng.gen_bool(
                 std::env::var("TAKE_TRIP_PROBABILITY")
                     .unwrap_or(DEFAULT_TAKE_TRIP_PROBABILTY.to_string())
@@ -229,18 +603,130 @@ impl Handler<CanHandleTrip> for TripHandler {
                     .unwrap_or(DEFAULT_TAKE_TRIP_PROBABILTY),
             );
 
-        let pid = msg.passenger_id.clone();
-        let did = msg.self_id.clone();
+        if response {
+            log::debug!("[TRIP] Holding the trip for passenger {}", msg.passenger_id);
+
+            let expiration = ctx.notify_later(
+                ExpireReservation {
+                    passenger_id: msg.passenger_id,
+                    offer_id: msg.offer_id,
+                },
+                msg.lease + self.confirm_timeout,
+            );
+
+            self.reserved_trip = Some((msg.offer_id, expiration));
+        }
+
+        let _ = self
+            .central_driver
+            .try_send(super::central_driver::CanHandleTripACK {
+                passenger_id: msg.passenger_id,
+                response,
+                driver_id: msg.self_id,
+                coordinator_id: msg.coordinator_id,
+                offer_id: msg.offer_id,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+    }
+}
 
-        let res = if response {
-            let result = self
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ConfirmTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Posicion inicial del pasajero
+    pub passenger_location: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+    /// Id de la oferta que acepto el driver
+    pub offer_id: u64,
+    /// Condiciones que acepto el pasajero: el multiplicador por demanda y las paradas intermedias
+    pub terms: TripTerms,
+}
+
+#[async_handler]
+impl Handler<ConfirmTrip> for TripHandler {
+    type Result = ();
+
+    /// Maneja la confirmacion del lider de que el viaje le fue asignado a este driver.
+    /// - Si ya esta llevando a ese pasajero (por ejemplo, si un nuevo lider vuelve a confirmar el viaje), no hace nada.
+    /// - Si ya lleva a otros pasajeros, busca donde sumar al nuevo a la ruta (ver `plan_pool`).
+    /// - Se conecta con el Central Driver y le envia el mensaje `ConnectWithPassenger` para que se conecte con el pasajero.
+    ///     - Si la conexión fue exitosa, le envia un mensaje al Central Driver con el mensaje `SendTripResponse` para notificarle al pasajero que el driver esta en camino.
+    ///     - Si ya lleva a otros pasajeros, suma las paradas del nuevo a la ruta y les avisa que se desvia a buscarlo.
+    ///     - Si no, notifica que se encuentra en el 'infinito' con el fin de que no sea tomado en cuenta para proximos viajes
+    ///       (solo se le ofrecen los que le queden de camino) e inicia la ruta, pasando por las paradas intermedias del
+    ///       pasajero, enviando un mensaje al actor con el mensaje `GoTo`.
+    /// - Los viajes con paradas intermedias no se suman a la ruta de otros pasajeros.
+    /// - Si no pudo iniciar el viaje, le avisa al lider con el mensaje `FinishAssignment` para liberar la asignacion.
+    async fn handle(&mut self, msg: ConfirmTrip, _ctx: &mut Context<Self>) -> Self::Result {
+        let new_rider = !self.riders.contains_key(&msg.passenger_id);
+
+        if new_rider {
+            if let Some((_, expiration)) = self
+                .reserved_trip
+                .take_if(|(offer_id, _)| *offer_id == msg.offer_id)
+            {
+                _ctx.cancel_future(expiration);
+            }
+        }
+
+        let pooled_stops = match !new_rider || self.riders.is_empty() || !msg.terms.stops.is_empty()
+        {
+            true => None,
+            false => self.plan_pool(msg.passenger_id, msg.passenger_location, msg.destination),
+        };
+        let fits = self.riders.is_empty() || pooled_stops.is_some();
+
+        if new_rider && !fits {
+            log::error!(
+                "{}:{}, Trip for passenger {} confirmed but it does not fit in the route",
+                std::file!(),
+                std::line!(),
+                msg.passenger_id
+            );
+
+            let _ = self
                 .central_driver
-                .send(ConnectWithPassenger {
+                .try_send(FinishAssignment {
                     passenger_id: msg.passenger_id,
+                    offer_id: msg.offer_id,
                 })
-                .await;
+                .inspect_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
+                });
+        }
+
+        let central_driver = self.central_driver.clone();
+        let passenger_id = msg.passenger_id;
+
+        let connection = async move {
+            match new_rider && fits {
+                true => Some(
+                    central_driver
+                        .send(ConnectWithPassenger { passenger_id })
+                        .await,
+                ),
+                false => None,
+            }
+        }
+        .await;
 
-            match result {
+        if let Some(result) = connection {
+            let rider = Rider {
+                offer_id: msg.offer_id,
+                source: msg.passenger_location,
+                destination: msg.destination,
+                waypoints: msg.terms.stops.clone(),
+                surge: msg.terms.surge,
+                picked_up: false,
+                pickup_arrival: None,
+                waiting_time: Duration::ZERO,
+                shared_blocks: 0.0,
+            };
+
+            let started = match result {
                 Ok(Ok(_)) => {
                     let _ = self
                         .central_driver
@@ -249,17 +735,65 @@ impl Handler<CanHandleTrip> for TripHandler {
                             status: TripStatus::Info,
                             detail: format!(
                                 "Hi!, i am driver {}. I will be at your location in a moment.",
-                                msg.self_id
+                                self.id
                             ),
                         })
                         .inspect_err(|e| {
                             log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
                         });
 
-                    let p = self.current_location.take();
+                    if let Some(stops) = pooled_stops {
+                        for passenger_id in self.riders.keys() {
+                            let _ = self
+                                .central_driver
+                                .try_send(SendTripResponse {
+                                    passenger_id: *passenger_id,
+                                    status: TripStatus::Info,
+                                    detail: "We are picking up another passenger on the way"
+                                        .to_string(),
+                                })
+                                .inspect_err(|e| {
+                                    log::error!(
+                                        "{}:{}, {}",
+                                        std::file!(),
+                                        std::line!(),
+                                        e.to_string()
+                                    )
+                                });
+                        }
+
+                        self.stops = stops;
+                        self.riders.insert(msg.passenger_id, rider);
+
+                        log::info!(
+                            "[TRIP] Sharing the ride with passenger {}, stops: {:?}",
+                            msg.passenger_id,
+                            self.stops
+                                .iter()
+                                .map(|stop| (stop.passenger_id, stop.kind))
+                                .collect::<Vec<_>>()
+                        );
 
-                    if let Some(current_position) = p {
-                        self.passenger_id = Some(msg.passenger_id);
+                        self.report_route();
+                        true
+                    } else if let Some(current_position) = self.current_location.take() {
+                        self.riders.insert(msg.passenger_id, rider);
+                        self.stops = std::iter::once((msg.passenger_location, StopKind::Pickup))
+                            .chain(
+                                msg.terms
+                                    .stops
+                                    .iter()
+                                    .map(|position| (*position, StopKind::Waypoint)),
+                            )
+                            .chain(std::iter::once((msg.destination, StopKind::Dropoff)))
+                            .map(|(position, kind)| Stop {
+                                passenger_id: msg.passenger_id,
+                                position,
+                                kind,
+                            })
+                            .collect();
+                        self.route_position = current_position;
+                        self.route_id += 1;
 
                         let _ = self
                             .central_driver
@@ -273,12 +807,11 @@ impl Handler<CanHandleTrip> for TripHandler {
 
                         log::info!("[TRIP] Start trip for passenger {}", msg.passenger_id);
 
+                        self.report_route();
+
                         _ctx.notify(GoTo {
                             current_position,
-                            next_position: msg.passenger_location,
-                            passenger_id: msg.passenger_id,
-                            passenger_location: msg.passenger_location,
-                            destination: msg.destination,
+                            route_id: self.route_id,
                         });
 
                         true
@@ -294,19 +827,133 @@ impl Handler<CanHandleTrip> for TripHandler {
                     }
                 }
                 _ => false,
+            };
+
+            if !started {
+                let _ = self
+                    .central_driver
+                    .try_send(FinishAssignment {
+                        passenger_id: msg.passenger_id,
+                        offer_id: msg.offer_id,
+                    })
+                    .inspect_err(|e| {
+                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
+                    });
             }
-        } else {
-            false
-        };
+        }
+    }
+}
 
-        let _ = self
-            .central_driver
-            .try_send(super::central_driver::CanHandleTripACK {
-                passenger_id: pid,
-                response: res,
-                driver_id: did,
-            })
-            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RevokeOffer {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Id de la oferta revocada
+    pub offer_id: u64,
+}
+
+impl Handler<RevokeOffer> for TripHandler {
+    type Result = ();
+
+    /// Revoca una oferta de viaje, ya sea porque vencio antes de que llegara la respuesta o porque
+    /// el viaje no le fue asignado a este driver.
+    /// - Si la oferta estaba reservada, la libera.
+    /// - Si el driver ya iba a buscar al pasajero por esa oferta, lo saca de la ruta y le avisa al lider
+    ///   para que libere la asignacion.
+    fn handle(&mut self, msg: RevokeOffer, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some((_, expiration)) = self
+            .reserved_trip
+            .take_if(|(offer_id, _)| *offer_id == msg.offer_id)
+        {
+            log::debug!(
+                "[TRIP] Released the trip for passenger {}",
+                msg.passenger_id
+            );
+            ctx.cancel_future(expiration);
+            return;
+        }
+
+        if self
+            .riders
+            .get(&msg.passenger_id)
+            .is_some_and(|rider| rider.offer_id == msg.offer_id)
+        {
+            log::warn!(
+                "[TRIP] The trip for passenger {} was revoked, aborting",
+                msg.passenger_id
+            );
+
+            self.drop_rider(msg.passenger_id);
+
+            let _ = self
+                .central_driver
+                .try_send(FinishAssignment {
+                    passenger_id: msg.passenger_id,
+                    offer_id: msg.offer_id,
+                })
+                .inspect_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string())
+                });
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RevokeStaleTrips {
+    /// Ofertas de los viajes que el log de asignaciones todavia le asigna a este driver
+    pub offer_ids: HashSet<u64>,
+}
+
+impl Handler<RevokeStaleTrips> for TripHandler {
+    type Result = ();
+
+    /// Abandona los viajes cuya oferta ya no le esta asignada a este driver segun el log de asignaciones,
+    /// por ejemplo si se lo dio por muerto mientras estaba aislado y el viaje se le asigno a otro driver.
+    fn handle(&mut self, msg: RevokeStaleTrips, _ctx: &mut Context<Self>) -> Self::Result {
+        let stale = self
+            .riders
+            .iter()
+            .filter(|(_, rider)| !msg.offer_ids.contains(&rider.offer_id))
+            .map(|(passenger_id, _)| *passenger_id)
+            .collect::<Vec<u32>>();
+
+        for passenger_id in stale {
+            log::warn!(
+                "[TRIP] The trip for passenger {} is no longer mine, aborting",
+                passenger_id
+            );
+
+            self.drop_rider(passenger_id);
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ExpireReservation {
+    /// Id del pasajero
+    passenger_id: u32,
+    /// Id de la oferta
+    offer_id: u64,
+}
+
+impl Handler<ExpireReservation> for TripHandler {
+    type Result = ();
+
+    /// Libera la oferta reservada si el lider no confirmo la asignacion a tiempo.
+    fn handle(&mut self, msg: ExpireReservation, _ctx: &mut Context<Self>) -> Self::Result {
+        if self
+            .reserved_trip
+            .take_if(|(offer_id, _)| *offer_id == msg.offer_id)
+            .is_some()
+        {
+            log::warn!(
+                "[TRIP] The trip for passenger {} was not confirmed in time",
+                msg.passenger_id
+            );
+        }
     }
 }
 
@@ -322,23 +969,61 @@ pub struct ClearPassenger {
 impl Handler<ClearPassenger> for TripHandler {
     type Result = ();
 
-    /// Limpia el estado del viaje.
+    /// Limpia el estado del viaje del pasajero, si es uno de los que lleva este driver (ver `release_trip`).
     fn handle(&mut self, msg: ClearPassenger, _ctx: &mut Context<Self>) -> Self::Result {
-        if let None = self.passenger_id {
-            return ();
-        }
-
-        if self.passenger_id.unwrap() == msg.passenger_id {
+        if self.riders.contains_key(&msg.passenger_id) {
             if msg.disconnected {
                 log::warn!("What the hell!! The passenger jump out of the car!!");
             }
 
-            self.passenger_id = None;
-            log::info!("Now i'm ready for another trip!");
+            self.release_trip(msg.passenger_id);
         }
     }
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CancelTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+}
+
+impl Handler<CancelTrip> for TripHandler {
+    type Result = ();
+
+    /// Abandona el viaje de un pasajero que lo cancelo, si es uno de los que lleva este driver.
+    /// Como el driver ya habia salido a buscarlo, le confirma la cancelacion al pasajero y le cobra
+    /// la multa por cancelar, que es la tarifa base. Luego libera el viaje como al terminarlo.
+    fn handle(&mut self, msg: CancelTrip, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.riders.contains_key(&msg.passenger_id) {
+            return;
+        }
+
+        log::info!("[TRIP] Passenger {} cancelled the trip", msg.passenger_id);
+
+        let _ = self
+            .central_driver
+            .try_send(SendTripResponse {
+                passenger_id: msg.passenger_id,
+                status: TripStatus::Cancelled,
+                detail: "Your trip has been cancelled, you will be charged a cancellation fee"
+                    .to_string(),
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        let _ = self
+            .central_driver
+            .try_send(CollectMoneyPassenger {
+                passenger_id: msg.passenger_id,
+                amount: self.pricing.base_fee,
+                cancellation_fee: true,
+            })
+            .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()));
+
+        self.release_trip(msg.passenger_id);
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct ForceNotifyPosition {}
@@ -365,3 +1050,34 @@ impl Handler<NotifyPosition> for TripHandler {
         ctx.notify_later(NotifyPosition {}, POSITION_NOTIFICATION_INTERVAL);
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Drain {
+    /// Canal por el que se avisa que el driver quedo libre y puede apagarse
+    pub idle_tx: oneshot::Sender<()>,
+}
+
+impl Handler<Drain> for TripHandler {
+    type Result = ();
+
+    /// Deja de aceptar viajes y notifica que se encuentra en el 'infinito'.
+    /// - Si no esta llevando pasajeros, avisa en el momento que quedo libre.
+    /// - Si no, avisa cuando deje al ultimo.
+    fn handle(&mut self, msg: Drain, _ctx: &mut Context<Self>) -> Self::Result {
+        self.draining = true;
+        self.notify_pos();
+        self.report_route();
+
+        if self.riders.is_empty() {
+            let _ = msg.idle_tx.send(());
+            return;
+        }
+
+        log::info!(
+            "Shutting down after finishing the trips for passengers {:?}",
+            self.riders.keys().collect::<Vec<_>>()
+        );
+        self.idle_tx = Some(msg.idle_tx);
+    }
+}
@@ -1,22 +1,74 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
-use common::utils::position::Position;
+use common::utils::{json_parser::Booking, position::Position};
+
+use super::{
+    assignment_log::{LogEntry, LogSnapshot},
+    membership::MemberInfo,
+    pool::PoolRoute,
+};
+
+/// Busqueda de conductor abierta para un pasajero
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PendingTrip {
+    /// Id del pasajero
+    pub passenger_id: u32,
+    /// Posicion inicial del pasajero
+    pub source: Position,
+    /// Posicion destino del pasajero
+    pub destination: Position,
+}
 
 #[derive(Serialize, Deserialize)]
 pub enum CommonMessages {
-    Identification { id: u32, type_: char },
+    Identification {
+        id: u32,
+        type_: char,
+        /// Direccion en la que escucha el driver, los pasajeros no la envian
+        #[serde(default)]
+        addr: Option<String>,
+    },
+}
+
+/// Condiciones que acordo un pasajero para su viaje, con las que lo lleva el driver asignado
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TripTerms {
+    /// Multiplicador por demanda que acepto el pasajero
+    pub surge: f64,
+    /// Paradas intermedias del viaje, en el orden en que se recorren
+    pub stops: Vec<Position>,
+}
+
+impl Default for TripTerms {
+    fn default() -> Self {
+        Self {
+            surge: 1.0,
+            stops: Vec::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum DriverMessages {
     Coordinator {
         leader_id: u32,
+        term: u64,
+    },
+    RequestVote {
+        term: u64,
+        candidate_id: u32,
+        last_log_index: u64,
+        last_log_term: u64,
     },
-    Election {
-        sender_id: u32,
+    Vote {
+        term: u64,
+        voter_id: u32,
+        granted: bool,
     },
-    Alive {
-        responder_id: u32,
+    TimeoutNow {
+        term: u64,
     },
     NotifyPosition {
         driver_id: u32,
@@ -32,10 +84,147 @@ pub enum DriverMessages {
         driver_id: u32,
         passenger_location: Position,
         destination: Position,
+        coordinator_id: u32,
+        offer_id: u64,
+        lease_ms: u64,
     },
     CanHandleTripACK {
         response: bool,
         passenger_id: u32,
         driver_id: u32,
+        coordinator_id: u32,
+        offer_id: u64,
+    },
+    ReplicatePosition {
+        term: u64,
+        driver_id: u32,
+        driver_position: Position,
+    },
+    ReplicateTripSearch {
+        term: u64,
+        trip: PendingTrip,
+    },
+    ReplicateTripSearchDone {
+        term: u64,
+        passenger_id: u32,
+    },
+    ZoneTripSearch {
+        trip: PendingTrip,
+    },
+    ZoneTripSearchDone {
+        passenger_id: u32,
+    },
+    DispatchSnapshot {
+        term: u64,
+        driver_positions: HashMap<u32, Position>,
+        pending_trips: Vec<PendingTrip>,
+    },
+    MergeDispatchState {
+        term: u64,
+        leader_id: u32,
+        driver_positions: HashMap<u32, Position>,
+        pending_trips: Vec<PendingTrip>,
+    },
+    Heartbeat {},
+    Gossip {
+        members: Vec<MemberInfo>,
+    },
+    ZoneAssignment {
+        term: u64,
+        coordinators: Vec<u32>,
+    },
+    ZoneHandoff {
+        passenger_id: u32,
+        passenger_location: Position,
+        destination: Position,
+        handoff_zones: Vec<u32>,
+    },
+    ProposeAssignment {
+        passenger_id: u32,
+        driver_id: u32,
+        passenger_location: Position,
+        destination: Position,
+        offer_id: u64,
+    },
+    AppendEntries {
+        term: u64,
+        snapshot: Option<LogSnapshot>,
+        prev_index: u64,
+        prev_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    },
+    AppendEntriesACK {
+        term: u64,
+        driver_id: u32,
+        success: bool,
+        match_index: u64,
+    },
+    ConfirmTrip {
+        passenger_id: u32,
+        driver_id: u32,
+        passenger_location: Position,
+        destination: Position,
+        offer_id: u64,
+    },
+    RevokeOffer {
+        passenger_id: u32,
+        driver_id: u32,
+        offer_id: u64,
+    },
+    FinishAssignment {
+        passenger_id: u32,
+        offer_id: u64,
+    },
+    QueueTrip {
+        passenger_id: u32,
+        passenger_location: Position,
+        destination: Position,
+    },
+    DriverAvailable {
+        driver_id: u32,
+    },
+    CancelTrip {
+        passenger_id: u32,
+    },
+    TripCancelled {
+        passenger_id: u32,
+    },
+    ReleaseTrip {
+        passenger_id: u32,
+        driver_id: u32,
+    },
+    UpdateTripPosition {
+        passenger_id: u32,
+        position: Position,
+    },
+    SurgeUpdate {
+        term: u64,
+        multipliers: HashMap<u32, f64>,
+    },
+    RecordTripTerms {
+        passenger_id: u32,
+        terms: TripTerms,
+    },
+    ReplicateTripTerms {
+        term: u64,
+        passenger_id: u32,
+        terms: TripTerms,
+    },
+    UpdatePoolRoute {
+        driver_id: u32,
+        route: Option<PoolRoute>,
+    },
+    ReplicatePoolRoute {
+        term: u64,
+        driver_id: u32,
+        route: Option<PoolRoute>,
+    },
+    ScheduleTrip {
+        booking: Booking,
+    },
+    CancelBooking {
+        passenger_id: u32,
+        booking_id: u64,
     },
 }
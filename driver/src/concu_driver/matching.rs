@@ -0,0 +1,146 @@
+/// Resuelve el problema de asignacion con el algoritmo hungaro, en O(n^3).
+///
+/// `costs[i][j]` es el costo de asignarle la columna j a la fila i, o None si no se permite.
+/// Devuelve, para cada fila, la columna que se le asigno (si la hay). Primero se maximiza la cantidad
+/// de filas asignadas y, entre esas asignaciones, se minimiza el costo total.
+pub fn min_cost_assignment(costs: &[Vec<Option<u32>>]) -> Vec<Option<usize>> {
+    let rows = costs.len();
+    let cols = costs.first().map(|row| row.len()).unwrap_or_default();
+    let n = rows.max(cols);
+
+    if rows == 0 || cols == 0 {
+        return vec![None; rows];
+    }
+
+    // Costo de un par no permitido: mayor que cualquier asignacion que solo use pares permitidos
+    let max_cost = costs
+        .iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or_default() as i64;
+    let forbidden = (max_cost + 1) * n as i64 + 1;
+
+    // Las filas y columnas que faltan para que la matriz sea cuadrada tienen costo 0
+    let cost = |i: usize, j: usize| -> i64 {
+        if i < rows && j < cols {
+            costs[i][j].map(|c| c as i64).unwrap_or(forbidden)
+        } else {
+            0
+        }
+    };
+
+    // Potenciales de filas y columnas, fila asignada a cada columna y camino de aumento (indices desde 1)
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; n + 1];
+    let mut p = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+
+                let reduced = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if reduced < minv[j] {
+                    minv[j] = reduced;
+                    way[j] = j0;
+                }
+                if minv[j] < delta {
+                    delta = minv[j];
+                    j1 = j;
+                }
+            }
+
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut assignment = vec![None; rows];
+
+    for (j, &i) in p.iter().enumerate().skip(1) {
+        if (1..=rows).contains(&i) && j <= cols && costs[i - 1][j - 1].is_some() {
+            assignment[i - 1] = Some(j - 1);
+        }
+    }
+
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beats_greedy_assignment() {
+        let costs = vec![vec![Some(1), Some(2)], vec![Some(2), Some(100)]];
+
+        assert_eq!(min_cost_assignment(&costs), vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_more_rows_than_columns() {
+        let costs = vec![vec![Some(5)], vec![Some(1)], vec![Some(3)]];
+
+        assert_eq!(min_cost_assignment(&costs), vec![None, Some(0), None]);
+    }
+
+    #[test]
+    fn test_more_columns_than_rows() {
+        let costs = vec![vec![Some(7), Some(2), Some(4)]];
+
+        assert_eq!(min_cost_assignment(&costs), vec![Some(1)]);
+    }
+
+    #[test]
+    fn test_forbidden_pairs() {
+        let costs = vec![vec![Some(1), None], vec![Some(1), None]];
+        let assignment = min_cost_assignment(&costs);
+        assert_eq!(assignment.iter().flatten().count(), 1);
+        assert!(!assignment.contains(&Some(1)));
+
+        // Conviene asignar ambas filas aunque el costo total sea mayor
+        let costs = vec![vec![Some(1), Some(50)], vec![Some(2), None]];
+        assert_eq!(min_cost_assignment(&costs), vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(min_cost_assignment(&[]).is_empty());
+        assert_eq!(min_cost_assignment(&[vec![], vec![]]), vec![None, None]);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Estado de un driver segun la vista de membresia
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemberState {
+    Alive,
+    Suspect,
+    Dead,
+}
+
+/// Informacion de un driver del cluster, tal como se propaga por gossip
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MemberInfo {
+    /// Id del driver
+    pub id: u32,
+    /// Direccion en la que escucha el driver
+    pub addr: String,
+    /// Estado del driver
+    pub state: MemberState,
+    /// Numero de encarnacion, solo el propio driver lo aumenta para desmentir que esta caido
+    pub incarnation: u64,
+}
+
+/// Vista de la membresia del cluster al estilo SWIM.
+///
+/// Las actualizaciones se ordenan por numero de encarnacion: una actualizacion pisa a la vista
+/// actual de un driver si tiene mayor encarnacion o, con la misma, si es mas grave
+/// (Alive < Suspect < Dead). Un driver muerto solo vuelve si el mismo se anuncia con una encarnacion mayor.
+pub struct Membership {
+    /// Id de este driver
+    self_id: u32,
+    /// Drivers conocidos segun su id, incluyendo a este
+    members: HashMap<u32, MemberInfo>,
+}
+
+impl MemberState {
+    /// Gravedad del estado, para desempatar actualizaciones con la misma encarnacion
+    fn severity(&self) -> u8 {
+        match self {
+            MemberState::Alive => 0,
+            MemberState::Suspect => 1,
+            MemberState::Dead => 2,
+        }
+    }
+}
+
+impl Membership {
+    /// Crea una vista que solo conoce a este driver, vivo y con encarnacion 0.
+    pub fn new(self_id: u32, self_addr: String) -> Self {
+        let me = MemberInfo {
+            id: self_id,
+            addr: self_addr,
+            state: MemberState::Alive,
+            incarnation: 0,
+        };
+
+        Self {
+            self_id,
+            members: HashMap::from([(self_id, me)]),
+        }
+    }
+
+    /// Devuelve la informacion de un driver, si se lo conoce.
+    pub fn get(&self, id: u32) -> Option<&MemberInfo> {
+        self.members.get(&id)
+    }
+
+    /// Devuelve la vista completa, para enviarla por gossip.
+    pub fn digest(&self) -> Vec<MemberInfo> {
+        self.members.values().cloned().collect()
+    }
+
+    /// Aplica una actualizacion recibida por gossip.
+    /// Devuelve la nueva informacion del driver si la vista cambio.
+    ///
+    /// Si la actualizacion dice que este driver esta sospechado o muerto, lo desmiente
+    /// aumentando su encarnacion.
+    pub fn merge(&mut self, update: MemberInfo) -> Option<MemberInfo> {
+        if update.id == self.self_id {
+            return self.refute(&update);
+        }
+
+        let overrides = match self.members.get(&update.id) {
+            None => true,
+            Some(current) => {
+                update.incarnation > current.incarnation
+                    || (update.incarnation == current.incarnation
+                        && update.state.severity() > current.state.severity())
+            }
+        };
+
+        if !overrides {
+            return None;
+        }
+
+        self.members.insert(update.id, update.clone());
+        Some(update)
+    }
+
+    /// Registra a un driver con el que se establecio una conexion directa.
+    /// Si no se lo conocia se lo agrega vivo; si se lo sospechaba, la conexion lo desmiente.
+    /// Un driver muerto solo vuelve si se anuncia con una encarnacion mayor.
+    /// Devuelve la nueva informacion del driver si la vista cambio.
+    pub fn join(&mut self, id: u32, addr: String) -> Option<MemberInfo> {
+        match self.members.get_mut(&id) {
+            None => {
+                let member = MemberInfo {
+                    id,
+                    addr,
+                    state: MemberState::Alive,
+                    incarnation: 0,
+                };
+                self.members.insert(id, member.clone());
+                Some(member)
+            }
+            Some(member) => {
+                let changed = member.addr != addr || member.state == MemberState::Suspect;
+
+                member.addr = addr;
+                if member.state == MemberState::Suspect {
+                    member.state = MemberState::Alive;
+                }
+
+                changed.then(|| member.clone())
+            }
+        }
+    }
+
+    /// Marca como sospechado a un driver vivo, con su encarnacion actual.
+    /// Devuelve la nueva informacion del driver si la vista cambio.
+    pub fn suspect(&mut self, id: u32) -> Option<MemberInfo> {
+        self.set_state(id, MemberState::Suspect)
+    }
+
+    /// Confirma como muerto a un driver, con su encarnacion actual.
+    /// Devuelve la nueva informacion del driver si la vista cambio.
+    pub fn confirm_dead(&mut self, id: u32) -> Option<MemberInfo> {
+        self.set_state(id, MemberState::Dead)
+    }
+
+    /// Marca a este driver como muerto, para anunciar que deja el cluster.
+    pub fn leave(&mut self) -> Option<MemberInfo> {
+        let me = self.members.get_mut(&self.self_id)?;
+        me.state = MemberState::Dead;
+        Some(me.clone())
+    }
+
+    /// Cambia el estado de otro driver si el nuevo estado es mas grave que el actual.
+    fn set_state(&mut self, id: u32, state: MemberState) -> Option<MemberInfo> {
+        if id == self.self_id {
+            return None;
+        }
+
+        let member = self.members.get_mut(&id)?;
+
+        if member.state.severity() >= state.severity() {
+            return None;
+        }
+
+        member.state = state;
+        Some(member.clone())
+    }
+
+    /// Desmiente una actualizacion que dice que este driver esta sospechado o muerto.
+    fn refute(&mut self, update: &MemberInfo) -> Option<MemberInfo> {
+        let me = self.members.get_mut(&self.self_id)?;
+
+        if update.state == MemberState::Alive
+            || update.incarnation < me.incarnation
+            || me.state == MemberState::Dead
+        {
+            return None;
+        }
+
+        me.incarnation = update.incarnation + 1;
+        Some(me.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(id: u32, state: MemberState, incarnation: u64) -> MemberInfo {
+        MemberInfo {
+            id,
+            addr: format!("127.0.0.1:{}", 8080 + id),
+            state,
+            incarnation,
+        }
+    }
+
+    #[test]
+    fn test_merge_unknown_member() {
+        let mut membership = Membership::new(0, "127.0.0.1:8080".to_string());
+
+        let update = member(1, MemberState::Alive, 0);
+        assert_eq!(membership.merge(update.clone()), Some(update.clone()));
+        assert_eq!(membership.get(1), Some(&update));
+        assert_eq!(membership.digest().len(), 2);
+    }
+
+    #[test]
+    fn test_suspect_overrides_alive_with_same_incarnation() {
+        let mut membership = Membership::new(0, "127.0.0.1:8080".to_string());
+        membership.merge(member(1, MemberState::Alive, 3));
+
+        assert!(membership
+            .merge(member(1, MemberState::Suspect, 3))
+            .is_some());
+        assert!(membership.merge(member(1, MemberState::Alive, 3)).is_none());
+        assert_eq!(membership.get(1).unwrap().state, MemberState::Suspect);
+    }
+
+    #[test]
+    fn test_alive_with_higher_incarnation_overrides_dead() {
+        let mut membership = Membership::new(0, "127.0.0.1:8080".to_string());
+        membership.merge(member(1, MemberState::Dead, 2));
+
+        assert!(membership.merge(member(1, MemberState::Alive, 2)).is_none());
+        assert!(membership.merge(member(1, MemberState::Alive, 3)).is_some());
+        assert_eq!(membership.get(1).unwrap().state, MemberState::Alive);
+    }
+
+    #[test]
+    fn test_refute_suspicion_about_self() {
+        let mut membership = Membership::new(0, "127.0.0.1:8080".to_string());
+
+        let refutation = membership.merge(member(0, MemberState::Suspect, 0));
+        assert_eq!(refutation.map(|me| me.incarnation), Some(1));
+        assert_eq!(membership.get(0).unwrap().state, MemberState::Alive);
+
+        assert!(membership
+            .merge(member(0, MemberState::Suspect, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_local_suspicion_and_join() {
+        let mut membership = Membership::new(0, "127.0.0.1:8080".to_string());
+        membership.join(1, "127.0.0.1:8081".to_string());
+
+        assert!(membership.suspect(1).is_some());
+        assert!(membership.suspect(1).is_none());
+        assert!(membership.join(1, "127.0.0.1:8081".to_string()).is_some());
+        assert_eq!(membership.get(1).unwrap().state, MemberState::Alive);
+
+        assert!(membership.confirm_dead(1).is_some());
+        assert!(membership.join(1, "127.0.0.1:8081".to_string()).is_none());
+        assert_eq!(membership.get(1).unwrap().state, MemberState::Dead);
+    }
+}
@@ -1,10 +1,20 @@
+pub mod assignment_log;
 pub mod central_driver;
+pub mod config;
+pub mod connection_supervisor;
 pub mod connections_handler;
 pub mod consts;
+pub mod dispatch;
 pub mod driver;
 pub mod driver_connection;
 pub mod driver_finder;
+pub mod fairness;
 pub mod handle_trip;
 pub mod json_parser;
+pub mod matching;
+pub mod membership;
 pub mod passenger_connection;
 pub mod payment_connection;
+pub mod pool;
+pub mod surge;
+pub mod zones;
@@ -1,4 +1,4 @@
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, StreamHandler};
+use actix::{Actor, ActorContext, Addr, AsyncContext, Context, Handler, Message, StreamHandler};
 use actix_async_handler::async_handler;
 use common::utils::{
     consts::{HOST, MIN_PASSENGER_PORT},
@@ -12,7 +12,7 @@ use tokio_stream::wrappers::LinesStream;
 
 use crate::concu_driver::central_driver::RemovePassengerConnection;
 
-use super::central_driver::{CentralDriver, RedirectNewTrip};
+use super::central_driver::{CancelTrip, CentralDriver, RedirectNewTrip};
 
 pub struct PassengerConnection {
     /// Direccion del actor CentralDriver
@@ -72,6 +72,22 @@ impl PassengerConnection {
             }
         }
     }
+
+    /// Se conecta con un pasajero, le envia un mensaje y cierra la conexion.
+    /// Se usa para avisarle el progreso de la busqueda de su viaje sin ocupar la conexion
+    /// que luego abre el driver que lo lleva.
+    pub async fn notify(passenger_id: u32, data: String) -> Result<(), String> {
+        let addr = format!("{}:{}", HOST, MIN_PASSENGER_PORT + passenger_id);
+
+        let mut socket = TcpStream::connect(addr).await.map_err(|e| e.to_string())?;
+
+        socket
+            .write_all((data + "\n").as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        socket.shutdown().await.map_err(|e| e.to_string())
+    }
 }
 
 impl Actor for PassengerConnection {
@@ -165,7 +181,8 @@ impl Handler<RecvAll> for PassengerConnection {
     /// Maneja los mensajes recibidos desde el pasajero.
     /// Parsea el mensaje recibido y envía un mensaje:
     /// Si el mensaje es de tipo `TripRequest` envía un mensaje al `CentralDriver` con la respuesta.
-    /// Si el mensaje no es de tipo `TripRequest` loggea un error.
+    /// Si el mensaje es de tipo `Cancel` le pide al `CentralDriver` que cancele el viaje del pasajero.
+    /// Si el mensaje es de otro tipo loggea un error.
     fn handle(&mut self, msg: RecvAll, _ctx: &mut Context<Self>) -> Self::Result {
         let data = serde_json::from_str(&msg.data).map_err(|e| {
             log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
@@ -176,6 +193,7 @@ impl Handler<RecvAll> for PassengerConnection {
             TripMessages::TripRequest {
                 source,
                 destination,
+                ..
             } => self
                 .central_driver
                 .try_send(RedirectNewTrip {
@@ -187,6 +205,15 @@ impl Handler<RecvAll> for PassengerConnection {
                     log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                     e.to_string()
                 })?,
+            TripMessages::Cancel {} => self
+                .central_driver
+                .try_send(CancelTrip {
+                    passenger_id: self.passenger_id,
+                })
+                .map_err(|e| {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    e.to_string()
+                })?,
 
             _ => log::error!("Why i'm receiving a this type of message {:?}", data),
         }
@@ -194,3 +221,21 @@ impl Handler<RecvAll> for PassengerConnection {
         Ok(())
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseConnection {}
+
+impl Handler<CloseConnection> for PassengerConnection {
+    type Result = ();
+
+    /// Cierra la conexion con el pasajero deteniendo el actor, luego de enviarle los mensajes pendientes.
+    fn handle(&mut self, _msg: CloseConnection, ctx: &mut Context<Self>) -> Self::Result {
+        log::debug!(
+            "Closing the connection with passenger {}",
+            self.passenger_id
+        );
+        self.passenger_write_stream = None;
+        ctx.stop();
+    }
+}
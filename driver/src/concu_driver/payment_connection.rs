@@ -170,7 +170,7 @@ impl Handler<RecvAll> for PaymentConnection {
 
     /// Maneja los mensajes recibidos desde el servicio de pagos.
     /// Parsea el mensaje recibido y envía un mensaje al actor `CentralDriver` con la respuesta.
-    /// Si el mensaje es de tipo `CollectPayment` envía un mensaje al `CentralDriver` con la respuesta.
+    /// Si el mensaje es de tipo `CollectPayment` o `CollectCancellationFee` envía un mensaje al `CentralDriver` con la respuesta.
     /// Si el mensaje es de tipo `AuthPayment` loggea un error.
     fn handle(&mut self, msg: RecvAll, _ctx: &mut Context<Self>) -> Self::Result {
         let data = serde_json::from_str(&msg.data).map_err(|e| {
@@ -188,6 +188,22 @@ impl Handler<RecvAll> for PaymentConnection {
                     .try_send(CheckPaymentResponse {
                         passenger_id,
                         response,
+                        cancellation_fee: false,
+                    })
+                    .inspect_err(|e| {
+                        log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                    });
+            }
+            PaymentResponses::CollectCancellationFee {
+                passenger_id,
+                response,
+            } => {
+                let _ = self
+                    .central_driver
+                    .try_send(CheckPaymentResponse {
+                        passenger_id,
+                        response,
+                        cancellation_fee: true,
                     })
                     .inspect_err(|e| {
                         log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
@@ -0,0 +1,216 @@
+use common::utils::position::Position;
+use serde::{Deserialize, Serialize};
+
+/// Tipo de parada en la ruta de un auto
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopKind {
+    /// Subida de un pasajero
+    Pickup,
+    /// Parada intermedia de un pasajero que va en el auto
+    Waypoint,
+    /// Bajada de un pasajero
+    Dropoff,
+}
+
+/// Parada en la ruta de un auto
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stop {
+    /// Id del pasajero que sube o baja
+    pub passenger_id: u32,
+    /// Posicion de la parada
+    pub position: Position,
+    /// Si el pasajero sube o baja
+    pub kind: StopKind,
+}
+
+/// Ruta de un auto que puede llevar a varios pasajeros a la vez
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolRoute {
+    /// Posicion actual del auto
+    pub position: Position,
+    /// Paradas que faltan, en el orden en que se recorren
+    pub stops: Vec<Stop>,
+    /// Pasajeros que van en el auto
+    pub aboard: usize,
+    /// Asientos del auto
+    pub capacity: usize,
+}
+
+impl PoolRoute {
+    /// Crea la ruta de un auto en la posicion dada, sin paradas.
+    pub fn new(position: Position, aboard: usize, capacity: usize) -> Self {
+        Self {
+            position,
+            stops: Vec::new(),
+            aboard,
+            capacity,
+        }
+    }
+
+    /// Cuadras que faltan recorrer para pasar por todas las paradas
+    pub fn length(&self) -> u32 {
+        Self::length_of(&self.position, &self.stops)
+    }
+
+    /// Verifica si el auto tiene algun asiento que no esta ocupado ni reservado para un pasajero a buscar.
+    pub fn has_free_seat(&self) -> bool {
+        let pending_pickups = self
+            .stops
+            .iter()
+            .filter(|stop| stop.kind == StopKind::Pickup)
+            .count();
+
+        self.aboard + pending_pickups < self.capacity
+    }
+
+    /// Busca donde agregar la subida y la bajada de un nuevo pasajero para que la ruta se alargue lo menos
+    /// posible, sin superar los asientos del auto en ningun tramo. Devuelve cuantas cuadras se alarga
+    /// la ruta y las nuevas paradas, o None si el pasajero no entra.
+    pub fn plan(
+        &self,
+        passenger_id: u32,
+        pickup: Position,
+        dropoff: Position,
+    ) -> Option<(u32, Vec<Stop>)> {
+        let pickup = Stop {
+            passenger_id,
+            position: pickup,
+            kind: StopKind::Pickup,
+        };
+        let dropoff = Stop {
+            passenger_id,
+            position: dropoff,
+            kind: StopKind::Dropoff,
+        };
+
+        let current_length = self.length();
+        let mut best: Option<(u32, Vec<Stop>)> = None;
+
+        for i in 0..=self.stops.len() {
+            for j in i..=self.stops.len() {
+                let mut stops = self.stops.clone();
+                stops.insert(j, dropoff);
+                stops.insert(i, pickup);
+
+                if !self.fits(&stops) {
+                    continue;
+                }
+
+                let detour = Self::length_of(&self.position, &stops).saturating_sub(current_length);
+
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_detour, _)| detour < *best_detour)
+                {
+                    best = Some((detour, stops));
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Verifica que los pasajeros que van en el auto no superen sus asientos al recorrer las paradas dadas.
+    fn fits(&self, stops: &[Stop]) -> bool {
+        let mut occupied = self.aboard;
+
+        for stop in stops {
+            match stop.kind {
+                StopKind::Pickup => occupied += 1,
+                StopKind::Waypoint => (),
+                StopKind::Dropoff => occupied = occupied.saturating_sub(1),
+            }
+
+            if occupied > self.capacity {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Cuadras para recorrer las paradas dadas desde una posicion
+    fn length_of(position: &Position, stops: &[Stop]) -> u32 {
+        let mut from = *position;
+        let mut length: u32 = 0;
+
+        for stop in stops {
+            length = length.saturating_add(from.distance_to(&stop.position));
+            from = stop.position;
+        }
+
+        length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route_with_rider(capacity: usize) -> PoolRoute {
+        let mut route = PoolRoute::new(Position::new(0, 0), 1, capacity);
+        route.stops.push(Stop {
+            passenger_id: 1,
+            position: Position::new(20, 0),
+            kind: StopKind::Dropoff,
+        });
+        route
+    }
+
+    #[test]
+    fn test_plan_on_the_way() {
+        let route = route_with_rider(2);
+
+        let (detour, stops) = route
+            .plan(2, Position::new(5, 0), Position::new(10, 0))
+            .unwrap();
+
+        assert_eq!(detour, 0);
+        assert_eq!(
+            stops
+                .iter()
+                .map(|s| (s.passenger_id, s.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, StopKind::Pickup),
+                (2, StopKind::Dropoff),
+                (1, StopKind::Dropoff)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_with_detour() {
+        let route = route_with_rider(2);
+
+        // Hay que desviarse 5 cuadras para buscarlo y 5 para volver
+        let (detour, _) = route
+            .plan(2, Position::new(10, 5), Position::new(20, 0))
+            .unwrap();
+
+        assert_eq!(detour, 10);
+    }
+
+    #[test]
+    fn test_plan_respects_capacity() {
+        let route = route_with_rider(1);
+        assert!(!route.has_free_seat());
+
+        // Solo entra despues de que baje el primer pasajero
+        let (_, stops) = route
+            .plan(2, Position::new(5, 0), Position::new(10, 0))
+            .unwrap();
+        assert_eq!(stops[0].passenger_id, 1);
+
+        let mut full = route_with_rider(2);
+        full.stops.insert(
+            0,
+            Stop {
+                passenger_id: 3,
+                position: Position::new(2, 0),
+                kind: StopKind::Pickup,
+            },
+        );
+        assert!(!full.has_free_seat());
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use common::utils::position::Position;
+
+use super::zones::ZoneGrid;
+
+/// Calculo del multiplicador de precio por alta demanda en cada celda de una grilla del mapa.
+#[derive(Clone, Copy, Debug)]
+pub struct SurgeGrid {
+    /// Grilla de celdas en la que se divide el mapa
+    grid: ZoneGrid,
+    /// Cuanto sube el multiplicador por cada pedido de mas por driver libre en una celda
+    sensitivity: f64,
+    /// Multiplicador maximo
+    max_surge: f64,
+}
+
+impl SurgeGrid {
+    /// Crea una grilla con la cantidad de filas y columnas dadas, la sensibilidad y el multiplicador maximo.
+    pub fn new(rows: u32, cols: u32, sensitivity: f64, max_surge: f64) -> Self {
+        Self {
+            grid: ZoneGrid::new(rows, cols),
+            sensitivity: sensitivity.max(0.0),
+            max_surge: max_surge.max(1.0),
+        }
+    }
+
+    /// Devuelve la celda que contiene a una posicion.
+    pub fn cell_of(&self, position: &Position) -> u32 {
+        self.grid.zone_of(position)
+    }
+
+    /// Calcula el multiplicador de cada celda a partir de la relacion entre la demanda (pedidos abiertos)
+    /// y la oferta (drivers libres) en ella. Si hay mas pedidos que drivers, el multiplicador es
+    /// `1 + sensitivity * (pedidos / drivers - 1)`, redondeado a un decimal y hasta `max_surge`.
+    /// Solo devuelve las celdas con multiplicador mayor a 1.
+    pub fn multipliers(
+        &self,
+        free_drivers: &[Position],
+        open_requests: &[Position],
+    ) -> HashMap<u32, f64> {
+        let mut supply: HashMap<u32, u32> = HashMap::new();
+        let mut demand: HashMap<u32, u32> = HashMap::new();
+
+        for position in free_drivers {
+            *supply.entry(self.cell_of(position)).or_default() += 1;
+        }
+
+        for position in open_requests {
+            *demand.entry(self.cell_of(position)).or_default() += 1;
+        }
+
+        demand
+            .into_iter()
+            .filter_map(|(cell, requests)| {
+                let drivers = supply.get(&cell).copied().unwrap_or_default().max(1);
+                let ratio = requests as f64 / drivers as f64;
+
+                let surge = (1.0 + self.sensitivity * (ratio - 1.0)).min(self.max_surge);
+                let surge = (surge * 10.0).round() / 10.0;
+
+                (surge > 1.0).then_some((cell, surge))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(count: usize, x: u32, y: u32) -> Vec<Position> {
+        vec![Position::new(x, y); count]
+    }
+
+    #[test]
+    fn test_no_surge_with_enough_drivers() {
+        let surge = SurgeGrid::new(2, 2, 0.5, 3.0);
+
+        let multipliers = surge.multipliers(&positions(2, 10, 10), &positions(2, 20, 20));
+        assert!(multipliers.is_empty());
+    }
+
+    #[test]
+    fn test_surge_per_cell() {
+        let surge = SurgeGrid::new(2, 2, 0.5, 3.0);
+
+        let mut drivers = positions(2, 10, 10);
+        drivers.extend(positions(1, 60, 60));
+
+        let mut requests = positions(6, 10, 10);
+        requests.extend(positions(1, 60, 60));
+
+        // Celda 0: 6 pedidos para 2 drivers, celda 3: 1 pedido para 1 driver
+        let multipliers = surge.multipliers(&drivers, &requests);
+        assert_eq!(multipliers, HashMap::from([(0, 2.0)]));
+    }
+
+    #[test]
+    fn test_surge_is_capped() {
+        let surge = SurgeGrid::new(1, 1, 1.0, 2.5);
+
+        let multipliers = surge.multipliers(&[], &positions(10, 50, 50));
+        assert_eq!(multipliers, HashMap::from([(0, 2.5)]));
+    }
+}
@@ -0,0 +1,107 @@
+use common::utils::position::Position;
+
+use common::utils::consts::MAP_SIZE;
+
+/// Grilla de zonas en la que se divide el mapa.
+/// Las zonas se numeran por filas, de 0 a rows * cols - 1.
+#[derive(Clone, Copy, Debug)]
+pub struct ZoneGrid {
+    /// Cantidad de filas
+    rows: u32,
+    /// Cantidad de columnas
+    cols: u32,
+}
+
+impl ZoneGrid {
+    /// Crea una grilla con la cantidad de filas y columnas dadas (al menos una de cada una).
+    pub fn new(rows: u32, cols: u32) -> Self {
+        Self {
+            rows: rows.clamp(1, MAP_SIZE),
+            cols: cols.clamp(1, MAP_SIZE),
+        }
+    }
+
+    /// Cantidad de zonas de la grilla
+    pub fn zone_count(&self) -> u32 {
+        self.rows * self.cols
+    }
+
+    /// Devuelve la zona que contiene a una posicion.
+    /// Las posiciones fuera del mapa se asignan a la zona del borde mas cercano.
+    pub fn zone_of(&self, position: &Position) -> u32 {
+        let col = position.x.min(MAP_SIZE - 1) * self.cols / MAP_SIZE;
+        let row = position.y.min(MAP_SIZE - 1) * self.rows / MAP_SIZE;
+
+        row * self.cols + col
+    }
+
+    /// Devuelve las demas zonas que estan a lo sumo a `distance` de una posicion,
+    /// ordenadas de la mas cercana a la mas lejana.
+    pub fn zones_near(&self, position: &Position, distance: u32) -> Vec<u32> {
+        let own_zone = self.zone_of(position);
+
+        let mut zones = (0..self.zone_count())
+            .filter(|zone| *zone != own_zone)
+            .map(|zone| (zone, self.distance_to_zone(position, zone)))
+            .filter(|(_, d)| *d <= distance)
+            .collect::<Vec<(u32, u32)>>();
+
+        zones.sort_by_key(|(_, d)| *d);
+
+        zones.into_iter().map(|(zone, _)| zone).collect()
+    }
+
+    /// Distancia (en cuadras) desde una posicion hasta el punto mas cercano de una zona.
+    fn distance_to_zone(&self, position: &Position, zone: u32) -> u32 {
+        let (row, col) = (zone / self.cols, zone % self.cols);
+
+        let x_range = (
+            col * MAP_SIZE / self.cols,
+            (col + 1) * MAP_SIZE / self.cols - 1,
+        );
+        let y_range = (
+            row * MAP_SIZE / self.rows,
+            (row + 1) * MAP_SIZE / self.rows - 1,
+        );
+
+        Self::distance_to_range(position.x, x_range) + Self::distance_to_range(position.y, y_range)
+    }
+
+    /// Distancia desde un valor hasta un rango cerrado.
+    fn distance_to_range(value: u32, (min, max): (u32, u32)) -> u32 {
+        min.saturating_sub(value) + value.saturating_sub(max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_zone() {
+        let grid = ZoneGrid::new(1, 1);
+        assert_eq!(grid.zone_count(), 1);
+        assert_eq!(grid.zone_of(&Position::new(0, 0)), 0);
+        assert_eq!(grid.zone_of(&Position::new(99, 99)), 0);
+        assert!(grid.zones_near(&Position::new(50, 50), 10).is_empty());
+    }
+
+    #[test]
+    fn test_zone_of() {
+        let grid = ZoneGrid::new(2, 2);
+        assert_eq!(grid.zone_of(&Position::new(10, 10)), 0);
+        assert_eq!(grid.zone_of(&Position::new(60, 10)), 1);
+        assert_eq!(grid.zone_of(&Position::new(10, 60)), 2);
+        assert_eq!(grid.zone_of(&Position::new(60, 60)), 3);
+        assert_eq!(grid.zone_of(&Position::infinity()), 3);
+    }
+
+    #[test]
+    fn test_zones_near_border() {
+        let grid = ZoneGrid::new(2, 2);
+
+        assert!(grid.zones_near(&Position::new(10, 10), 10).is_empty());
+        assert_eq!(grid.zones_near(&Position::new(47, 10), 10), vec![1]);
+        assert_eq!(grid.zones_near(&Position::new(48, 47), 10), vec![1, 2, 3]);
+    }
+}
@@ -9,10 +9,14 @@ MAGENTA='\033[0;35m'
 CYAN='\033[0;36m'
 WHITE='\033[0;37m'
 
+# Amount of drivers of the cluster in the current test, the quorum is computed from it
+CLUSTER_SIZE=1
+
 # Aux PIDs
 PAYMENT_BACKGROUND_PID=''
 DRIVER_1_BACKGROUND_PID=''
 DRIVER_2_BACKGROUND_PID=''
+DRIVER_3_BACKGROUND_PID=''
 PASSENGER_1_BACkGROUND_PID=''
 PASSENGER_2_BACkGROUND_PID=''
 
@@ -54,7 +58,7 @@ boot_driver_1() {
   local id="$1"
   local accept_trip_probability="$2"
   cd driver || exit
-  TEST=true TAKE_TRIP_PROBABILITY="$accept_trip_probability" cargo run "$id" &> /dev/null &
+  TEST=true CLUSTER_SIZE="$CLUSTER_SIZE" TAKE_TRIP_PROBABILITY="$accept_trip_probability" cargo run "$id" &> /dev/null &
   DRIVER_1_BACKGROUND_PID=$!
   sleep 1
   cd ..
@@ -64,12 +68,22 @@ boot_driver_2() {
   local id="$1"
   local accept_trip_probability="$2"
   cd driver || exit
-  TEST=true TAKE_TRIP_PROBABILITY="$accept_trip_probability" cargo run "$id" &> /dev/null &
+  TEST=true CLUSTER_SIZE="$CLUSTER_SIZE" TAKE_TRIP_PROBABILITY="$accept_trip_probability" cargo run "$id" &> /dev/null &
   DRIVER_2_BACKGROUND_PID=$!
   sleep 1
   cd ..
 }
 
+boot_driver_3() {
+  local id="$1"
+  local accept_trip_probability="$2"
+  cd driver || exit
+  TEST=true CLUSTER_SIZE="$CLUSTER_SIZE" TAKE_TRIP_PROBABILITY="$accept_trip_probability" cargo run "$id" &> /dev/null &
+  DRIVER_3_BACKGROUND_PID=$!
+  sleep 1
+  cd ..
+}
+
 boot_passenger() {
   local id="$1"
   local origin="$2"
@@ -106,6 +120,7 @@ kill_all() {
   kill -9 "$PAYMENT_BACKGROUND_PID"
   [ -n "$DRIVER_1_BACKGROUND_PID" ] && kill -9 "$DRIVER_1_BACKGROUND_PID"
   [ -n "$DRIVER_2_BACKGROUND_PID" ] && kill -9 "$DRIVER_2_BACKGROUND_PID"
+  [ -n "$DRIVER_3_BACKGROUND_PID" ] && kill -9 "$DRIVER_3_BACKGROUND_PID"
   echo -e "${MAGENTA}Killed all processes${WHITE}"
 }
 
@@ -121,6 +136,7 @@ build_all
 
 # Test 1: One Driver and One Passenger (Accepts)
 echo -e "${CYAN}Test 1: One Available Driver and One Passenger${WHITE}"
+CLUSTER_SIZE=1
 boot_payment 1
 boot_driver_1 0 1.0
 boot_passenger 1 "(0,0)" "(10,10)"
@@ -129,6 +145,7 @@ kill_all
 
 # Test 2: One Driver and One Passenger (Rejects)
 echo -e "${CYAN}Test 2: One Non-Available Driver and One Passenger${WHITE}"
+CLUSTER_SIZE=1
 boot_payment 1
 boot_driver_1 0 0.0
 boot_passenger 1 "(0,0)" "(10,10)"
@@ -137,14 +154,16 @@ kill_all
 
 # Test 3: One driver very far away from the passenger cannot accept the trip
 echo -e "${CYAN}Test 3: One Driver very far away from the Passenger${WHITE}"
+CLUSTER_SIZE=1
 boot_payment 1
 boot_driver_1 10 1.0
 boot_passenger 1 "(0,0)" "(10,10)"
 assert_eq 1 $? "The exit code of the passenger was not the expected one."
 kill_all
 
-# Test 4: One driver and two passengers (One will be rejected)
+# Test 4: One driver and two passengers (The second one waits in the queue until the driver is free)
 echo -e "${CYAN}Test 4: One Driver and Two Passengers${WHITE}"
+CLUSTER_SIZE=1
 boot_payment 1
 boot_driver_1 0 1.0
 boot_passenger_1_in_background 1 "(0,0)" "(10,10)"
@@ -153,12 +172,13 @@ boot_passenger_2_in_background 2 "(2,2)" "(15,15)"
 wait "$PASSENGER_1_BACkGROUND_PID"
 assert_eq 0 $? "The exit code of the first passenger was not the expected one."
 wait "$PASSENGER_2_BACkGROUND_PID"
-assert_eq 1 $? "The exit code of the second passenger was not the expected one."
+assert_eq 0 $? "The exit code of the second passenger was not the expected one."
 kill_all
 
 
 # Test 5: Two drivers and two passengers (Both will be accepted)
 echo -e "${CYAN}Test 5: Two Drivers and Two Passengers${WHITE}"
+CLUSTER_SIZE=2
 boot_payment 1
 boot_driver_1 0 1.0
 boot_driver_2 1 1.0
@@ -172,6 +192,7 @@ kill_all
 
 # Test 6: One driver and one passenger (The driver crashes and the passenger cancels the trip)
 echo -e "${CYAN}Test 6: One Driver and One Passenger (The driver crashes and the passenger fails to reach driver)${WHITE}"
+CLUSTER_SIZE=1
 boot_payment 1
 boot_driver_1 0 1.0
 boot_passenger_1_in_background 1 "(0,0)" "(75,75)"
@@ -183,6 +204,7 @@ kill_all
 
 # Test 7: One driver and two passengers (The first passenger crashes, then another passenger is accepted)
 echo -e "${CYAN}Test 7: One Driver and One Passenger (The first passenger crashes, then another passenger is accepted)${WHITE}"
+CLUSTER_SIZE=1
 boot_payment 1
 boot_driver_1 0 1.0
 boot_passenger_1_in_background 1 "(0,0)" "(75,75)"
@@ -193,11 +215,14 @@ boot_passenger 2 "(5,5)" "(20,20)"
 assert_eq 0 $? "The exit code of the passenger was not the expected one."
 kill_all
 
-# Test 8: Two drivers and one passenger (First driver crashes, the second driver accepts the hanging trip)
-echo -e "${CYAN}Test 8: Two Drivers and One Passenger (First driver crashes, the second driver accepts the hanging trip)${WHITE}"
+# Test 8: Three drivers and one passenger (First driver crashes, another driver accepts the hanging trip)
+# The two drivers left are still a majority of the cluster, so they can elect a leader and commit the trip
+echo -e "${CYAN}Test 8: Three Drivers and One Passenger (First driver crashes, another driver accepts the hanging trip)${WHITE}"
+CLUSTER_SIZE=3
 boot_payment 1
 boot_driver_1 0 1.0
 boot_driver_2 1 1.0
+boot_driver_3 2 1.0
 boot_passenger_1_in_background 1 "(0,0)" "(15,15)"
 sleep 1
 kill -9 "$DRIVER_1_BACKGROUND_PID"
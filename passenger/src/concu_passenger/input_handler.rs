@@ -1,34 +1,115 @@
-use crate::concu_passenger::utils::TripData;
-use common::utils::position::Position;
+use crate::concu_passenger::utils::{PassengerCommand, TripData};
+use common::utils::{consts::MAP_SIZE, position::Position, time::now_ms};
 use regex::Regex;
 use std::env;
 
-/// Valida y parsea los argumentos recibidos por stdin
-pub fn validate_args() -> Result<TripData, String> {
+/// Valida y parsea los argumentos recibidos por stdin.
+/// Opcionalmente, `stop=(x,y)` (una o varias veces, despues del destino) indica paradas intermedias, que se recorren en orden,
+/// `max_surge=<multiplicador>` indica el multiplicador por demanda maximo que se acepta (1 por defecto)
+/// y `pickup_in=<segundos>` reserva el viaje para dentro de esa cantidad de segundos.
+///
+/// Ademas, `id=<id> bookings` lista las reservas pendientes del pasajero y `id=<id> cancel=<reserva>`
+/// cancela una de ellas.
+pub fn validate_args() -> Result<PassengerCommand, String> {
     let args: Vec<String> = env::args().skip(1).collect();
-    let command = args.join(" ");
+
+    parse_command(&args.join(" "))
+}
+
+/// Parsea un pedido del pasajero (ver `validate_args`).
+/// Todas las posiciones (origen, destino y paradas) tienen que estar dentro del mapa.
+fn parse_command(command: &str) -> Result<PassengerCommand, String> {
+    let bookings_pattern = Regex::new(r"^id=(\d+)\s+bookings$").expect("Regex no válida");
+
+    if let Some(captures) = bookings_pattern.captures(command) {
+        let id: u32 = captures[1].parse().expect("Invalid ID number");
+        return Ok(PassengerCommand::ListBookings { id });
+    }
+
+    let cancel_pattern = Regex::new(r"^id=(\d+)\s+cancel=(\d+)$").expect("Regex no válida");
+
+    if let Some(captures) = cancel_pattern.captures(command) {
+        let id: u32 = captures[1].parse().expect("Invalid ID number");
+        let booking_id: u64 = captures[2].parse().expect("Invalid booking ID");
+        return Ok(PassengerCommand::CancelBooking { id, booking_id });
+    }
 
     let command_pattern =
-        Regex::new(r"^id=(\d+)\s+origin=\((-?\d+),(-?\d+)\)\s+dest=\((-?\d+),(-?\d+)\)$")
+        Regex::new(r"^id=(\d+)\s+origin=\((\d+),(\d+)\)\s+dest=\((\d+),(\d+)\)((?:\s+stop=\(\d+,\d+\))*)(?:\s+max_surge=(\d+(?:\.\d+)?))?(?:\s+pickup_in=(\d+))?$")
             .expect("Regex no válida");
 
-    if let Some(captures) = command_pattern.captures(&command) {
+    if let Some(captures) = command_pattern.captures(command) {
         let id: u32 = captures[1].parse().expect("Invalid ID number");
-        let origin_x: u32 = captures[2].parse().expect("Invalid origin X");
-        let origin_y: u32 = captures[3].parse().expect("Invalid origin Y ");
-        let destination_x: u32 = captures[4].parse().expect("Invalid destination X ");
-        let destination_y: u32 = captures[5].parse().expect("Invalid destination Y ");
+        let origin = parse_position(&captures[2], &captures[3], "origin")?;
+        let destination = parse_position(&captures[4], &captures[5], "destination")?;
+        let stop_pattern = Regex::new(r"stop=\((\d+),(\d+)\)").expect("Regex no válida");
+        let stops = stop_pattern
+            .captures_iter(&captures[6])
+            .map(|stop| parse_position(&stop[1], &stop[2], "stop"))
+            .collect::<Result<Vec<Position>, String>>()?;
+        let max_surge: f64 = captures
+            .get(7)
+            .map(|max_surge| max_surge.as_str().parse().expect("Invalid max surge"))
+            .unwrap_or(1.0);
+        let pickup_at: Option<u64> = captures.get(8).map(|pickup_in| {
+            let seconds: u64 = pickup_in.as_str().parse().expect("Invalid pickup time");
+            now_ms() + seconds * 1000
+        });
 
-        if origin_x == destination_x && origin_y == destination_y {
+        if origin == destination {
             return Err("You can't go to the same place you are right now!".into());
         }
 
-        Ok(TripData {
+        Ok(PassengerCommand::Trip(TripData {
             id,
-            origin: Position::new(origin_x, origin_y),
-            destination: Position::new(destination_x, destination_y),
-        })
+            origin,
+            destination,
+            stops,
+            max_surge,
+            pickup_at,
+        }))
     } else {
         Err("Invalid command format.".to_string())
     }
 }
+
+/// Parsea las coordenadas de una posicion, verificando que este dentro del mapa.
+fn parse_position(x: &str, y: &str, name: &str) -> Result<Position, String> {
+    let coordinate = |value: &str| {
+        value
+            .parse::<u32>()
+            .ok()
+            .filter(|coordinate| *coordinate < MAP_SIZE)
+            .ok_or(format!(
+                "The {} ({},{}) is outside the {}x{} map",
+                name, x, y, MAP_SIZE, MAP_SIZE
+            ))
+    };
+
+    Ok(Position::new(coordinate(x)?, coordinate(y)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trip_with_stops() {
+        let command = parse_command("id=1 origin=(0,0) dest=(10,10) stop=(5,5) stop=(99,0)");
+
+        match command {
+            Ok(PassengerCommand::Trip(trip)) => {
+                assert_eq!(trip.destination, Position::new(10, 10));
+                assert_eq!(trip.stops, vec![Position::new(5, 5), Position::new(99, 0)]);
+            }
+            _ => panic!("Trip expected"),
+        }
+    }
+
+    #[test]
+    fn test_positions_outside_the_map_are_rejected() {
+        assert!(parse_command("id=1 origin=(0,0) dest=(10,10) stop=(5000,5000)").is_err());
+        assert!(parse_command("id=1 origin=(100,0) dest=(10,10)").is_err());
+        assert!(parse_command("id=1 origin=(0,0) dest=(10,99999999999)").is_err());
+    }
+}
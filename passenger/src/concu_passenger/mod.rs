@@ -1,3 +1,3 @@
 pub mod input_handler;
 pub mod passenger;
-mod utils;
+pub(crate) mod utils;
@@ -1,17 +1,18 @@
 use rand::Rng;
 use std::{error::Error, time::Duration};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
     net::TcpStream,
+    sync::mpsc,
 };
 
-use common::utils::json_parser::{CommonMessages, TripMessages};
+use common::utils::json_parser::{CommonMessages, TripMessages, TripStatus};
+use common::utils::time::now_ms;
 
-use crate::concu_passenger::utils::TripData;
-use common::utils::consts::{
-    HOST, MAX_DRIVER_PORT, MIN_DRIVER_PORT, MIN_PASSENGER_PORT, PAYMENT_PORT,
-};
+use crate::concu_passenger::utils::{TripData, CANCEL_COMMAND};
+use common::utils::consts::{HOST, MIN_PASSENGER_PORT, PAYMENT_PORT};
 use common::utils::json_parser::{PaymentMessages, PaymentResponses};
+use common::utils::seeds::driver_seeds;
 use tokio::net::TcpListener;
 use tokio::time::timeout;
 
@@ -21,20 +22,281 @@ async fn validate_credit_card(id: u32) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-/// Realiza una solicitud de viaje al servidor de conductores
-async fn request_trip(trip_data: TripData) -> Result<(), Box<dyn Error>> {
-    request(trip_data).await?;
+/// Resultado de leer las respuestas de una conexion con un conductor
+enum DriverResponses {
+    /// El pedido fue entregado o el viaje termino
+    Done,
+    /// La conexion solo traia mensajes de progreso de la busqueda y se cerro
+    Progress,
+    /// El viaje fue cancelado a pedido del pasajero
+    Cancelled,
+}
+
+/// Realiza una solicitud de viaje al servidor de conductores.
+/// Si el viaje es para mas adelante, primero lo reserva (ver `book_trip`) y espera a que se busque un
+/// conductor (ver `wait_booked_trip`). Si ningun conductor se conecta, lo pide para el momento.
+async fn request_trip(
+    mut trip_data: TripData,
+    cancel_rx: &mut mpsc::Receiver<()>,
+) -> Result<(), Box<dyn Error>> {
+    if trip_data.pickup_at.is_some() {
+        let (booking_id, search_at) = match book_trip(&trip_data).await? {
+            Some(booking) => booking,
+            None => return Ok(()),
+        };
+
+        if !wait_booked_trip(&trip_data, booking_id, search_at, cancel_rx).await? {
+            return Ok(());
+        }
+
+        trip_data.pickup_at = None;
+    }
+
+    request(trip_data, cancel_rx).await?;
     Ok(())
 }
 
-/// Maneja el proceso de completar un viaje
+/// Lista las reservas pendientes del pasajero, segun algun conductor (ver `ask_any_driver`)
+#[tokio::main]
+pub(crate) async fn handle_bookings_list(id: u32) -> Result<(), Box<dyn Error>> {
+    let bookings = match ask_any_driver(id, &TripMessages::ListBookings {}).await? {
+        TripMessages::Bookings { bookings } => bookings,
+        _ => return Err("Bookings expected".into()),
+    };
+
+    if bookings.is_empty() {
+        log::info!("You have no upcoming bookings");
+    }
+
+    for booking in bookings {
+        log::info!(
+            "Booking {}: from {:?} to {:?} in {} seconds, for ${}",
+            booking.booking_id,
+            booking.source,
+            booking.destination,
+            booking.pickup_at.saturating_sub(now_ms()) / 1000,
+            booking.quote.amount
+        );
+    }
+
+    Ok(())
+}
+
+/// Cancela una reserva del pasajero
+#[tokio::main]
+pub(crate) async fn handle_booking_cancellation(
+    id: u32,
+    booking_id: u64,
+) -> Result<(), Box<dyn Error>> {
+    cancel_booking(id, booking_id).await
+}
+
+/// Le pide a algun conductor que cancele una reserva del pasajero (ver `ask_any_driver`)
+/// - Si el conductor confirma la cancelacion, retorna Ok
+/// - Si el conductor responde que no existe la reserva, retorna un error
+async fn cancel_booking(id: u32, booking_id: u64) -> Result<(), Box<dyn Error>> {
+    match ask_any_driver(id, &TripMessages::CancelBooking { booking_id }).await? {
+        TripMessages::TripResponse {
+            status: TripStatus::Cancelled,
+            detail,
+        } => {
+            log::info!("{}", detail);
+            Ok(())
+        }
+        TripMessages::TripResponse { detail, .. } => Err(detail.into()),
+        _ => Err("Booking cancellation expected".into()),
+    }
+}
+
+/// Itera por cada una de las direcciones de los conductores (ver `driver_seeds`) hasta poder reservar
+/// el viaje en alguno de ellos (ver `make_booking`).
+/// Retorna el id de la reserva y el momento en el que se empieza a buscar un conductor, o None si el
+/// pasajero rechazo el presupuesto.
+async fn book_trip(trip_data: &TripData) -> Result<Option<(u64, u64)>, Box<dyn Error>> {
+    let mut seeds = driver_seeds();
+    let mut rng = rand::thread_rng();
+    log::info!("Booking trip");
+
+    while !seeds.is_empty() {
+        let index = rng.gen_range(0..seeds.len());
+        let addr = seeds.remove(index);
+
+        let mut socket = match TcpStream::connect(addr).await {
+            Err(_) => continue,
+            Ok(socket) => socket,
+        };
+
+        match make_booking(trip_data, &mut socket).await {
+            Err(e) => {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                continue;
+            }
+            Ok(booking) => return Ok(booking),
+        }
+    }
+
+    Err("Oops!, I can't book a trip correctly".into())
+}
+
+/// Reserva el viaje en el servidor de conductores
+/// - Envia un mensaje de identificación
+/// - Envia un mensaje de solicitud de viaje, con la hora a la que hay que buscar al pasajero
+/// - Responde el presupuesto del viaje (ver `answer_quote`). Si lo rechaza, no reserva el viaje
+/// - Espera la confirmacion de la reserva
+async fn make_booking(
+    trip_data: &TripData,
+    socket: &mut TcpStream,
+) -> Result<Option<(u64, u64)>, Box<dyn Error>> {
+    send_identification(trip_data.id, socket).await?;
+
+    send_trip_request(socket, trip_data).await?;
+
+    if !answer_quote(trip_data, socket).await? {
+        return Ok(None);
+    }
+
+    let mut reader = BufReader::new(&mut *socket);
+    let response = wait_response(&mut reader, "Error receiving the booking".into()).await?;
+
+    match parse_trip_response(response)? {
+        TripMessages::TripResponse {
+            status:
+                TripStatus::Scheduled {
+                    booking_id,
+                    search_at,
+                },
+            detail,
+        } => {
+            log::info!("{}", detail);
+            Ok(Some((booking_id, search_at)))
+        }
+        TripMessages::TripResponse { detail, .. } => Err(detail.into()),
+        _ => Err("Booking confirmation expected".into()),
+    }
+}
+
+/// Espera el viaje reservado. Escucha conexiones desde ya, para que el conductor lo encuentre aunque la
+/// busqueda empiece un poco antes, y una vez que empieza la busqueda espera a los conductores
+/// (ver `listen_connections`).
+/// Si el pasajero cancela el viaje antes de que empiece la busqueda, se cancela la reserva.
+///
+/// Retorna si hay que volver a pedir el viaje, porque no se conecto ningun conductor.
+async fn wait_booked_trip(
+    trip_data: &TripData,
+    booking_id: u64,
+    search_at: u64,
+    cancel_rx: &mut mpsc::Receiver<()>,
+) -> Result<bool, Box<dyn Error>> {
+    let self_addr = format!("{}:{}", HOST, MIN_PASSENGER_PORT + trip_data.id);
+    let mut listener = TcpListener::bind(&self_addr)
+        .await
+        .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()))?;
+
+    log::info!("My addr is {}", self_addr);
+
+    let wait = Duration::from_millis(search_at.saturating_sub(now_ms()));
+    log::info!(
+        "Waiting {} seconds for the search of a driver, write '{}' to cancel the booking",
+        wait.as_secs(),
+        CANCEL_COMMAND
+    );
+
+    tokio::select! {
+        _ = tokio::time::sleep(wait) => (),
+        Some(()) = cancel_rx.recv() => {
+            cancel_booking(trip_data.id, booking_id).await?;
+            return Ok(false);
+        }
+    }
+
+    match listen_connections(&mut listener, trip_data, cancel_rx).await {
+        Err(e) => {
+            log::error!("{}", e.to_string());
+            Ok(true)
+        }
+        Ok(Ok(_)) => Ok(false),
+        Ok(Err(e)) => {
+            log::error!("{}", e.to_string());
+            Ok(false)
+        }
+    }
+}
+
+/// Se conecta a algun conductor (ver `driver_seeds`), se identifica, le envia el mensaje dado y
+/// retorna su respuesta. Si ningun conductor responde, retorna un error.
+async fn ask_any_driver(id: u32, message: &TripMessages) -> Result<TripMessages, Box<dyn Error>> {
+    let mut seeds = driver_seeds();
+    let mut rng = rand::thread_rng();
+    let request = serde_json::to_string(message)?;
+
+    while !seeds.is_empty() {
+        let index = rng.gen_range(0..seeds.len());
+        let addr = seeds.remove(index);
+
+        let mut socket = match TcpStream::connect(addr).await {
+            Err(_) => continue,
+            Ok(socket) => socket,
+        };
+
+        if let Err(e) = send_identification(id, &mut socket).await {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            continue;
+        }
+
+        if let Err(e) = socket.write_all((request.clone() + "\n").as_bytes()).await {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            continue;
+        }
+
+        let mut reader = BufReader::new(&mut socket);
+
+        match wait_response(&mut reader, "Error receiving the driver response".into()).await {
+            Ok(response) => return Ok(parse_trip_response(response)?),
+            Err(e) => {
+                log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                continue;
+            }
+        }
+    }
+
+    Err("Oops!, I can't reach any driver".into())
+}
+
+/// Maneja el proceso de completar un viaje.
+/// Mientras tanto, el pasajero puede cancelar el viaje escribiendo 'cancel' en la entrada estandar.
 #[tokio::main]
 pub(crate) async fn handle_complete_trip(trip_data: TripData) -> Result<(), Box<dyn Error>> {
     validate_credit_card(trip_data.id).await?;
-    request_trip(trip_data).await?;
+    let mut cancel_rx = watch_cancellation();
+    request_trip(trip_data, &mut cancel_rx).await?;
     Ok(())
 }
 
+/// Lanza un hilo que lee la entrada estandar y avisa por el canal devuelto cada vez que el pasajero
+/// pide cancelar el viaje. Se usa un hilo aparte porque la lectura bloqueante de la entrada estandar
+/// no se puede interrumpir, y asi no demora la salida del programa.
+fn watch_cancellation() -> mpsc::Receiver<()> {
+    let (cancel_tx, cancel_rx) = mpsc::channel(1);
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            if line.trim() == CANCEL_COMMAND && cancel_tx.blocking_send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    cancel_rx
+}
+
+/// Espera a que el pasajero pida cancelar el viaje. Si no hay canal de cancelacion, nunca termina.
+async fn cancellation(cancel_rx: &mut Option<&mut mpsc::Receiver<()>>) -> Option<()> {
+    match cancel_rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
 /// Valida la tarjeta de crédito del pasajero.
 /// Se conecta al servidor de pagos, envía un mensaje de autenticación y
 /// espera la respuesta del servidor
@@ -135,22 +397,36 @@ async fn bind_listener(socket: &mut TcpStream, id: u32) -> Result<TcpListener, S
 /// Si la conexión es exitosa, se espera la respuesta del conductor
 /// - Si la respuesta es afirmativa, el viaje fue completado y sale del loop
 /// - Si la respuesta es negativa, el viaje fue rechazado
+/// - Si la conexion solo informaba el progreso de la busqueda, se sigue esperando
 /// - Si no hay respuesta, se retorna un error
 ///
 /// Si la conexión falla, se retorna un error.
-
-async fn listen_connections(listener: &mut TcpListener) -> Result<Result<(), String>, String> {
+///
+/// Si el pasajero cancela el viaje mientras se busca un conductor, se le pide la cancelacion a algun
+/// conductor (ver `request_cancellation`). Si ya hay un conductor conectado, se le pide a el.
+async fn listen_connections(
+    listener: &mut TcpListener,
+    trip_data: &TripData,
+    cancel_rx: &mut mpsc::Receiver<()>,
+) -> Result<Result<(), String>, String> {
     loop {
-        let result = timeout(Duration::from_secs(10), listener.accept()).await;
+        let result = tokio::select! {
+            result = timeout(Duration::from_secs(10), listener.accept()) => result,
+            Some(()) = cancel_rx.recv() => {
+                return Ok(request_cancellation(trip_data).await);
+            }
+        };
 
         match result {
             Ok(Ok((mut socket, _))) => {
                 log::info!("Connection accepted");
-                match wait_driver_responses(&mut socket).await {
-                    Ok(Ok(_)) => {
+                match wait_driver_responses(&mut socket, Some(&mut *cancel_rx)).await {
+                    Ok(Ok(DriverResponses::Done)) => {
                         log::info!("We arrived at your destination!");
                         break;
                     }
+                    Ok(Ok(DriverResponses::Progress)) => continue,
+                    Ok(Ok(DriverResponses::Cancelled)) => break,
                     Ok(Err(e)) => return Ok(Err(e)),
                     Err(_broken_pipe) => {
                         return Err("Driver disconnected!, requesting trip again".into())
@@ -173,40 +449,91 @@ async fn listen_connections(listener: &mut TcpListener) -> Result<Result<(), Str
 /// Realiza una solicitud de viaje al servidor de conductores
 /// - Envia un mensaje de identificación
 /// - Envia un mensaje de solicitud de viaje
+/// - Responde el presupuesto del viaje (ver `answer_quote`). Si lo rechaza, no continua con el pedido
 /// - Crea un listener y envia un mensaje 'Listening'
 /// - Espera las respuestas de los conductores
 async fn make_request(
     trip_data: &TripData,
     socket: &mut TcpStream,
-) -> Result<TcpListener, Box<dyn Error>> {
-    send_identification(&trip_data, socket).await?;
+) -> Result<Option<TcpListener>, Box<dyn Error>> {
+    send_identification(trip_data.id, socket).await?;
 
     send_trip_request(socket, &trip_data).await?;
 
+    if !answer_quote(trip_data, socket).await? {
+        return Ok(None);
+    }
+
     let listener = bind_listener(socket, trip_data.id).await?;
 
-    wait_driver_responses(socket).await??;
+    wait_driver_responses(socket, None).await??;
 
-    Ok(listener)
+    Ok(Some(listener))
+}
+
+/// Espera el presupuesto del viaje y le responde al conductor si lo acepta. Se acepta si el multiplicador
+/// por demanda no supera el maximo que indico el pasajero con `max_surge` (1 por defecto, es decir,
+/// solo se aceptan los presupuestos sin multiplicador).
+async fn answer_quote(
+    trip_data: &TripData,
+    socket: &mut TcpStream,
+) -> Result<bool, Box<dyn Error>> {
+    let mut reader = BufReader::new(&mut *socket);
+    let response = wait_response(&mut reader, "Error receiving the trip quote".into()).await?;
+
+    let quote = match parse_trip_response(response)? {
+        TripMessages::Quote { quote } => quote,
+        _ => return Err("Trip quote expected".into()),
+    };
+
+    let accepted = quote.surge <= trip_data.max_surge;
+
+    match accepted {
+        true => log::info!(
+            "Accepted the quote of ${} for {} blocks (x{} surge)",
+            quote.amount,
+            quote.distance,
+            quote.surge
+        ),
+        false => log::warn!(
+            "Rejected the quote of ${} for {} blocks, the x{} surge is above your maximum of x{}",
+            quote.amount,
+            quote.distance,
+            quote.surge,
+            trip_data.max_surge
+        ),
+    }
+
+    let answer = serde_json::to_string(&TripMessages::QuoteAnswer { accepted })?;
+
+    socket
+        .write_all((answer + "\n").as_bytes())
+        .await
+        .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()))?;
+
+    Ok(accepted)
 }
 
-/// Itera por cada uno de los puertos de los conductores, intentando conectarse a cada uno de ellos
-/// hasta que se logre una conexión exitosa o hasta que se agoten los puertos.
+/// Itera por cada una de las direcciones de los conductores (ver `driver_seeds`), intentando conectarse
+/// a cada uno de ellos hasta que se logre una conexión exitosa o hasta que se agoten las direcciones.
 /// - Si la conexión es exitosa, envía un mensaje de solicitud de viaje
 /// - Si la conexión falla, intenta conectarse a otro conductor
 /// - Si no hay conductores disponibles, retorna un error
 ///
 /// Una vez que se logra una conexión exitosa, se espera que un conductor se contacte con el pasajero  abriendo una nueva conexión TCP
-async fn request(trip_data: TripData) -> Result<(), Box<dyn Error>> {
-    let mut ports: Vec<u32> = (MIN_DRIVER_PORT..=MAX_DRIVER_PORT).collect();
+async fn request(
+    trip_data: TripData,
+    cancel_rx: &mut mpsc::Receiver<()>,
+) -> Result<(), Box<dyn Error>> {
+    let mut seeds = driver_seeds();
     let mut rng = rand::thread_rng();
     log::info!("Requesting trip");
 
     let mut ret: Result<(), Box<dyn Error>> = Err("Oops!, I can't request a trip correctly".into());
 
-    while !ports.is_empty() {
-        let index = rng.gen_range(0..ports.len());
-        let addr = format!("{}:{}", HOST, ports.remove(index));
+    while !seeds.is_empty() {
+        let index = rng.gen_range(0..seeds.len());
+        let addr = seeds.remove(index);
 
         let mut socket = match TcpStream::connect(addr.clone()).await {
             Err(_) => continue,
@@ -218,13 +545,18 @@ async fn request(trip_data: TripData) -> Result<(), Box<dyn Error>> {
                 log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
                 continue;
             }
-            Ok(mut listener) => {
-                let listen_result = async move { listen_connections(&mut listener).await }.await;
+            Ok(None) => {
+                log::info!("The trip was not requested");
+                ret = Ok(());
+                break;
+            }
+            Ok(Some(mut listener)) => {
+                let listen_result = listen_connections(&mut listener, &trip_data, cancel_rx).await;
 
                 match listen_result {
                     Err(e) => {
                         log::error!("{}", e.to_string());
-                        ports = (MIN_DRIVER_PORT..=MAX_DRIVER_PORT).collect();
+                        seeds = driver_seeds();
                         continue;
                     } // Broken pipe
                     Ok(Ok(_)) => {
@@ -251,50 +583,84 @@ async fn request(trip_data: TripData) -> Result<(), Box<dyn Error>> {
 ///    - Que el viaje fue rechazado, se retorna un error
 ///    -
 /// - Si la respuesta es negativa, el viaje fue rechazado y retorna un error
+/// - Si la respuesta confirma la cancelacion del viaje, se sale del loop
 /// - Si no hay respuesta, se retorna un error
-/// - Si la conexión falla, se retorna un error
+/// - Si la conexión falla, se retorna un error, salvo que solo se hayan recibido mensajes
+///   de progreso de la busqueda
 ///
-async fn wait_driver_responses(socket: &mut TcpStream) -> Result<Result<(), String>, String> {
-    let mut reader = BufReader::new(socket);
+/// Si se recibe un pedido de cancelacion por `cancel_rx`, se le envia al conductor y se sigue esperando
+/// su confirmacion.
+async fn wait_driver_responses(
+    socket: &mut TcpStream,
+    mut cancel_rx: Option<&mut mpsc::Receiver<()>>,
+) -> Result<Result<DriverResponses, String>, String> {
+    let (r, mut w) = socket.split();
+    let mut lines = BufReader::new(r).lines();
 
     let mut request_delivered = false;
+    let mut only_info = false;
 
     loop {
-        let string_response =
-            wait_response(&mut reader, "Error receiving trip response".into()).await;
+        let line = tokio::select! {
+            line = lines.next_line() => line,
+            Some(()) = cancellation(&mut cancel_rx) => {
+                send_cancel_request(&mut w).await.map_err(|e| e.to_string())?;
+                continue;
+            }
+        };
 
-        if let Err(res) = string_response {
-            if request_delivered {
-                return Ok(Ok(()));
-            } else {
-                return Err(res.to_string());
+        let string_response = match line {
+            Ok(Some(line)) => line,
+            Ok(None) | Err(_) => {
+                if let Err(e) = line {
+                    log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+                }
+
+                if request_delivered {
+                    return Ok(Ok(DriverResponses::Done));
+                } else if only_info {
+                    return Ok(Ok(DriverResponses::Progress));
+                } else {
+                    return Err("Error receiving trip response".into());
+                }
             }
-        }
+        };
 
-        let response = match parse_trip_response(string_response.map_err(|e| {
-            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
-            e.to_string()
-        })?) {
+        let response = match parse_trip_response(string_response) {
             Ok(value) => value,
             Err(value) => return Err(value),
         };
 
         match response {
             TripMessages::TripResponse { status, detail } => match status {
-                common::utils::json_parser::TripStatus::Success => {
+                TripStatus::Success => {
                     log::info!("{}", detail);
-                    return Ok(Ok(()));
+                    return Ok(Ok(DriverResponses::Done));
                 }
-                common::utils::json_parser::TripStatus::Info => {
+                TripStatus::Info => {
                     log::info!("{}", detail);
+                    only_info = true;
                 }
-                common::utils::json_parser::TripStatus::Error => {
+                TripStatus::Error => {
                     return Ok(Err(detail));
                 }
-                common::utils::json_parser::TripStatus::RequestDelivered => {
+                TripStatus::RequestDelivered { quote } => {
                     log::info!("{}", detail);
+                    log::info!(
+                        "The trip of {} blocks costs ${}, plus the time the driver waits for you",
+                        quote.distance,
+                        quote.amount
+                    );
                     request_delivered = true;
                 }
+                TripStatus::Cancelled => {
+                    log::info!("{}", detail);
+                    return Ok(Ok(DriverResponses::Cancelled));
+                }
+                TripStatus::Scheduled { .. } => {
+                    log::error!("Unexpected booking confirmation");
+                    break;
+                }
             },
             _ => {
                 log::error!("Invalid response");
@@ -302,7 +668,43 @@ async fn wait_driver_responses(socket: &mut TcpStream) -> Result<Result<(), Stri
             }
         }
     }
-    Ok(Ok(()))
+    Ok(Ok(DriverResponses::Done))
+}
+
+/// Le pide a algun conductor (ver `driver_seeds`) que cancele el viaje del pasajero, que todavia no
+/// tiene un conductor conectado, y espera la confirmacion.
+/// - Si un conductor confirma la cancelacion, retorna Ok
+/// - Si ningun conductor la confirma, retorna un error
+async fn request_cancellation(trip_data: &TripData) -> Result<(), String> {
+    let mut seeds = driver_seeds();
+    let mut rng = rand::thread_rng();
+    log::info!("Cancelling the trip");
+
+    while !seeds.is_empty() {
+        let index = rng.gen_range(0..seeds.len());
+        let addr = seeds.remove(index);
+
+        let mut socket = match TcpStream::connect(addr).await {
+            Err(_) => continue,
+            Ok(socket) => socket,
+        };
+
+        if let Err(e) = send_identification(trip_data.id, &mut socket).await {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            continue;
+        }
+
+        if let Err(e) = send_cancel_request(&mut socket).await {
+            log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string());
+            continue;
+        }
+
+        if let Ok(Ok(DriverResponses::Cancelled)) = wait_driver_responses(&mut socket, None).await {
+            return Ok(());
+        }
+    }
+
+    Err("Oops!, I can't cancel the trip".into())
 }
 
 /// Parsea la respuesta del servidor de conductores
@@ -349,6 +751,8 @@ async fn send_trip_request(
     let request = serde_json::to_string(&TripMessages::TripRequest {
         source: request.origin,
         destination: request.destination,
+        stops: request.stops.clone(),
+        pickup_at: request.pickup_at,
     })?;
 
     socket
@@ -361,6 +765,20 @@ async fn send_trip_request(
     Ok(())
 }
 
+/// Le pide al conductor que cancele el viaje
+async fn send_cancel_request(socket: &mut (impl AsyncWrite + Unpin)) -> Result<(), Box<dyn Error>> {
+    let request = serde_json::to_string(&TripMessages::Cancel {})?;
+
+    socket
+        .write_all((request + "\n").as_bytes())
+        .await
+        .inspect_err(|e| log::error!("{}:{}, {}", std::file!(), std::line!(), e.to_string()))?;
+
+    log::info!("Cancel request sent!");
+
+    Ok(())
+}
+
 /// Notifica al conductor que ya esta escuchando nuevas conexiones
 async fn send_listening_notification(socket: &mut TcpStream) -> Result<(), Box<dyn Error>> {
     let request = serde_json::to_string(&TripMessages::Listening {})?;
@@ -376,13 +794,11 @@ async fn send_listening_notification(socket: &mut TcpStream) -> Result<(), Box<d
 }
 
 /// Envia un mensaje de identificación, a través del socket, al servidor de conductores
-async fn send_identification(
-    trip_data: &TripData,
-    socket: &mut TcpStream,
-) -> Result<(), Box<dyn Error>> {
+async fn send_identification(id: u32, socket: &mut TcpStream) -> Result<(), Box<dyn Error>> {
     let identification = serde_json::to_string(&CommonMessages::Identification {
-        id: trip_data.id,
+        id,
         type_: 'P',
+        addr: None,
     })?;
 
     socket.write_all((identification + "\n").as_bytes()).await?;
@@ -6,4 +6,25 @@ pub struct TripData {
     pub id: u32,
     pub origin: Position,
     pub destination: Position,
+    /// Paradas intermedias del viaje, en orden
+    pub stops: Vec<Position>,
+    /// Multiplicador por demanda maximo que acepta el pasajero
+    pub max_surge: f64,
+    /// Momento en el que el pasajero quiere que lo busquen, en milisegundos desde UNIX_EPOCH.
+    /// Si no esta, el viaje se pide para el momento
+    pub pickup_at: Option<u64>,
 }
+
+/// Pedido del pasajero, segun sus argumentos
+#[derive(Debug)]
+pub enum PassengerCommand {
+    /// Pedir (o reservar) un viaje
+    Trip(TripData),
+    /// Listar las reservas pendientes del pasajero
+    ListBookings { id: u32 },
+    /// Cancelar una reserva del pasajero
+    CancelBooking { id: u32, booking_id: u64 },
+}
+
+/// Comando que el pasajero escribe en la entrada estandar para cancelar su viaje
+pub const CANCEL_COMMAND: &str = "cancel";
@@ -1,16 +1,23 @@
 use concu_passenger::input_handler;
-use concu_passenger::passenger::handle_complete_trip;
+use concu_passenger::passenger::{
+    handle_booking_cancellation, handle_bookings_list, handle_complete_trip,
+};
+use concu_passenger::utils::PassengerCommand;
 use std::error::Error;
 
 pub mod concu_passenger;
 
 pub fn run() -> Result<(), Box<dyn Error>> {
     match input_handler::validate_args() {
-        Ok(trip_data) => {
+        Ok(PassengerCommand::Trip(trip_data)) => {
             log::info!("Validated trip data: {:?}", trip_data);
             handle_complete_trip(trip_data)?;
             Ok(())
         }
+        Ok(PassengerCommand::ListBookings { id }) => handle_bookings_list(id),
+        Ok(PassengerCommand::CancelBooking { id, booking_id }) => {
+            handle_booking_cancellation(id, booking_id)
+        }
         Err(error) => {
             eprintln!("{}", error);
             Err(Box::from(error))
@@ -14,8 +14,8 @@ pub(crate) async fn handle_payments() -> Result<(), Box<dyn Error>> {
 
 /// Crea un listener en el puerto PAYMENT_PORT y se se queda escuchando mensajes, cuando recibe un mensaje
 /// lo parsea y dependiendo del tipo de mensaje, si es AuthPayment, lo agrega a la lista de pasajeros autorizados
-/// si es CollectPayment, verifica si el pasajero esta en la lista de pasajeros autorizados y responde con un mensaje
-/// a traves del socket
+/// si es CollectPayment o CollectCancellationFee, verifica si el pasajero esta en la lista de pasajeros autorizados
+/// y responde con un mensaje a traves del socket
 async fn handle() -> Result<(), Box<dyn Error>> {
     let mut auth_passengers = Vec::new();
 
@@ -68,26 +68,49 @@ async fn handle() -> Result<(), Box<dyn Error>> {
             PaymentMessages::CollectPayment {
                 driver_id,
                 passenger_id,
+                amount,
             } => {
-                handle_collect_message(&mut auth_passengers, &mut socket, driver_id, &passenger_id)
-                    .await?;
+                handle_collect_message(
+                    &mut auth_passengers,
+                    &mut socket,
+                    driver_id,
+                    &passenger_id,
+                    amount,
+                )
+                .await?;
+            }
+            PaymentMessages::CollectCancellationFee {
+                driver_id,
+                passenger_id,
+                amount,
+            } => {
+                handle_cancellation_fee_message(
+                    &auth_passengers,
+                    &mut socket,
+                    driver_id,
+                    &passenger_id,
+                    amount,
+                )
+                .await?;
             }
         }
     }
 }
 
-
-/// Verifica si el pasajero esta en la lista de pasajeros autorizados y responde con un mensaje a traves del socket
+/// Verifica si el pasajero esta en la lista de pasajeros autorizados para cobrarle el monto del viaje y responde
+/// con un mensaje a traves del socket
 async fn handle_collect_message(
     auth_passengers: &mut Vec<u32>,
     socket: &mut TcpStream,
     driver_id: u32,
     passenger_id: &u32,
+    amount: u32,
 ) -> Result<(), Box<dyn Error>> {
     let response_message = if auth_passengers.contains(passenger_id) {
         log::debug!(
-            "Driver {} collected payment from passenger {}",
+            "Driver {} collected ${} from passenger {}",
             driver_id,
+            amount,
             passenger_id
         );
 
@@ -97,8 +120,9 @@ async fn handle_collect_message(
         }
     } else {
         log::debug!(
-            "Driver {} could not collect payment from passenger {}",
+            "Driver {} could not collect ${} from passenger {}",
             driver_id,
+            amount,
             passenger_id
         );
 
@@ -113,6 +137,41 @@ async fn handle_collect_message(
     Ok(())
 }
 
+/// Verifica si el pasajero que cancelo su viaje esta en la lista de pasajeros autorizados para cobrarle
+/// la multa por cancelacion y responde con un mensaje a traves del socket
+async fn handle_cancellation_fee_message(
+    auth_passengers: &[u32],
+    socket: &mut TcpStream,
+    driver_id: u32,
+    passenger_id: &u32,
+    amount: u32,
+) -> Result<(), Box<dyn Error>> {
+    let response = auth_passengers.contains(passenger_id);
+
+    match response {
+        true => log::debug!(
+            "Driver {} collected the cancellation fee of ${} from passenger {}",
+            driver_id,
+            amount,
+            passenger_id
+        ),
+        false => log::debug!(
+            "Driver {} could not collect the cancellation fee of ${} from passenger {}",
+            driver_id,
+            amount,
+            passenger_id
+        ),
+    }
+
+    let response_message = PaymentResponses::CollectCancellationFee {
+        passenger_id: *passenger_id,
+        response,
+    };
+
+    let response_json = serialize_response_message(&response_message)?;
+    send_response(socket, response_json).await;
+    Ok(())
+}
 
 /// Agrega a la lista de pasajeros autorizados a un pasajero con una probabilidad  y envia un mensaje exitoso o fallido según
 /// la probabilidad a través socket
@@ -153,7 +212,6 @@ async fn handle_auth_message(
     Ok(())
 }
 
-
 /// Envia un una respuesta a través del socket
 async fn send_response(socket: &mut TcpStream, response_json: String) {
     if let Err(e) = socket.write_all((response_json + "\n").as_bytes()).await {